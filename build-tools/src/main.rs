@@ -552,7 +552,12 @@ fn run() -> Result<(), Error> {
             let database_url = "edit-server/edit.sqlite3";
             if !Path::new(database_url).exists() {
                 eprintln!("Building database on first startup...");
+            } else {
+                println!("Database path: edit-server/edit.sqlite3");
+            }
 
+            // Bring new and existing databases up to date.
+            {
                 use migrations_internals as migrations;
                 use std::io::stdout;
 
@@ -563,8 +568,6 @@ fn run() -> Result<(), Error> {
                     Path::new("edit-server/migrations"),
                     &mut stdout(),
                 )?;
-            } else {
-                println!("Database path: edit-server/edit.sqlite3");
             }
 
             eprintln!("Starting server...");
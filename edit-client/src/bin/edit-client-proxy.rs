@@ -201,7 +201,6 @@ fn spawn_sync_connection(
 }
 
 fn setup_client(
-    name: &str,
     page_id: &str,
     out: Arc<Mutex<ws::Sender>>,
     ws_port: u16,
@@ -223,7 +222,7 @@ fn setup_client(
     spawn_send_to_client(rx_client, out);
 
     let mut state = Rc::new(RefCell::new(Client {
-            client_doc: ClientDoc::new(name.to_owned()),
+            client_doc: ClientDoc::new(None),
             last_controls: None,
             last_caret_state: None,
 
@@ -294,7 +293,7 @@ impl SimpleSocket for ProxySocket {
     ) -> Result<ProxySocket, Error> {
         let page_id = url[1..].to_string();
         let (alive, monkey, tx_task, tx_sync) =
-            setup_client("$$$$$$", &page_id, out.clone(), ws_port);
+            setup_client(&page_id, out.clone(), ws_port);
 
         Ok(ProxySocket {
             alive,
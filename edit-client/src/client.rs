@@ -9,10 +9,12 @@ use crate::walkers::Pos;
 use edit_common::{
     commands::*,
     doc_as_html,
+    html::html_to_doc,
 };
 use failure::Error;
 use oatie::doc::*;
 use oatie::rtf::*;
+use oatie::stepper::Program;
 use oatie::validate::validate_doc;
 use std::char::from_u32;
 use std::sync::atomic::{
@@ -123,6 +125,14 @@ fn key_handlers<C: ClientController>() -> Vec<KeyHandler<C>> {
             false,
             Box::new(|client| client.client_op(|doc| split_block(doc, false))),
         ),
+        // cmd + enter
+        KeyHandler(
+            13,
+            true,
+            false,
+            false,
+            Box::new(|client| client.client_op(|doc| toggle_task(doc))),
+        ),
         // enter
         KeyHandler(
             13,
@@ -139,7 +149,27 @@ fn key_handlers<C: ClientController>() -> Vec<KeyHandler<C>> {
             false,
             false,
             false,
-            Box::new(|client| client.client_op(|doc| toggle_list(doc))),
+            Box::new(|client| {
+                if in_table(client) {
+                    client.client_op(|doc| caret_cell_move(doc, true))
+                } else {
+                    client.client_op(|doc| indent_list(doc))
+                }
+            }),
+        ),
+        // shift + tab
+        KeyHandler(
+            9,
+            false,
+            true,
+            false,
+            Box::new(|client| {
+                if in_table(client) {
+                    client.client_op(|doc| caret_cell_move(doc, false))
+                } else {
+                    client.client_op(|doc| outdent_list(doc))
+                }
+            }),
         ),
         // opt + left
         KeyHandler(
@@ -165,9 +195,100 @@ fn key_handlers<C: ClientController>() -> Vec<KeyHandler<C>> {
             false,
             Box::new(|client| client.client_op(|doc| caret_select_all(doc))),
         ),
+        // cmd + e
+        KeyHandler(
+            69,
+            true,
+            false,
+            false,
+            Box::new(|client| toggle_style(client, RtfStyle::Code)),
+        ),
+        // cmd + shift + x
+        KeyHandler(
+            88,
+            true,
+            true,
+            false,
+            Box::new(|client| toggle_style(client, RtfStyle::Strikethrough)),
+        ),
+        // cmd + k
+        KeyHandler(
+            75,
+            true,
+            false,
+            false,
+            Box::new(|client| prompt_link(client)),
+        ),
+        // cmd + z
+        KeyHandler(
+            90,
+            true,
+            false,
+            false,
+            Box::new(|client| client.client_undo(false)),
+        ),
+        // cmd + shift + z
+        KeyHandler(
+            90,
+            true,
+            true,
+            false,
+            Box::new(|client| client.client_undo(true)),
+        ),
     ]
 }
 
+/// Adds a style to the selected text, or removes it if the selection already
+/// has it.
+fn toggle_style<C: ClientController>(client: &mut C, style: RtfStyle) -> Result<(), Error> {
+    let has_style = client
+        .state()
+        .last_caret_state
+        .as_ref()
+        .map(|x| x.styles.contains(&style))
+        .unwrap_or(false);
+    client.client_op(|doc| {
+        if has_style {
+            remove_styles(doc, StyleSet::from(hashset![style.clone()]))
+        } else {
+            apply_style(doc, style.clone(), None)
+        }
+    })
+}
+
+/// Whether the caret was last in a table cell, where tab moves between
+/// cells rather than indenting.
+fn in_table<C: ClientController>(client: &mut C) -> bool {
+    client
+        .state()
+        .last_caret_state
+        .as_ref()
+        .map(|x| x.in_table)
+        .unwrap_or(false)
+}
+
+/// Asks the frontend for the URL to link the selected text to, suggesting
+/// the URL of any link already in the selection.
+fn prompt_link<C: ClientController>(client: &mut C) -> Result<(), Error> {
+    let url = client
+        .state()
+        .last_caret_state
+        .as_ref()
+        .and_then(|x| {
+            StyleSet::from(x.styles.clone())
+                .link()
+                .map(|x| x.to_string())
+        })
+        .unwrap_or_default();
+    client.send_frontend(&FrontendCommand::PromptString(
+        "Link URL".to_string(),
+        url,
+        ControllerCommand::Link {
+            url: "".to_string(),
+        },
+    ))
+}
+
 pub fn button_handlers<C: ClientController>(
     state: Option<CaretState>,
 ) -> (Vec<Box<dyn Fn(&mut C) -> Result<(), Error>>>, Vec<Ui>) {
@@ -188,8 +309,22 @@ pub fn button_handlers<C: ClientController>(
         .as_ref()
         .map(|x| x.styles.contains(&RtfStyle::Italic))
         .unwrap_or(false);
+    let is_code = state
+        .as_ref()
+        .map(|x| x.styles.contains(&RtfStyle::Code))
+        .unwrap_or(false);
+    let is_strikethrough = state
+        .as_ref()
+        .map(|x| x.styles.contains(&RtfStyle::Strikethrough))
+        .unwrap_or(false);
+    let is_link = state
+        .as_ref()
+        .map(|x| StyleSet::from(x.styles.clone()).link().is_some())
+        .unwrap_or(false);
+
+    let in_table = state.as_ref().map(|x| x.in_table).unwrap_or(false);
 
-    let ui = vec![
+    let mut ui = vec![
         Ui::ButtonGroup(vec![
             Ui::Button(
                 "Text".to_string(),
@@ -229,7 +364,7 @@ pub fn button_handlers<C: ClientController>(
             ),
             Ui::Button(
                 "Code".to_string(),
-                callback!(|client| client.client_op(|doc| replace_block(doc, Attrs::Code))),
+                callback!(|client| client.client_op(|doc| replace_block(doc, Attrs::Code(None)))),
                 state.as_ref().map(|x| x.block == "pre").unwrap_or(false),
             ),
             Ui::Button(
@@ -243,11 +378,31 @@ pub fn button_handlers<C: ClientController>(
             callback!(|client| client.client_op(|doc| toggle_list(doc))),
             state.as_ref().map(|x| x.in_list).unwrap_or(false),
         ),
+        Ui::Button(
+            "Numbered".to_string(),
+            callback!(|client| client.client_op(|doc| toggle_ordered_list(doc))),
+            state.as_ref().map(|x| x.in_ordered_list).unwrap_or(false),
+        ),
+        Ui::Button(
+            "Tasks".to_string(),
+            callback!(|client| client.client_op(|doc| toggle_task_list(doc))),
+            state.as_ref().map(|x| x.in_task_list).unwrap_or(false),
+        ),
+        Ui::Button(
+            "Quote".to_string(),
+            callback!(|client| client.client_op(|doc| toggle_quote(doc))),
+            state.as_ref().map(|x| x.in_quote).unwrap_or(false),
+        ),
         Ui::Button(
             "HR".to_string(),
             callback!(|client| client.client_op(|doc| split_block(doc, true))),
             false,
         ),
+        Ui::Button(
+            "Table".to_string(),
+            callback!(|client| client.client_op(|doc| insert_table(doc))),
+            in_table,
+        ),
         Ui::ButtonGroup(vec![
             Ui::Button(
                 "Bold".to_string(),
@@ -267,9 +422,50 @@ pub fn button_handlers<C: ClientController>(
                 })),
                 is_italic,
             ),
+            Ui::Button(
+                "Inline Code".to_string(),
+                callback!(|client| toggle_style(client, RtfStyle::Code)),
+                is_code,
+            ),
+            Ui::Button(
+                "Strikethrough".to_string(),
+                callback!(|client| toggle_style(client, RtfStyle::Strikethrough)),
+                is_strikethrough,
+            ),
+            Ui::Button(
+                "Link".to_string(),
+                callback!(|client| prompt_link(client)),
+                is_link,
+            ),
         ]),
     ];
 
+    // Table editing buttons only show up in tables.
+    if in_table {
+        ui.push(Ui::ButtonGroup(vec![
+            Ui::Button(
+                "Add Row".to_string(),
+                callback!(|client| client.client_op(|doc| add_table_row(doc))),
+                false,
+            ),
+            Ui::Button(
+                "Remove Row".to_string(),
+                callback!(|client| client.client_op(|doc| remove_table_row(doc))),
+                false,
+            ),
+            Ui::Button(
+                "Add Column".to_string(),
+                callback!(|client| client.client_op(|doc| add_table_column(doc))),
+                false,
+            ),
+            Ui::Button(
+                "Remove Column".to_string(),
+                callback!(|client| client.client_op(|doc| remove_table_column(doc))),
+                false,
+            ),
+        ]));
+    }
+
     (callbacks, ui)
 }
 
@@ -322,6 +518,23 @@ fn controller_command<C: ClientController>(
         ControllerCommand::InsertText { text } => {
             client.client_op(|doc| add_string(doc, &text).map(|ctx| ctx.result()))?;
         }
+        ControllerCommand::PasteHtml { html } => {
+            let fragment = html_to_doc(&html)?;
+            client.client_op(|doc| paste(doc, &fragment))?;
+        }
+        ControllerCommand::Link { url } => {
+            // An empty URL removes the link.
+            client.client_op(|doc| {
+                set_link(
+                    doc,
+                    if url.is_empty() {
+                        None
+                    } else {
+                        Some(url.clone())
+                    },
+                )
+            })?;
+        }
         ControllerCommand::RandomTarget { .. } => {
             // This should never happen! We rewrite RandomTarget beforehand in
             // the method handle_task.
@@ -351,6 +564,9 @@ fn controller_command<C: ClientController>(
         ControllerCommand::CursorSelectWord { focus } => {
             client.client_op(|ctx| caret_word_select(&ctx, &focus))?;
         }
+        ControllerCommand::ToggleTask { curspan } => {
+            client.client_op(|ctx| toggle_task_at(&ctx, &curspan))?;
+        }
         ControllerCommand::Monkey { enabled: setting } => {
             // console_log!("received monkey setting: {:?}", setting);
             client.state().monkey.store(setting, Ordering::Relaxed);
@@ -406,9 +622,6 @@ pub trait ClientController {
         };
     }
 
-    // TODO can we catch_unwind inside handle task so we can add our own
-    // "TASK: data" dump into the error payload? So then it's easy to
-    // corrolate with the logs.
     fn handle_task(&mut self, mut value: Task) -> Result<(), Error>
     where
         Self: Sized,
@@ -425,7 +638,9 @@ pub trait ClientController {
         // TODO Also is it possible to correct the use of AssertUnwindSafe? So it's correct?
         let res = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
             move || -> Result<(), Error> {
-                let delay_log = self.state().client_doc.client_id == "$$$$$$";
+                // Tasks from before the server gave us an id are logged once
+                // they've run.
+                let delay_log = self.state().client_doc.client_id.is_none();
 
                 // Rewrite random targets here.
                 if let Task::ControllerCommand(ControllerCommand::RandomTarget { position: pos }) =
@@ -443,16 +658,13 @@ pub trait ClientController {
                 }
 
                 if !delay_log {
-                    log_wasm!(Task(
-                        self.state().client_doc.client_id.clone(),
-                        value.clone()
-                    ));
+                    log_wasm!(Task(self.assigned_client_id()?, value.clone()));
                 }
 
                 match value.clone() {
                     // Handle all commands from Frontend.
                     Task::ControllerCommand(command) => {
-                        if self.state().client_doc.client_id == "$$$$$$" {
+                        if self.state().client_doc.client_id.is_none() {
                             println!("FRONTEND COMMAND ARRIVED TOO EARLY");
                             return Ok(());
                         }
@@ -461,14 +673,21 @@ pub trait ClientController {
                     }
 
                     // Server sent the client the initial document.
-                    Task::ClientCommand(ClientCommand::Init(new_client_id, doc_span, version)) => {
-                        self.state().client_doc.client_id = new_client_id.clone();
-                        self.state().client_doc.init(&Doc(doc_span), version);
+                    Task::ClientCommand(ClientCommand::Init(
+                        new_client_id,
+                        doc_span,
+                        version,
+                        generation,
+                    )) => {
+                        self.state().client_doc.client_id = Some(new_client_id.clone());
+                        self.state()
+                            .client_doc
+                            .init(&Doc(doc_span), version, generation);
 
                         // Announce.
                         println!("inital version is {:?}", version);
 
-                        log_wasm!(Setup(self.state().client_doc.client_id.clone()));
+                        log_wasm!(Setup(new_client_id.clone()));
 
                         // If the caret doesn't exist or was deleted, reinitialize it.
                         if !self
@@ -492,16 +711,17 @@ pub trait ClientController {
 
                     // Server sent us a new document version.
                     Task::ClientCommand(ClientCommand::Update(version, client_id, input_op)) => {
-                        if self.state().client_doc.client_id == "$$$$$$" {
+                        if self.state().client_doc.client_id.is_none() {
                             return Ok(());
                         }
 
-                        // Generated from original_doc transformed with input_op
+                        // Generated from original_doc transformed with input_op. Operations
+                        // which don't fit our copy of the server's document are rejected.
                         // let mut bc = vec![];
-                        let doc = Op::apply(&self.state().client_doc.original_doc, &input_op);
+                        let doc = Op::try_apply(&self.state().client_doc.original_doc, &input_op)?;
 
                         // If this operation is an acknowledgment...
-                        if self.state().client_doc.client_id == client_id {
+                        if self.state().client_doc.client_id.as_ref() == Some(&client_id) {
                             // Confirm pending op, send out next if one is available.
                             let local_op = self
                                 .state()
@@ -519,7 +739,7 @@ pub trait ClientController {
                             let (last_doc, input_op) = self
                                 .state()
                                 .client_doc
-                                .sync_sent_new_version(&doc, version, &input_op);
+                                .sync_sent_new_version(&doc, version, &input_op)?;
 
                             // Client drives frontend frontend state.
                             let res = if cfg!(feature = "full_client_updates") {
@@ -556,6 +776,16 @@ pub trait ClientController {
                         }
                     }
 
+                    // Server rejected our operation. It follows up with an Init to
+                    // start us over with its document.
+                    Task::ClientCommand(ClientCommand::Error { code, message }) => {
+                        println!("server rejected our operation: {:?} {}", code, message);
+                        self.send_frontend(&FrontendCommand::Error(format!(
+                            "The server rejected a change ({:?}) and the document was reloaded.",
+                            code
+                        )))?;
+                    }
+
                     Task::ClientCommand(ClientCommand::ServerDisconnect) => {
                         // Notify frontend.
                         self.send_frontend(&FrontendCommand::ServerDisconnect)
@@ -564,10 +794,10 @@ pub trait ClientController {
                 }
 
                 if delay_log {
-                    log_wasm!(Task(
-                        self.state().client_doc.client_id.clone(),
-                        value.clone()
-                    ));
+                    let client_id = self.state().client_doc.client_id.clone();
+                    if let Some(client_id) = client_id {
+                        log_wasm!(Task(client_id, value.clone()));
+                    }
                 }
 
                 Ok(())
@@ -585,11 +815,35 @@ pub trait ClientController {
         }
     }
 
+    /// The id the server gave us.
+    fn assigned_client_id(&mut self) -> Result<String, Error> {
+        self.state()
+            .client_doc
+            .client_id
+            .clone()
+            .ok_or_else(|| format_err!("The server hasn't given us a client id"))
+    }
+
     fn upload(&mut self, local_op: Op<RtfSchema>) -> Result<(), Error> {
         log_wasm!(Debug("CLIENTOP".to_string()));
-        let client_id = self.state().client_doc.client_id.clone();
+        let client_id = self.assigned_client_id()?;
         let version = self.state().client_doc.version;
-        Ok(self.send_server(&ServerCommand::Commit(client_id, local_op, version))?)
+        let generation = self.state().client_doc.generation;
+        Ok(self.send_server(&ServerCommand::Commit(
+            client_id, local_op, version, generation,
+        ))?)
+    }
+
+    /// Picks up where we left off with the server after reconnecting. It
+    /// replies with the operations we missed.
+    fn resume_server(&mut self) -> Result<(), Error> {
+        let client_id = self.assigned_client_id()?;
+        let version = self.state().client_doc.version;
+        let generation = self.state().client_doc.generation;
+        let op = self.state().client_doc.resume_payload();
+        self.send_server(&ServerCommand::Resume(client_id, version, generation, op))?;
+
+        self.send_frontend(&FrontendCommand::ServerReconnect)
     }
 
     // TODO combine with client_op?
@@ -598,7 +852,7 @@ pub trait ClientController {
         C: Fn(ActionContext) -> Result<T, Error>,
     {
         let doc = self.state().client_doc.doc.clone();
-        let client_id = self.state().client_doc.client_id.clone();
+        let client_id = self.assigned_client_id()?;
 
         callback(ActionContext::new(doc, client_id))
     }
@@ -616,6 +870,35 @@ pub trait ClientController {
         let bc = ::oatie::apply::apply_op_bc(&self.state().client_doc.doc.0, &op);
         self.state().client_doc.apply_local_op(&op);
 
+        self.client_op_applied(op, bc)
+    }
+
+    /// Undo (or redo) the last local change.
+    fn client_undo(&mut self, redo: bool) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let doc = self.state().client_doc.doc.clone();
+        let op = if redo {
+            self.state().client_doc.redo()
+        } else {
+            self.state().client_doc.undo()
+        };
+
+        if let Some(op) = op {
+            let bc = ::oatie::apply::apply_op_bc(&doc.0, &op);
+            self.client_op_applied(op, bc)?;
+        }
+
+        Ok(())
+    }
+
+    /// Render, upload, and update controls after a local operation has been
+    /// applied to our client document.
+    fn client_op_applied(&mut self, op: Op<RtfSchema>, bc: Vec<Program>) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
         // Check that our operations can compose well.
         // if cfg!(not(target_arch = "wasm32")) {
         //     // println!("ORIGINAL: {:?}", client.original_doc);
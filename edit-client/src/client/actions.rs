@@ -1,12 +1,16 @@
 mod caret;
 mod identify;
 mod modify;
+mod paste;
 mod styles;
+mod table;
 
 pub use self::caret::*;
 pub use self::identify::*;
 pub use self::modify::*;
+pub use self::paste::*;
 pub use self::styles::*;
+pub use self::table::*;
 use crate::walkers::*;
 use failure::Error;
 use oatie::doc::*;
@@ -23,6 +27,19 @@ fn caret_attrs(client_id: &str, focus: bool) -> Attrs {
     }
 }
 
+/// Collects every caret in a span, in document order.
+fn collect_carets(span: &DocSpan<RtfSchema>, carets: &mut Vec<Attrs>) {
+    for elem in span {
+        if let DocGroup(ref attrs, ref inner) = elem {
+            if is_any_caret(attrs) {
+                carets.push(attrs.clone());
+            } else {
+                collect_carets(inner, carets);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ActionContext {
     pub doc: Doc<RtfSchema>,
@@ -86,11 +86,9 @@ fn walker_right_word(walker: &mut Walker<'_>) {
                     walker.next_char();
                 }
             }
-            Some(DocGroup(ref attrs, _)) => {
-                if let Attrs::Caret { .. } = attrs {
-                    // guess we'll stop
-                    break;
-                }
+            Some(DocGroup(..)) => {
+                // Stop at carets and images.
+                break;
             }
             None => {
                 // guess we'll stop
@@ -112,11 +110,9 @@ fn walker_left_word(walker: &mut Walker<'_>) {
                     walker.back_char();
                 }
             }
-            Some(DocGroup(ref attrs, _)) => {
-                if let Attrs::Caret { .. } = attrs {
-                    // guess we'll stop
-                    break;
-                }
+            Some(DocGroup(..)) => {
+                // Stop at carets and images.
+                break;
             }
             None => {
                 // guess we'll stop
@@ -264,14 +260,34 @@ pub fn caret_block_move(ctx: ActionContext, increase: bool) -> Result<Op<RtfSche
     writer.del.close();
     let op_1 = writer.exit_result();
 
+    // In a table, move to the same column of the adjacent row. Past the
+    // first or last row, leave the table from its first or last cell.
+    let mut in_adjacent_row = false;
+    if let Some(cursor) = find_cell(&ctx)? {
+        let last = cursor.cells.len() - 1;
+        let (row, edge) = if increase {
+            (cursor.row + 1, cursor.cell(last, cursor.cells[last]))
+        } else {
+            (cursor.row.wrapping_sub(1), cursor.cell(0, 0))
+        };
+        if let Some(cell) = cursor.cell(row, cursor.column) {
+            walker = cell;
+            in_adjacent_row = true;
+        } else if let Some(edge) = edge {
+            walker = edge;
+        }
+    }
+
     // Second operation inserts the new caret.
-    if increase {
-        if !walker.next_block() {
-            return Ok(op!([], []));
+    if !in_adjacent_row {
+        if increase {
+            if !walker.next_block() {
+                return Ok(op!([], []));
+            }
+        } else {
+            assert!(walker.back_block());
+            let _ = walker.back_block(); // don't care
         }
-    } else {
-        assert!(walker.back_block());
-        let _ = walker.back_block(); // don't care
     }
 
     let mut writer = walker.to_writer();
@@ -13,6 +13,10 @@ use std::collections::HashSet;
 pub struct CaretState {
     pub block: String,
     pub in_list: bool,
+    pub in_ordered_list: bool,
+    pub in_task_list: bool,
+    pub in_quote: bool,
+    pub in_table: bool,
     pub styles: HashSet<RtfStyle>,
 }
 
@@ -85,21 +89,46 @@ pub fn identify_block(ctx: ActionContext) -> Result<CaretState, Error> {
         let tag = match attrs {
             Attrs::Header(level) => format!("h{}", level),
             Attrs::Html => format!("html"),
-            Attrs::Code => format!("pre"),
+            Attrs::Code(..) => format!("pre"),
             Attrs::Rule => format!("hr"),
             Attrs::Caret { .. } => format!("caret"),
+            Attrs::Image { .. } => format!("img"),
             Attrs::Para => format!("p"),
             Attrs::ListItem => format!("bullet"),
+            Attrs::OrderedListItem(..) => format!("ordered"),
+            Attrs::TaskListItem { .. } => format!("task"),
+            Attrs::BlockQuote => format!("blockquote"),
+            Attrs::Table => format!("table"),
+            Attrs::TableRow => format!("tr"),
+            Attrs::TableCell => format!("td"),
         };
         let mut in_list = false;
+        let mut in_ordered_list = false;
+        let mut in_task_list = false;
+        let mut in_quote = false;
+        let mut in_table = false;
         if walker.parent() {
             if let Some(DocGroup(ref attrs_2, _)) = walker.doc().head() {
-                in_list = *attrs_2 == Attrs::ListItem
+                in_list = *attrs_2 == Attrs::ListItem;
+                in_ordered_list = match attrs_2 {
+                    Attrs::OrderedListItem(..) => true,
+                    _ => false,
+                };
+                in_task_list = match attrs_2 {
+                    Attrs::TaskListItem { .. } => true,
+                    _ => false,
+                };
+                in_quote = *attrs_2 == Attrs::BlockQuote;
+                in_table = *attrs_2 == Attrs::TableCell;
             }
         }
         Ok(CaretState {
             block: tag,
             in_list,
+            in_ordered_list,
+            in_task_list,
+            in_quote,
+            in_table,
             styles: styles.styles(),
         })
     } else {
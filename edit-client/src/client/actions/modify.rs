@@ -5,6 +5,8 @@ use crate::walkers::*;
 use failure::Error;
 use oatie::doc::*;
 use oatie::rtf::*;
+use oatie::stepper::CurStepper;
+use std::cmp;
 
 // Insert a string at the user's caret position.
 pub fn add_string(ctx: ActionContext, input: &str) -> Result<ActionContext, Error> {
@@ -30,18 +32,24 @@ pub fn add_string(ctx: ActionContext, input: &str) -> Result<ActionContext, Erro
         })
 }
 
-pub fn toggle_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+/// Wraps the current block in a group with the given attributes, or unwraps
+/// it if its parent already is one.
+fn toggle_parent(ctx: ActionContext, parent_attrs: Attrs) -> Result<Op<RtfSchema>, Error> {
     // Create a walker that points to the beginning of the block the caret
     // is currently in.
     let mut walker = ctx.get_walker(Pos::Focus).expect("Expected a Focus caret");
     assert!(walker.back_block());
 
-    // If the parent of our current block is a list item, delete it.
+    // If the parent of our current block matches, delete it.
     let mut parent_walker = walker.clone();
     if parent_walker.parent() {
         if let Some(DocGroup(ref attrs, ref span)) = parent_walker.doc().head() {
-            if let Attrs::ListItem = attrs {
-                // Delete the bullet group.
+            // Table cells can't hold lists or quotes.
+            if *attrs == Attrs::TableCell {
+                return Ok(Op::empty());
+            }
+            if *attrs == parent_attrs {
+                // Delete the parent group.
                 let mut writer = parent_walker.to_writer();
                 writer
                     .del
@@ -51,12 +59,252 @@ pub fn toggle_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
         }
     }
 
-    // Wrap current block with a bullet group.
+    // Wrap current block with the parent group.
     Ok({
         let mut writer = walker.to_writer();
         writer
             .add
-            .place(&AddGroup(Attrs::ListItem, add_span![AddSkip(1)]));
+            .place(&AddGroup(parent_attrs, add_span![AddSkip(1)]));
+        writer.exit_result()
+    })
+}
+
+/// Wraps the current block in a list item, or unwraps it if it's in a list
+/// item of the same kind. A list item of the other kind is switched over by
+/// changing its attributes, keeping concurrent edits to its contents intact.
+fn toggle_list_item(ctx: ActionContext, list_attrs: Attrs) -> Result<Op<RtfSchema>, Error> {
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+
+    if walker.parent() && at_list_item(&walker) {
+        let (attrs, span) = match walker.doc().head() {
+            Some(DocGroup(attrs, span)) => (attrs, span),
+            _ => unreachable!(),
+        };
+        let same_kind = match (attrs, &list_attrs) {
+            (Attrs::ListItem, Attrs::ListItem) => true,
+            (Attrs::OrderedListItem(..), Attrs::OrderedListItem(..)) => true,
+            (Attrs::TaskListItem { .. }, Attrs::TaskListItem { .. }) => true,
+            _ => false,
+        };
+
+        let mut writer = walker.to_writer();
+        if same_kind {
+            writer
+                .del
+                .place(&DelGroup(del_span![DelSkip(span.skip_len())]));
+        } else {
+            writer.add.place(&AddAttrs(list_attrs, vec![]));
+        }
+        return Ok(writer.exit_result());
+    }
+
+    toggle_parent(ctx, list_attrs)
+}
+
+pub fn toggle_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    toggle_list_item(ctx, Attrs::ListItem)
+}
+
+pub fn toggle_ordered_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    // Continue numbering from a numbered list item preceding the list item
+    // we're in, or would create.
+    let start = {
+        let mut walker = ctx.get_walker(Pos::Focus)?;
+        assert!(walker.back_block());
+        let mut parent_walker = walker.clone();
+        if parent_walker.parent() && at_list_item(&parent_walker) {
+            walker = parent_walker;
+        }
+        match walker.doc().unhead() {
+            Some(DocGroup(Attrs::OrderedListItem(start), _)) => *start,
+            _ => 1,
+        }
+    };
+    toggle_list_item(ctx, Attrs::OrderedListItem(start))
+}
+
+pub fn toggle_task_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    toggle_list_item(ctx, Attrs::TaskListItem { checked: false })
+}
+
+/// Checks or unchecks the task list item at the walker. This only replaces
+/// the item's attributes, so concurrent edits to its text are kept.
+fn toggle_task_checked(walker: &Walker<'_>) -> Op<RtfSchema> {
+    let checked = match walker.doc().head() {
+        Some(DocGroup(Attrs::TaskListItem { checked }, _)) => *checked,
+        _ => return Op::empty(),
+    };
+    let mut writer = walker.to_writer();
+    writer
+        .add
+        .place(&AddAttrs(Attrs::TaskListItem { checked: !checked }, vec![]));
+    writer.exit_result()
+}
+
+/// Checks or unchecks the task list item the caret is in.
+pub fn toggle_task(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+    if !walker.parent() {
+        return Ok(Op::empty());
+    }
+    Ok(toggle_task_checked(&walker))
+}
+
+/// Checks or unchecks the task list item a cursor points to, like when its
+/// checkbox is clicked. The caret doesn't move.
+pub fn toggle_task_at(ctx: &ActionContext, cur: &CurSpan) -> Result<Op<RtfSchema>, Error> {
+    let mut walker = Walker::new(&ctx.doc);
+    {
+        let doc = &mut walker.stepper.doc;
+        let mut match_cur = CurStepper::new(cur);
+        loop {
+            match match_cur.head() {
+                Some(CurGroup) => break,
+                Some(CurSkip(n)) => {
+                    match_cur.next();
+                    doc.skip(n);
+                }
+                Some(CurWithGroup(..)) => {
+                    match_cur.enter();
+                    doc.enter();
+                }
+                Some(CurChar) | None => return Ok(Op::empty()),
+            }
+        }
+    }
+    Ok(toggle_task_checked(&walker))
+}
+
+pub fn toggle_quote(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    toggle_parent(ctx, Attrs::BlockQuote)
+}
+
+fn at_list_item(walker: &Walker<'_>) -> bool {
+    match walker.doc().head() {
+        Some(DocGroup(ref attrs, _)) => is_list_item(attrs),
+        _ => false,
+    }
+}
+
+/// Moves the list item the caret is in to the end of its previous sibling
+/// list item. Blocks outside of a list are turned into a list item instead.
+pub fn indent_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    // Find the list item containing the current block.
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+    if !(walker.parent() && at_list_item(&walker)) {
+        return toggle_list(ctx);
+    }
+
+    let (item_attrs, item_len) = match walker.doc().head() {
+        Some(DocGroup(attrs, span)) => (attrs.clone(), span.skip_len()),
+        _ => unreachable!(),
+    };
+
+    // The first item in a list has nothing to be nested under.
+    let (prev_attrs, prev_len) = match walker.doc().unhead() {
+        Some(DocGroup(attrs, span)) if is_list_item(attrs) => (attrs.clone(), span.skip_len()),
+        _ => return Ok(Op::empty()),
+    };
+
+    // Move to the preceding list item.
+    walker.stepper.doc.prev();
+
+    Ok({
+        let mut writer = walker.to_writer();
+
+        // Delete both list items, keeping their contents.
+        writer.del.begin();
+        if prev_len > 0 {
+            writer.del.place(&DelSkip(prev_len));
+        }
+        writer.del.close();
+        writer.del.begin();
+        if item_len > 0 {
+            writer.del.place(&DelSkip(item_len));
+        }
+        writer.del.close();
+
+        // Wrap both again, with our list item as the last child of the
+        // preceding one.
+        writer.add.begin();
+        if prev_len > 0 {
+            writer.add.place(&AddSkip(prev_len));
+        }
+        writer.add.begin();
+        if item_len > 0 {
+            writer.add.place(&AddSkip(item_len));
+        }
+        writer.add.close(item_attrs);
+        writer.add.close(prev_attrs);
+
+        writer.exit_result()
+    })
+}
+
+/// Moves the list item the caret is in out of its parent list item, placing
+/// it after the parent. Any list items following it are nested under it, so
+/// the order of the document is kept. Top level list items are unwrapped.
+pub fn outdent_list(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    // Find the list item containing the current block.
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+    if !(walker.parent() && at_list_item(&walker)) {
+        return Ok(Op::empty());
+    }
+
+    let (item_attrs, item_len) = match walker.doc().head() {
+        Some(DocGroup(attrs, span)) => (attrs.clone(), span.skip_len()),
+        _ => unreachable!(),
+    };
+
+    // Find the parent list item, or unwrap our list item if there is none.
+    let mut parent_walker = walker.clone();
+    if !(parent_walker.parent() && at_list_item(&parent_walker)) {
+        return toggle_parent(ctx, item_attrs);
+    }
+    let (parent_attrs, parent_len) = match parent_walker.doc().head() {
+        Some(DocGroup(attrs, span)) => (attrs.clone(), span.skip_len()),
+        _ => unreachable!(),
+    };
+
+    // Count the elements surrounding our list item in its parent.
+    let following_len = walker.doc().skip_len() - 1;
+    let preceding_len = parent_len - following_len - 1;
+
+    Ok({
+        let mut writer = parent_walker.to_writer();
+
+        // Delete the parent list item and our own, keeping their contents.
+        writer.del.begin();
+        if preceding_len > 0 {
+            writer.del.place(&DelSkip(preceding_len));
+        }
+        writer.del.begin();
+        if item_len > 0 {
+            writer.del.place(&DelSkip(item_len));
+        }
+        writer.del.close();
+        if following_len > 0 {
+            writer.del.place(&DelSkip(following_len));
+        }
+        writer.del.close();
+
+        // Rewrap what preceded our list item in the parent, unless nothing
+        // did, then wrap our list item's contents and what followed it.
+        if preceding_len > 0 {
+            writer.add.begin();
+            writer.add.place(&AddSkip(preceding_len));
+            writer.add.close(parent_attrs);
+        }
+        writer.add.begin();
+        if item_len + following_len > 0 {
+            writer.add.place(&AddSkip(item_len + following_len));
+        }
+        writer.add.close(item_attrs);
+
         writer.exit_result()
     })
 }
@@ -68,19 +316,11 @@ pub fn replace_block(ctx: ActionContext, attrs: Attrs) -> Result<Op<RtfSchema>,
     let mut walker = ctx.get_walker(Pos::Focus).expect("Expected a Focus caret");
     assert!(walker.back_block());
 
-    // Get the skip length of the contents of the current block.
-    let len = match walker.doc().head() {
-        Some(DocGroup(_, ref span)) => span.skip_len(),
-        _ => unreachable!(),
-    };
-
-    // Delete the current block, and the wrap its contents with a new block.
+    // Change the attributes of the current block in place, so concurrent
+    // edits to its contents are preserved.
     Ok({
         let mut writer = walker.to_writer();
-        writer.del.place(&DelGroup(del_span![DelSkip(len)]));
-        writer
-            .add
-            .place_all(&add_span![AddGroup(attrs, [AddSkip(len)],)]);
+        writer.add.place(&AddAttrs(attrs, vec![]));
         writer.exit_result()
     })
 }
@@ -94,18 +334,27 @@ fn combine_with_previous_block(walker: Walker<'_>) -> Result<Op<RtfSchema>, Erro
     // Check if we are in a block inside of a list item. Also get the length
     // of the contents of the parent list item, or otherwise just use "1"
     // (indicating the current block).
-    let mut is_list_item = false;
+    let mut in_list_item = false;
     let mut list_item_skip_len = 1;
     if parent_walker.doc().unhead() == None && parent_walker.parent() {
         if let Some(DocGroup(ref attrs_2, ref span_2)) = parent_walker.doc().head() {
-            if let Attrs::ListItem = attrs_2 {
+            // Blocks aren't joined across table cells.
+            if *attrs_2 == Attrs::TableCell {
+                return Ok(Op::empty());
+            }
+            if is_list_item(attrs_2) {
                 // We are at the start of a block inside of a list item.
-                is_list_item = true;
+                in_list_item = true;
                 list_item_skip_len = span_2.skip_len();
             }
         }
     }
 
+    // Nor is a block joined into the last cell of a preceding table.
+    if let Some(DocGroup(Attrs::Table, _)) = parent_walker.doc().unhead() {
+        return Ok(Op::empty());
+    }
+
     // Check if parent is preceded by a list item.
     // 1. If we are in a list item also, delete both and join together as one
     //    list item.
@@ -113,7 +362,7 @@ fn combine_with_previous_block(walker: Walker<'_>) -> Result<Op<RtfSchema>, Erro
     //    group spanning its contents and our block.
     // contents of both list items.
     if let Some(DocGroup(ref attrs, ref span)) = parent_walker.doc().unhead() {
-        if let Attrs::ListItem = attrs {
+        if is_list_item(attrs) {
             // Create local copies of attributes and span length of the previous
             // bullet group.
             let attrs = attrs.to_owned();
@@ -130,13 +379,13 @@ fn combine_with_previous_block(walker: Walker<'_>) -> Result<Op<RtfSchema>, Erro
                     writer.del.place(&DelSkip(skip_len));
                 }
                 writer.del.close();
-                if is_list_item {
+                if in_list_item {
                     writer.del.begin();
                 }
                 if list_item_skip_len > 0 {
                     writer.del.place(&DelSkip(list_item_skip_len));
                 }
-                if is_list_item {
+                if in_list_item {
                     writer.del.close();
                 }
 
@@ -156,7 +405,7 @@ fn combine_with_previous_block(walker: Walker<'_>) -> Result<Op<RtfSchema>, Erro
 
     // If we are in a list item but there is no preceding list item, unindent
     // the current list item by deleting it and preserving its contents.
-    if is_list_item {
+    if in_list_item {
         return Ok({
             let mut writer = parent_walker.to_writer();
             writer.del.begin();
@@ -245,49 +494,342 @@ fn delete_char_inner(mut walker: Walker<'_>) -> Result<Op<RtfSchema>, Error> {
     while let Some(DocGroup(ref attrs, _)) = walker.doc().head() {
         if let Attrs::Caret { .. } = attrs {
             walker.stepper.doc.next();
+        } else {
+            break;
         }
     }
 
-    // Check that we precede a character.
-    if let Some(DocText(..)) = walker.doc().head() {
-        // fallthrough
-    } else {
-        unreachable!();
-    }
-
-    // Delete the character we skipped over.
     let mut writer = walker.to_writer();
-    writer.del.place(&DelText(1));
+    match walker.doc().head() {
+        // Delete the character we skipped over.
+        Some(DocText(..)) => {
+            writer.del.place(&DelText(1));
+        }
+        // Images are deleted as a whole, like a character.
+        Some(DocGroup(ref attrs, _)) if is_image(attrs) => {
+            writer.del.object();
+        }
+        _ => unreachable!(),
+    }
     Ok(writer.exit_result())
 }
 
+/// Finds the path to one of a client's carets: the index of the element at
+/// each level of nesting, counting characters and groups alike.
+fn caret_path(span: &DocSpan<RtfSchema>, client_id: &str, focus: bool) -> Option<Vec<usize>> {
+    let mut index = 0;
+    for elem in span {
+        match elem {
+            DocText(_, text) => index += text.char_len(),
+            DocGroup(attrs, inner) => {
+                if is_caret(attrs, Some(client_id), focus) {
+                    return Some(vec![index]);
+                }
+                if let Some(mut path) = caret_path(inner, client_id, focus) {
+                    path.insert(0, index);
+                    return Some(path);
+                }
+                index += 1;
+            }
+        }
+    }
+    None
+}
+
+/// The groups along a path from `span`, outermost first. Only blocks hold
+/// text, so above them an element's index is its position in the span.
+fn path_groups<'a>(
+    mut span: &'a DocSpan<RtfSchema>,
+    path: &[usize],
+) -> Vec<(&'a Attrs, &'a DocSpan<RtfSchema>)> {
+    let mut groups = vec![];
+    for &index in path {
+        match span[index] {
+            DocGroup(ref attrs, ref inner) => {
+                groups.push((attrs, inner));
+                span = inner;
+            }
+            DocText(..) => unreachable!(),
+        }
+    }
+    groups
+}
+
+/// Collects the path of every block in a span, in document order.
+fn block_paths(span: &DocSpan<RtfSchema>, prefix: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    for (index, elem) in span.iter().enumerate() {
+        if let DocGroup(attrs, inner) = elem {
+            prefix.push(index);
+            if is_block(attrs) {
+                paths.push(prefix.clone());
+            } else {
+                block_paths(inner, prefix, paths);
+            }
+            prefix.pop();
+        }
+    }
+}
+
+/// Whether deleting backward from the start of block `b` joins it to block
+/// `a` before it. Like `combine_with_previous_block`, blocks aren't joined
+/// across table cells, or into or out of a table.
+fn can_join_blocks(doc: &DocSpan<RtfSchema>, a: &[usize], b: &[usize]) -> bool {
+    let common = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    path_groups(doc, a)
+        .into_iter()
+        .skip(common)
+        .chain(path_groups(doc, b).into_iter().skip(common))
+        .all(|(attrs, _)| match attrs {
+            Attrs::Table | Attrs::TableRow | Attrs::TableCell => false,
+            _ => true,
+        })
+}
+
+/// Deletes the text and images in a block from `from` up to `to`, keeping
+/// any carets. Returns the deletion and the number of carets it kept.
+fn delete_inline(span: &DocSpan<RtfSchema>, from: usize, to: usize) -> (DelSpan<RtfSchema>, usize) {
+    let mut del: DelSpan<RtfSchema> = vec![];
+    let mut kept = 0;
+    let mut index = 0;
+    for elem in span {
+        if index >= to {
+            break;
+        }
+        match elem {
+            DocText(_, text) => {
+                let len = text.char_len();
+                let skip = cmp::min(len, from.saturating_sub(index));
+                let delete = cmp::min(index + len, to).saturating_sub(index + skip);
+                if skip > 0 {
+                    del.place(&DelSkip(skip));
+                }
+                if delete > 0 {
+                    del.place(&DelText(delete));
+                }
+                index += len;
+            }
+            DocGroup(attrs, _) => {
+                if index < from {
+                    del.place(&DelSkip(1));
+                } else if is_image(attrs) {
+                    del.place(&DelObject);
+                } else {
+                    del.place(&DelSkip(1));
+                    kept += 1;
+                }
+                index += 1;
+            }
+        }
+    }
+    (del, kept)
+}
+
+/// Descends along `path` to apply a deletion.
+fn del_at_path(path: &[usize], inner: DelSpan<RtfSchema>) -> DelSpan<RtfSchema> {
+    match path.split_first() {
+        Some((&index, rest)) => {
+            let mut del: DelSpan<RtfSchema> = vec![];
+            if index > 0 {
+                del.place(&DelSkip(index));
+            }
+            del.place(&DelWithGroup(del_at_path(rest, inner)));
+            del
+        }
+        None => inner,
+    }
+}
+
+/// Descends along `path` to apply an addition.
+fn add_at_path(path: &[usize], inner: AddSpan<RtfSchema>) -> AddSpan<RtfSchema> {
+    match path.split_first() {
+        Some((&index, rest)) => {
+            let mut add: AddSpan<RtfSchema> = vec![];
+            if index > 0 {
+                add.place(&AddSkip(index));
+            }
+            add.place(&AddWithGroup(add_at_path(rest, inner)));
+            add
+        }
+        None => inner,
+    }
+}
+
+/// Deletes from `from` in the block at `start` up to `to` in the block at
+/// `end`. Blocks and block objects in between are deleted whole, and what's
+/// left of the end block and the groups it's in are joined onto the start
+/// block and the groups it's in, matched up from the block outward, the way
+/// deleting one character at a time would join them. Carets in deleted
+/// blocks move to where the two blocks meet.
+fn delete_range(
+    doc: &DocSpan<RtfSchema>,
+    start: &[usize],
+    from: usize,
+    end: &[usize],
+    to: usize,
+) -> Op<RtfSchema> {
+    if start == end {
+        let (_, block) = *path_groups(doc, start).last().unwrap();
+        let (del, _) = delete_inline(block, from, to);
+        return Op(del_at_path(start, del), vec![]);
+    }
+
+    // The two blocks are in different groups of their closest common
+    // ancestor.
+    let common = start.iter().zip(end).take_while(|(a, b)| a == b).count();
+    let ancestor = match path_groups(doc, &start[..common]).last() {
+        Some(&(_, span)) => span,
+        None => doc,
+    };
+    let (first, last) = (start[common], end[common]);
+    let start_groups = path_groups(ancestor, &start[common..]);
+    let end_groups = path_groups(ancestor, &end[common..]);
+    let mut moved = vec![];
+
+    // Delete to the end of the start block and each group it's in, keeping
+    // count of what's left before the point where the blocks meet.
+    let start_depth = start_groups.len();
+    let mut kept = vec![0; start_depth];
+    let (_, block) = start_groups[start_depth - 1];
+    let (block_del, block_carets) = delete_inline(block, from, block.skip_len());
+    kept[start_depth - 1] = from + block_carets;
+    let mut start_del = DelGroup(block_del);
+    for depth in (0..start_depth - 1).rev() {
+        let (_, span) = start_groups[depth];
+        let child = start[common + depth + 1];
+        let mut del: DelSpan<RtfSchema> = vec![];
+        if child > 0 {
+            del.place(&DelSkip(child));
+        }
+        del.place(&start_del);
+        if child + 1 < span.len() {
+            collect_carets(&span[child + 1..], &mut moved);
+            del.place(&DelMany(span.len() - child - 1));
+        }
+        kept[depth] = child;
+        start_del = DelGroup(del);
+    }
+
+    // Delete from the start of the end block and each group it's in,
+    // keeping count of what's left after the point where the blocks meet.
+    let end_depth = end_groups.len();
+    let mut remaining = vec![0; end_depth];
+    let (_, block) = end_groups[end_depth - 1];
+    let (block_del, block_carets) = delete_inline(block, 0, to);
+    remaining[end_depth - 1] = block_carets + block.skip_len() - to;
+    let mut end_del = DelGroup(block_del);
+    for depth in (0..end_depth - 1).rev() {
+        let (_, span) = end_groups[depth];
+        let child = end[common + depth + 1];
+        let mut del: DelSpan<RtfSchema> = vec![];
+        if child > 0 {
+            collect_carets(&span[..child], &mut moved);
+            del.place(&DelMany(child));
+        }
+        del.place(&end_del);
+        remaining[depth] = span.len() - child - 1;
+        end_del = DelGroup(del);
+    }
+
+    let mut del: DelSpan<RtfSchema> = vec![];
+    if first > 0 {
+        del.place(&DelSkip(first));
+    }
+    del.place(&start_del);
+    if first + 1 < last {
+        collect_carets(&ancestor[first + 1..last], &mut moved);
+        del.place(&DelMany(last - first - 1));
+    }
+    del.place(&end_del);
+
+    // Every group deleted above is unwrapped, so rebuild the groups of the
+    // start block around what's left. Each group of the end block is matched
+    // with the group of the start block as far from its block; the rest of
+    // the end block's groups stay unwrapped after the start block's.
+    let mut add: AddSpan<RtfSchema> = vec![];
+    if kept[start_depth - 1] > 0 {
+        add.place(&AddSkip(kept[start_depth - 1]));
+    }
+    for attrs in &moved {
+        add.place(&AddGroup(attrs.clone(), vec![]));
+    }
+    if remaining[end_depth - 1] > 0 {
+        add.place(&AddSkip(remaining[end_depth - 1]));
+    }
+    for depth in (0..start_depth - 1).rev() {
+        let mut inner: AddSpan<RtfSchema> = vec![];
+        if kept[depth] > 0 {
+            inner.place(&AddSkip(kept[depth]));
+        }
+        inner.place(&AddGroup(start_groups[depth + 1].0.clone(), add));
+        if end_depth + depth >= start_depth {
+            let end_depth = end_depth + depth - start_depth;
+            if remaining[end_depth] > 0 {
+                inner.place(&AddSkip(remaining[end_depth]));
+            }
+        }
+        add = inner;
+    }
+    let mut top: AddSpan<RtfSchema> = vec![];
+    if first > 0 {
+        top.place(&AddSkip(first));
+    }
+    top.place(&AddGroup(start_groups[0].0.clone(), add));
+
+    Op(
+        del_at_path(&start[..common], del),
+        add_at_path(&start[..common], top),
+    )
+}
+
 /// Deletes the contents of the current selection. Returns a modified context
 /// and a boolean indicating if a selection existed to delete.
-fn delete_selection(ctx: ActionContext) -> Result<(bool, ActionContext), Error> {
-    Ok(ctx).and_then(|ctx| {
+pub(super) fn delete_selection(ctx: ActionContext) -> Result<(bool, ActionContext), Error> {
+    let selected = {
         let start = ctx.get_walker(Pos::Start)?;
         let end = ctx.get_walker(Pos::End)?;
-        let delta = end.delta(&start).unwrap_or(0) as usize;
-
-        // If we found a selection, delete every character in the selection.
-        // We implement this by looping until the caret distance between our
-        // cursors is 0.
-        // TODO: This is incredibly inefficient.
-        //  1. Dont' recurse infinitely, do this in a loop.
-        //  2. Skip entire DocText components instead of one character at a time.
-        Ok(if delta != 0 {
-            // Get real weird with it.
-            let op = delete_char_inner(end)?;
-            let ctx = ctx.apply(&op)?;
-            if delta > 1 {
-                delete_selection(ctx)?
-            } else {
-                (true, ctx)
+        end.delta(&start).unwrap_or(0) != 0
+    };
+    if !selected {
+        return Ok((false, ctx));
+    }
+
+    let op = {
+        let doc = &ctx.doc.0;
+        let (start, end) = match (
+            caret_path(doc, &ctx.client_id, false),
+            caret_path(doc, &ctx.client_id, true),
+        ) {
+            (Some(anchor), Some(focus)) => {
+                if anchor < focus {
+                    (anchor, focus)
+                } else {
+                    (focus, anchor)
+                }
             }
-        } else {
-            (false, ctx)
-        })
-    })
+            _ => bail!("Could not find the selection."),
+        };
+        let (mut start_block, mut from) = (&start[..start.len() - 1], start[start.len() - 1] + 1);
+        let (end_block, to) = (&end[..end.len() - 1], end[end.len() - 1]);
+
+        // Deleting stops at the first block going backward from the end that
+        // can't be joined to the one before it.
+        let mut blocks = vec![];
+        block_paths(doc, &mut vec![], &mut blocks);
+        let first = blocks.iter().position(|block| &block[..] == start_block);
+        let last = blocks.iter().position(|block| &block[..] == end_block);
+        if let (Some(first), Some(last)) = (first, last) {
+            if let Some(block) = (first + 1..=last)
+                .rev()
+                .find(|&block| !can_join_blocks(doc, &blocks[block - 1], &blocks[block]))
+            {
+                start_block = &blocks[block][..];
+                from = 0;
+            }
+        }
+
+        delete_range(doc, start_block, from, end_block, to)
+    };
+    Ok((true, ctx.apply(&op)?))
 }
 
 /// Backspace.
@@ -317,13 +859,17 @@ pub fn split_block(ctx: ActionContext, add_hr: bool) -> Result<Op<RtfSchema>, Er
         unreachable!();
     };
 
-    // Identify if we're nested inside of a bullet.
+    // Identify if we're nested inside of a list item.
     let mut parent_walker = prev_walker.clone();
-    let mut nested_bullet = false;
+    let mut list_item_attrs = None;
     if parent_walker.parent() {
         if let Some(DocGroup(ref attrs, _)) = parent_walker.doc().head() {
-            if let Attrs::ListItem = attrs {
-                nested_bullet = true;
+            // A table cell holds a single block.
+            if *attrs == Attrs::TableCell {
+                return Ok(Op::empty());
+            }
+            if is_list_item(attrs) {
+                list_item_attrs = Some(attrs.clone());
             }
         }
     }
@@ -335,13 +881,13 @@ pub fn split_block(ctx: ActionContext, add_hr: bool) -> Result<Op<RtfSchema>, Er
             writer.del.place(&DelSkip(skip));
         }
         writer.del.close();
-        if nested_bullet {
+        if list_item_attrs.is_some() {
             writer.del.close();
         }
 
         writer.add.close(previous_block_attrs);
-        if nested_bullet {
-            writer.add.close(Attrs::ListItem);
+        if let Some(ref attrs) = list_item_attrs {
+            writer.add.close(attrs.clone());
             writer.add.begin();
         }
         if add_hr {
@@ -353,8 +899,12 @@ pub fn split_block(ctx: ActionContext, add_hr: bool) -> Result<Op<RtfSchema>, Er
             writer.add.place(&AddSkip(skip));
         }
         writer.add.close(Attrs::Para);
-        if nested_bullet {
-            writer.add.close(Attrs::ListItem);
+        if let Some(attrs) = list_item_attrs {
+            // New tasks start out unchecked.
+            writer.add.close(match attrs {
+                Attrs::TaskListItem { .. } => Attrs::TaskListItem { checked: false },
+                attrs => attrs,
+            });
         }
 
         writer.exit_result()
@@ -0,0 +1,127 @@
+//! Pastes a document fragment, like converted HTML from the clipboard.
+
+use super::*;
+use crate::walkers::*;
+use failure::Error;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+/// Converts a span of a document into a span that adds it.
+fn add_span(span: &DocSpan<RtfSchema>) -> AddSpan<RtfSchema> {
+    span.iter()
+        .map(|elem| match elem {
+            DocText(styles, text) => AddText(styles.clone(), text.clone()),
+            DocGroup(attrs, span) => AddGroup(attrs.clone(), add_span(span)),
+        })
+        .collect()
+}
+
+/// Collects the text of each block in a span, for places that only hold
+/// plain text.
+fn block_text(span: &DocSpan<RtfSchema>, lines: &mut Vec<String>) {
+    for elem in span {
+        if let DocGroup(attrs, span) = elem {
+            if is_block(attrs) {
+                lines.push(
+                    span.iter()
+                        .filter_map(|elem| match elem {
+                            DocText(_, text) => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect(),
+                );
+            } else {
+                block_text(span, lines);
+            }
+        }
+    }
+}
+
+/// The inline contents of a paragraph.
+fn para_contents(elem: Option<&DocElement<RtfSchema>>) -> Option<&DocSpan<RtfSchema>> {
+    match elem {
+        Some(DocGroup(Attrs::Para, span)) => Some(span),
+        _ => None,
+    }
+}
+
+/// Replaces the selection with a fragment of a document. The first and last
+/// paragraphs of the fragment join the text before and after the caret, and
+/// any blocks between them are inserted after the caret's block.
+pub fn paste(ctx: ActionContext, fragment: &DocSpan<RtfSchema>) -> Result<Op<RtfSchema>, Error> {
+    let (_, ctx) = delete_selection(ctx)?;
+
+    // Code blocks and table cells can only hold the fragment's text.
+    let plain_text = {
+        let mut walker = ctx.get_walker(Pos::Start)?;
+        assert!(walker.back_block());
+        let in_code = match walker.doc().head() {
+            Some(DocGroup(Attrs::Code(..), _)) | Some(DocGroup(Attrs::Html, _)) => true,
+            _ => false,
+        };
+        let in_cell = walker.parent()
+            && match walker.doc().head() {
+                Some(DocGroup(Attrs::TableCell, _)) => true,
+                _ => false,
+            };
+        in_code || in_cell
+    };
+    if plain_text {
+        let mut lines = vec![];
+        block_text(fragment, &mut lines);
+        return add_string(ctx, &lines.join("\n")).map(|ctx| ctx.result());
+    }
+
+    let op = {
+        let walker = ctx.get_walker(Pos::Start)?;
+        let mut writer = walker.to_writer();
+
+        let mut blocks = &fragment[..];
+        if blocks.is_empty() {
+            Op::empty()
+        } else if let (1, Some(span)) = (blocks.len(), para_contents(blocks.first())) {
+            // A single paragraph is inserted at the caret, like typed text.
+            writer.add.place_all(&add_span(span));
+            writer.exit_result()
+        } else {
+            let mut block_walker = walker.clone();
+            assert!(block_walker.back_block());
+            let block_attrs = match block_walker.doc().head() {
+                Some(DocGroup(attrs, _)) => attrs.clone(),
+                _ => unreachable!(),
+            };
+
+            // Split the block at the caret, like split_block does.
+            let skip = walker.doc().skip_len();
+            if skip > 0 {
+                writer.del.place(&DelSkip(skip));
+            }
+            writer.del.close();
+
+            if let Some(span) = para_contents(blocks.first()) {
+                writer.add.place_all(&add_span(span));
+                blocks = &blocks[1..];
+            }
+            writer.add.close(block_attrs);
+
+            let last = para_contents(blocks.last());
+            if last.is_some() {
+                blocks = &blocks[..blocks.len() - 1];
+            }
+            writer.add.place_all(&add_span(blocks));
+
+            // The text after the caret, along with the caret, goes after the
+            // pasted blocks.
+            writer.add.begin();
+            if let Some(span) = last {
+                writer.add.place_all(&add_span(span));
+            }
+            if skip > 0 {
+                writer.add.place(&AddSkip(skip));
+            }
+            writer.add.close(Attrs::Para);
+            writer.exit_result()
+        }
+    };
+    Ok(ctx.apply(&op)?.result())
+}
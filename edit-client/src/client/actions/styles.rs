@@ -1,5 +1,6 @@
 use super::*;
 use crate::walkers::*;
+use edit_common::html::is_safe_url;
 use failure::Error;
 use oatie::doc::*;
 use oatie::rtf::*;
@@ -116,3 +117,80 @@ pub fn restyle(ctx: ActionContext, ops: Vec<StyleOp>) -> Result<Op<RtfSchema>, E
 
     Ok(writer.exit_result())
 }
+
+/// Links the selected text to `url`, replacing any links it already has. With
+/// no URL, the selected text is unlinked instead. URLs that aren't safe to
+/// follow, like `javascript:` ones, are refused.
+pub fn set_link(ctx: ActionContext, url: Option<String>) -> Result<Op<RtfSchema>, Error> {
+    if let Some(ref url) = url {
+        ensure!(is_safe_url(url), "Unsafe link URL: {:?}", url);
+    }
+
+    // Find start and end carets, or return if either are missing.
+    let (walker_start, walker_end) = match (ctx.get_walker(Pos::Start), ctx.get_walker(Pos::End)) {
+        (Ok(walker_start), Ok(walker_end)) => (walker_start, walker_end),
+        _ => {
+            return Ok(Op::empty());
+        }
+    };
+
+    // Calculate delta.
+    let delta = walker_end.delta(&walker_start).unwrap_or(0);
+    if delta == 0 {
+        return Ok(Op::empty());
+    }
+
+    let mut writer = walker_start.to_writer();
+
+    // Remove existing links.
+    let mut doc1: DocStepper<RtfSchema> = walker_start.doc().to_owned();
+    let doc2: DocStepper<RtfSchema> = walker_end.doc().to_owned();
+    while doc1 != doc2 {
+        match doc1.head() {
+            Some(DocGroup(..)) => {
+                writer.del.begin();
+                doc1.enter();
+            }
+            Some(DocText(ref styles, ref text)) => {
+                let links = styles.links();
+                if links.is_empty() {
+                    writer.del.place(&DelSkip(text.char_len()));
+                } else {
+                    writer.del.place(&DelStyles(text.char_len(), links));
+                }
+                doc1.skip(text.char_len());
+            }
+            None => {
+                writer.del.exit();
+                doc1.exit();
+            }
+        }
+    }
+
+    // Add the new link.
+    if let Some(url) = url {
+        let mut doc1 = walker_start.doc().to_owned();
+        let doc2 = walker_end.doc().to_owned();
+        while doc1 != doc2 {
+            match doc1.head() {
+                Some(DocGroup(..)) => {
+                    writer.add.begin();
+                    doc1.enter();
+                }
+                Some(DocText(_, ref text)) => {
+                    writer.add.place(&AddStyles(
+                        text.char_len(),
+                        StyleSet::from(hashset![RtfStyle::Link(url.clone())]),
+                    ));
+                    doc1.skip(text.char_len());
+                }
+                None => {
+                    writer.add.exit();
+                    doc1.exit();
+                }
+            }
+        }
+    }
+
+    Ok(writer.exit_result())
+}
@@ -0,0 +1,363 @@
+//! Actions that edit tables, and move the caret between their cells.
+
+use super::*;
+use crate::walkers::*;
+use failure::Error;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::stepper::DocStepper;
+use oatie::writer::AddWriter;
+use std::cmp;
+
+/// The number of rows in a new table, including its header row.
+const NEW_TABLE_ROWS: usize = 2;
+const NEW_TABLE_COLUMNS: usize = 2;
+
+/// The cell the caret is in, and the shape of its table.
+pub(super) struct TableCursor<'a> {
+    /// Points to the table.
+    table: Walker<'a>,
+    pub(super) row: usize,
+    pub(super) column: usize,
+    /// The number of cells in each row. Rows can differ in length after
+    /// concurrent edits add or remove columns.
+    pub(super) cells: Vec<usize>,
+}
+
+impl<'a> TableCursor<'a> {
+    /// Returns a walker at the start of a cell's first block. Columns past
+    /// the end of the row are clamped to its last cell.
+    pub(super) fn cell(&self, row: usize, column: usize) -> Option<Walker<'a>> {
+        let columns = *self.cells.get(row)?;
+        if columns == 0 {
+            return None;
+        }
+
+        let mut walker = self.table.clone();
+        {
+            let doc = &mut walker.stepper.doc;
+            doc.enter();
+            doc.skip(row);
+            doc.enter();
+            doc.skip(cmp::min(column, columns - 1));
+            doc.enter();
+            match doc.head() {
+                Some(DocGroup(ref attrs, _)) if is_block(attrs) => {}
+                _ => return None,
+            }
+            doc.enter();
+        }
+        Some(walker)
+    }
+
+    /// The carets inside the given cell.
+    fn carets_in_cell(&self, row: usize, column: usize) -> Vec<Attrs> {
+        let mut carets = vec![];
+        if let Some(DocGroup(_, ref rows)) = self.table.doc().head() {
+            if let Some(DocGroup(_, ref cells)) = rows.get(row) {
+                if let Some(DocGroup(_, ref blocks)) = cells.get(column) {
+                    collect_carets(blocks, &mut carets);
+                }
+            }
+        }
+        carets
+    }
+}
+
+/// The number of cells in each row of a table.
+fn row_lengths(rows: &DocSpan<RtfSchema>) -> Vec<usize> {
+    rows.iter()
+        .map(|row| match row {
+            DocGroup(_, ref cells) => cells.len(),
+            _ => 0,
+        })
+        .collect()
+}
+
+/// Moves the stepper from its head up to the enclosing group, if that group
+/// has the given attributes. Returns the index the head had in the group.
+fn enter_parent(doc: &mut DocStepper<'_, RtfSchema>, attrs: &Attrs) -> Option<usize> {
+    if doc.at_root() {
+        return None;
+    }
+    let following = doc.skip_len();
+    doc.unenter();
+    match doc.head() {
+        Some(DocGroup(ref parent_attrs, ref span)) if parent_attrs == attrs => {
+            Some(span.len() - following)
+        }
+        _ => None,
+    }
+}
+
+/// Finds the table cell the focus caret is in, if any.
+pub(super) fn find_cell(ctx: &ActionContext) -> Result<Option<TableCursor<'_>>, Error> {
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+
+    // Walk up from the block to its cell, row, and table.
+    let position = {
+        let doc = &mut walker.stepper.doc;
+        enter_parent(doc, &Attrs::TableCell)
+            .and_then(|_| enter_parent(doc, &Attrs::TableRow))
+            .and_then(|column| enter_parent(doc, &Attrs::Table).map(|row| (row, column)))
+    };
+    let (row, column) = match position {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+
+    let cells = match walker.doc().head() {
+        Some(DocGroup(_, ref rows)) => row_lengths(rows),
+        _ => unreachable!(),
+    };
+
+    Ok(Some(TableCursor {
+        table: walker,
+        row,
+        column,
+        cells,
+    }))
+}
+
+/// Adds an empty cell, holding an empty paragraph for the caret to go in.
+fn add_empty_cell(add: &mut AddWriter<RtfSchema>) {
+    add.begin();
+    add.begin();
+    add.close(Attrs::Para);
+    add.close(Attrs::TableCell);
+}
+
+/// Adds the given carets at a walker's position.
+fn add_carets(walker: &Walker<'_>, carets: &[Attrs]) -> Op<RtfSchema> {
+    let mut writer = walker.to_writer();
+    for attrs in carets {
+        writer.add.begin();
+        writer.add.close(attrs.clone());
+    }
+    writer.exit_result()
+}
+
+/// Moves both of the user's carets to a walker's position, collapsing the
+/// selection.
+fn move_carets(ctx: &ActionContext, walker: &Walker<'_>) -> Op<RtfSchema> {
+    Op::transform_advance(
+        &Op::transform_advance(
+            &caret_clear(ctx, Pos::Focus).unwrap_or_else(|_| Op::empty()),
+            &caret_clear(ctx, Pos::Anchor).unwrap_or_else(|_| Op::empty()),
+        ),
+        &add_carets(
+            walker,
+            &[
+                caret_attrs(&ctx.client_id, false),
+                caret_attrs(&ctx.client_id, true),
+            ],
+        ),
+    )
+}
+
+/// Inserts an empty table after the current block, and moves the caret into
+/// its first cell.
+pub fn insert_table(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    // Tables can't be nested.
+    if find_cell(&ctx)?.is_some() {
+        return Ok(Op::empty());
+    }
+
+    let op = {
+        let mut walker = ctx.get_walker(Pos::Focus)?;
+        assert!(walker.back_block());
+        walker.stepper.doc.next();
+
+        let mut writer = walker.to_writer();
+        writer.add.begin();
+        for _ in 0..NEW_TABLE_ROWS {
+            writer.add.begin();
+            for _ in 0..NEW_TABLE_COLUMNS {
+                add_empty_cell(&mut writer.add);
+            }
+            writer.add.close(Attrs::TableRow);
+        }
+        writer.add.close(Attrs::Table);
+        writer.exit_result()
+    };
+    let ctx = ctx.apply(&op)?;
+
+    let op = match find_first_cell_after_focus(&ctx)? {
+        Some(cell) => move_carets(&ctx, &cell),
+        None => Op::empty(),
+    };
+    Ok(ctx.apply(&op)?.result())
+}
+
+/// Returns a walker in the first cell of the table following the focus
+/// caret's block.
+fn find_first_cell_after_focus(ctx: &ActionContext) -> Result<Option<Walker<'_>>, Error> {
+    let mut walker = ctx.get_walker(Pos::Focus)?;
+    assert!(walker.back_block());
+    walker.stepper.doc.next();
+    let cells = match walker.doc().head() {
+        Some(DocGroup(Attrs::Table, ref rows)) => row_lengths(rows),
+        _ => return Ok(None),
+    };
+    Ok(TableCursor {
+        table: walker,
+        row: 0,
+        column: 0,
+        cells,
+    }
+    .cell(0, 0))
+}
+
+/// Adds an empty row below the caret's row.
+pub fn add_table_row(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    let cursor = match find_cell(&ctx)? {
+        Some(cursor) => cursor,
+        None => return Ok(Op::empty()),
+    };
+
+    let mut writer = cursor.table.to_writer();
+    writer.add.begin();
+    writer.add.place(&AddSkip(cursor.row + 1));
+    writer.add.begin();
+    for _ in 0..cursor.cells[cursor.row] {
+        add_empty_cell(&mut writer.add);
+    }
+    writer.add.close(Attrs::TableRow);
+    Ok(writer.exit_result())
+}
+
+/// Removes the caret's row, unless it's the only one. Carets in the row move
+/// to the row below it, or above it for the last row.
+pub fn remove_table_row(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    let cursor = match find_cell(&ctx)? {
+        Some(cursor) => cursor,
+        None => return Ok(Op::empty()),
+    };
+    let rows = cursor.cells.len();
+    if rows < 2 {
+        return Ok(Op::empty());
+    }
+
+    let target_row = if cursor.row + 1 < rows {
+        cursor.row + 1
+    } else {
+        cursor.row - 1
+    };
+    let target = match cursor.cell(target_row, cursor.column) {
+        Some(target) => target,
+        None => return Ok(Op::empty()),
+    };
+    let carets = (0..cursor.cells[cursor.row])
+        .flat_map(|column| cursor.carets_in_cell(cursor.row, column))
+        .collect::<Vec<_>>();
+
+    let mut writer = cursor.table.to_writer();
+    writer.del.begin();
+    if cursor.row > 0 {
+        writer.del.place(&DelSkip(cursor.row));
+    }
+    writer.del.many(1);
+    let op = writer.exit_result();
+
+    Ok(Op::transform_advance(&op, &add_carets(&target, &carets)))
+}
+
+/// Adds an empty column to the right of the caret's column.
+pub fn add_table_column(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    let cursor = match find_cell(&ctx)? {
+        Some(cursor) => cursor,
+        None => return Ok(Op::empty()),
+    };
+
+    let mut writer = cursor.table.to_writer();
+    writer.add.begin();
+    for &columns in &cursor.cells {
+        writer.add.begin();
+        let skip = cmp::min(cursor.column + 1, columns);
+        if skip > 0 {
+            writer.add.place(&AddSkip(skip));
+        }
+        add_empty_cell(&mut writer.add);
+        writer.add.exit();
+    }
+    Ok(writer.exit_result())
+}
+
+/// Removes the caret's column, unless it's the only one. Carets in the
+/// column move to the column to its right, or its left for the last column.
+pub fn remove_table_column(ctx: ActionContext) -> Result<Op<RtfSchema>, Error> {
+    let cursor = match find_cell(&ctx)? {
+        Some(cursor) => cursor,
+        None => return Ok(Op::empty()),
+    };
+    let columns = cursor.cells[cursor.row];
+    if columns < 2 {
+        return Ok(Op::empty());
+    }
+
+    let target_column = if cursor.column + 1 < columns {
+        cursor.column + 1
+    } else {
+        cursor.column - 1
+    };
+    let target = match cursor.cell(cursor.row, target_column) {
+        Some(target) => target,
+        None => return Ok(Op::empty()),
+    };
+
+    let mut carets = vec![];
+    let mut writer = cursor.table.to_writer();
+    writer.del.begin();
+    for (row, &row_columns) in cursor.cells.iter().enumerate() {
+        writer.del.begin();
+        // Rows are never left without cells.
+        if row_columns > cursor.column && row_columns > 1 {
+            if cursor.column > 0 {
+                writer.del.place(&DelSkip(cursor.column));
+            }
+            writer.del.many(1);
+            carets.extend(cursor.carets_in_cell(row, cursor.column));
+        }
+        writer.del.exit();
+    }
+    let op = writer.exit_result();
+
+    // Carets from other rows end up in the caret's row.
+    Ok(Op::transform_advance(&op, &add_carets(&target, &carets)))
+}
+
+/// Moves the caret to the start of the next or previous cell, continuing on
+/// the next or previous row. Does nothing outside of a table.
+pub fn caret_cell_move(ctx: ActionContext, increase: bool) -> Result<Op<RtfSchema>, Error> {
+    let cursor = match find_cell(&ctx)? {
+        Some(cursor) => cursor,
+        None => return Ok(Op::empty()),
+    };
+
+    let (row, column) = if increase {
+        if cursor.column + 1 < cursor.cells[cursor.row] {
+            (cursor.row, cursor.column + 1)
+        } else if cursor.row + 1 < cursor.cells.len() {
+            (cursor.row + 1, 0)
+        } else {
+            return Ok(Op::empty());
+        }
+    } else {
+        if cursor.column > 0 {
+            (cursor.row, cursor.column - 1)
+        } else if cursor.row > 0 {
+            (
+                cursor.row - 1,
+                cursor.cells[cursor.row - 1].saturating_sub(1),
+            )
+        } else {
+            return Ok(Op::empty());
+        }
+    };
+
+    Ok(match cursor.cell(row, column) {
+        Some(cell) => move_carets(&ctx, &cell),
+        None => Op::empty(),
+    })
+}
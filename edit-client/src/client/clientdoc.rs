@@ -1,7 +1,11 @@
 //! Document + versioning state that talks to a synchronization server.
 
+use failure::Error;
 use oatie::doc::*;
-use oatie::rtf::RtfSchema;
+use oatie::rtf::{
+    Attrs,
+    RtfSchema,
+};
 use oatie::validate::validate_doc;
 use std::mem;
 
@@ -9,35 +13,121 @@ use std::mem;
 pub struct ClientDoc {
     pub doc: Doc<RtfSchema>,
     pub version: usize,
-    pub client_id: String,
+    // Given to us by the server when it sends our first document.
+    pub client_id: Option<String>,
+    // Which load of the page on the server our version belongs to.
+    pub generation: usize,
 
     pub original_doc: Doc<RtfSchema>,
     pub pending_op: Option<Op<RtfSchema>>,
     pub local_op: Op<RtfSchema>,
+
+    // Inverse operations, most recent last. Each entry applies to the
+    // document left after undoing every entry above it.
+    pub undo_stack: Vec<Op<RtfSchema>>,
+    pub redo_stack: Vec<Op<RtfSchema>>,
+    pub undo_group: bool,
+}
+
+/// Removes all carets from a document span. Used to determine whether an
+/// operation changed the contents of a document or only moved carets.
+fn strip_carets(span: &DocSpan<RtfSchema>) -> DocSpan<RtfSchema> {
+    let mut res: DocSpan<RtfSchema> = vec![];
+    for elem in span {
+        match elem {
+            DocGroup(Attrs::Caret { .. }, _) => {}
+            DocGroup(attrs, span) => {
+                res.place(&DocGroup(attrs.clone(), strip_carets(span)));
+            }
+            DocText(..) => {
+                res.place(elem);
+            }
+        }
+    }
+    res
+}
+
+/// Removes other clients' carets from an addition. Undoing a change restores
+/// what it deleted, but those carets have moved on since.
+fn strip_other_carets(span: &AddSpan<RtfSchema>, client_id: Option<&str>) -> AddSpan<RtfSchema> {
+    let mut res: AddSpan<RtfSchema> = vec![];
+    for elem in span {
+        match elem {
+            AddGroup(Attrs::Caret { client_id: id, .. }, span)
+                if Some(id.as_str()) != client_id =>
+            {
+                res.place_all(&strip_other_carets(span, client_id));
+            }
+            AddGroup(attrs, span) => {
+                res.place(&AddGroup(attrs.clone(), strip_other_carets(span, client_id)));
+            }
+            AddWithGroup(span) => {
+                res.place(&AddWithGroup(strip_other_carets(span, client_id)));
+            }
+            AddAttrs(attrs, span) => {
+                res.place(&AddAttrs(attrs.clone(), strip_other_carets(span, client_id)));
+            }
+            _ => {
+                res.place(elem);
+            }
+        }
+    }
+    res
+}
+
+/// Whether a deletion span only deletes text, with no other changes.
+fn is_text_deletion(span: &DelSpan<RtfSchema>) -> bool {
+    span.iter().all(|elem| match elem {
+        DelSkip(..) | DelText(..) => true,
+        DelWithGroup(span) => is_text_deletion(span),
+        _ => false,
+    })
+}
+
+/// Transforms a stack of undo or redo entries past an operation that was
+/// applied to the current document. Entries are transformed from the most
+/// recent to the oldest, as each one applies after the one above it.
+fn transform_stack(stack: &mut Vec<Op<RtfSchema>>, op: &Op<RtfSchema>) {
+    let mut op = op.clone();
+    for entry in stack.iter_mut().rev() {
+        let (entry_transform, op_transform) = Op::transform(&op, entry);
+        *entry = entry_transform;
+        op = op_transform;
+    }
 }
 
 impl ClientDoc {
     // Default
-    pub fn new(client_id: String) -> ClientDoc {
+    pub fn new(client_id: Option<String>) -> ClientDoc {
         ClientDoc {
             doc: Doc(vec![]),
             version: 100,
             client_id,
+            generation: 0,
 
             original_doc: Doc(vec![]),
             pending_op: None,
             local_op: Op::empty(),
+
+            undo_stack: vec![],
+            redo_stack: vec![],
+            undo_group: false,
         }
     }
 
     /// Overwrite current state
-    pub fn init(&mut self, new_doc: &Doc<RtfSchema>, version: usize) {
+    pub fn init(&mut self, new_doc: &Doc<RtfSchema>, version: usize, generation: usize) {
         self.doc = new_doc.clone();
         self.version = version;
+        self.generation = generation;
 
         self.original_doc = new_doc.clone();
         self.pending_op = None;
         self.local_op = Op::empty();
+
+        self.undo_stack = vec![];
+        self.redo_stack = vec![];
+        self.undo_group = false;
     }
 
     /// Sync ACK'd our pending operation.
@@ -82,7 +172,7 @@ impl ClientDoc {
         new_doc: &Doc<RtfSchema>,
         version: usize,
         input_op: &Op<RtfSchema>,
-    ) -> (Doc<RtfSchema>, Op<RtfSchema>) {
+    ) -> Result<(Doc<RtfSchema>, Op<RtfSchema>), Error> {
         // log_wasm!(SyncNew("new_op".into()));
         self.assert_compose_correctness(None);
 
@@ -91,10 +181,11 @@ impl ClientDoc {
         // Optimization
         if self.pending_op.is_none() && self.local_op == Op::empty() {
             // Skip ahead
+            self.transform_undo(input_op);
             self.doc = new_doc.clone();
             self.version = version;
             self.original_doc = new_doc.clone();
-            return (current_doc, Op::empty());
+            return Ok((current_doc, Op::empty()));
         }
 
         println!("\n----> TRANSFORMING");
@@ -117,13 +208,14 @@ impl ClientDoc {
         println!();
 
         // I x P -> I', P'
-        let (pending_transform, input_transform) = Op::transform(&input_op, &pending_op);
+        let (pending_transform, input_transform) = Op::try_transform(&input_op, &pending_op)?;
 
         // let pending_final = Op::compose(&pending_transform, &correction);
         // let input_final = Op::compose(&input_transform, &correction);
 
         // P' x L -> P'', L'
-        let (local_transform, _) = Op::transform(&input_transform, &local_op);
+        let (local_transform, input_local_transform) =
+            Op::try_transform(&input_transform, &local_op)?;
 
         // let correction = correct_op(&local_transform).unwrap();
         // let input_correction = correct_op(&input_transform).unwrap();
@@ -179,6 +271,9 @@ impl ClientDoc {
             self.local_op = local_transform;
         }
 
+        // Undo history was recorded against our local document.
+        self.transform_undo(&input_local_transform);
+
         // Update other variables.
         self.version = version;
         self.original_doc = new_doc.clone();
@@ -187,7 +282,7 @@ impl ClientDoc {
 
         self.assert_compose_correctness(None);
 
-        (current_doc, input_transform)
+        Ok((current_doc, input_transform))
     }
 
     /// When there are no payloads queued, queue a next one.
@@ -203,6 +298,17 @@ impl ClientDoc {
         }
     }
 
+    /// The operation to send the server when resuming after a reconnect:
+    /// the pending operation it hasn't acknowledged, or if there is none,
+    /// our queued local operation.
+    pub fn resume_payload(&mut self) -> Option<Op<RtfSchema>> {
+        if self.pending_op.is_some() {
+            self.pending_op.clone()
+        } else {
+            self.next_payload()
+        }
+    }
+
     #[allow(unused)]
     fn assert_compose_correctness(&self, op: Option<Op<RtfSchema>>) {
         // Reference for variable names:
@@ -258,6 +364,74 @@ impl ClientDoc {
 
     /// An operation was applied to the document locally.
     pub fn apply_local_op(&mut self, op: &Op<RtfSchema>) {
+        let inverse = self.invert(op);
+        let last_doc = self.doc.clone();
+
+        self.apply_op(op);
+
+        // Operations which only move our carets aren't undoable, but the
+        // history still needs to be moved past them.
+        if strip_carets(&last_doc.0) == strip_carets(&self.doc.0) {
+            self.transform_undo(op);
+            self.undo_group = false;
+            return;
+        }
+
+        // Consecutive text insertions are undone as a single step.
+        let is_typing = inverse.1.is_empty() && is_text_deletion(&inverse.0);
+        let top = if is_typing && self.undo_group {
+            self.undo_stack.pop()
+        } else {
+            None
+        };
+        self.undo_stack.push(match top {
+            Some(top) => Op::compose(&inverse, &top),
+            None => inverse,
+        });
+        self.undo_group = is_typing;
+
+        // Any new change invalidates the redo history.
+        self.redo_stack = vec![];
+    }
+
+    /// Reverts the most recent local change. Returns the applied operation.
+    pub fn undo(&mut self) -> Option<Op<RtfSchema>> {
+        let op = self.undo_stack.pop()?;
+        let inverse = self.invert(&op);
+        self.redo_stack.push(inverse);
+        self.undo_group = false;
+
+        self.apply_op(&op);
+        Some(op)
+    }
+
+    /// Reapplies the most recently undone change. Returns the applied operation.
+    pub fn redo(&mut self) -> Option<Op<RtfSchema>> {
+        let op = self.redo_stack.pop()?;
+        let inverse = self.invert(&op);
+        self.undo_stack.push(inverse);
+        self.undo_group = false;
+
+        self.apply_op(&op);
+        Some(op)
+    }
+
+    /// Inverts an operation on the current document for the undo or redo
+    /// history, leaving out other clients' carets.
+    fn invert(&self, op: &Op<RtfSchema>) -> Op<RtfSchema> {
+        let Op(del, add) = Op::invert(&self.doc, op);
+        let client_id = self.client_id.as_ref().map(|id| id.as_str());
+        Op(del, strip_other_carets(&add, client_id))
+    }
+
+    /// Moves the undo and redo history past an operation applied to the
+    /// current document which didn't originate from undo or redo.
+    fn transform_undo(&mut self, op: &Op<RtfSchema>) {
+        transform_stack(&mut self.undo_stack, op);
+        transform_stack(&mut self.redo_stack, op);
+    }
+
+    fn apply_op(&mut self, op: &Op<RtfSchema>) {
         self.assert_compose_correctness(Some(op.clone()));
 
         // TODO pending op should be none, but it's actually a value here.
@@ -270,9 +444,6 @@ impl ClientDoc {
         // Apply the new operation.
         self.doc = Op::apply(&self.doc, op);
 
-        // TODO Generate an "undo" version of the operation and store it.
-        // This should come from the Op::apply above.
-
         // Combine operation with previous queued operations.
         self.local_op = Op::compose(&self.local_op, &op);
 
@@ -229,8 +229,11 @@ impl<'a> Walker<'a> {
                 if rstepper.next().is_none() {
                     break;
                 }
-                if let Some(DocGroup(_, _)) = rstepper.doc.head() {
-                    depth -= 1;
+                if let Some(DocGroup(attrs, _)) = rstepper.doc.head() {
+                    // Images are stepped over without being entered.
+                    if !is_image(attrs) {
+                        depth -= 1;
+                    }
                 } else if let None = rstepper.doc.head() {
                     depth += 1;
                 }
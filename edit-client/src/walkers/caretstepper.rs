@@ -7,6 +7,10 @@ pub fn is_block(attrs: &Attrs) -> bool {
     RtfSchema::track_type_from_attrs(attrs) == Some(RtfTrack::Blocks)
 }
 
+pub fn is_list_item(attrs: &Attrs) -> bool {
+    RtfSchema::track_type_from_attrs(attrs) == Some(RtfTrack::ListItems)
+}
+
 // TODO what does this refer to?
 pub fn is_block_object(attrs: &Attrs) -> bool {
     RtfSchema::track_type_from_attrs(attrs) == Some(RtfTrack::BlockObjects)
@@ -33,6 +37,16 @@ pub fn is_any_caret(attrs: &Attrs) -> bool {
     }
 }
 
+/// Images are stepped over as a single character, rather than entered like
+/// other groups.
+pub fn is_image(attrs: &Attrs) -> bool {
+    if let Attrs::Image { .. } = attrs {
+        true
+    } else {
+        false
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub enum Pos {
     Start,
@@ -67,9 +81,12 @@ impl<'a> CaretStepper<'a> {
     }
 
     pub fn is_valid_caret_pos(&self) -> bool {
-        if let Some(DocText(..)) = self.doc.unhead() {
-            return true;
-        } else if self.doc.unhead().is_none() && !self.doc.is_back_done() {
+        match self.doc.unhead() {
+            Some(DocText(..)) => return true,
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => return true,
+            _ => {}
+        }
+        if self.doc.unhead().is_none() && !self.doc.is_back_done() {
             if is_block(self.doc.parent_attrs()) {
                 return true;
             }
@@ -86,6 +103,10 @@ impl<'a> CaretStepper<'a> {
                 self.doc.next();
                 len
             }
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => {
+                self.doc.next();
+                1
+            }
             Some(DocGroup(..)) => {
                 self.doc.enter();
                 1
@@ -116,6 +137,9 @@ impl<'a> Iterator for CaretStepper<'a> {
             Some(DocText(..)) => {
                 self.doc.skip(1);
             }
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => {
+                self.doc.next();
+            }
             Some(DocGroup(..)) => {
                 self.doc.enter();
             }
@@ -155,9 +179,12 @@ impl<'a> ReverseCaretStepper<'a> {
         // more easily.
 
         // Fast-path
-        if let Some(DocText(..)) = self.doc.unhead() {
-            return true;
-        } else if self.doc.unhead().is_none() {
+        match self.doc.unhead() {
+            Some(DocText(..)) => return true,
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => return true,
+            _ => {}
+        }
+        if self.doc.unhead().is_none() {
             if self.doc.at_root() {
                 // end of document, bail
                 return false;
@@ -179,9 +206,12 @@ impl<'a> ReverseCaretStepper<'a> {
         }
 
         // Identically repeat fast-path logic
-        if let Some(DocText(..)) = doc2.unhead() {
-            return true;
-        } else if doc2.unhead().is_none() {
+        match doc2.unhead() {
+            Some(DocText(..)) => return true,
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => return true,
+            _ => {}
+        }
+        if doc2.unhead().is_none() {
             if doc2.at_root() {
                 // end of document, bail
                 return false;
@@ -214,6 +244,9 @@ impl<'a> Iterator for ReverseCaretStepper<'a> {
             Some(DocText(..)) => {
                 self.doc.unskip(1);
             }
+            Some(DocGroup(ref attrs, _)) if is_image(attrs) => {
+                self.doc.unskip(1);
+            }
             Some(DocGroup(..)) => {
                 self.doc.unexit();
             }
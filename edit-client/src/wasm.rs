@@ -12,6 +12,7 @@ use failure::Error;
 use js_sys;
 use serde_json;
 use std::cell::{
+    Cell,
     RefCell,
     RefMut,
 };
@@ -24,6 +25,12 @@ use wasm_bindgen::JsCast;
 use web_sys;
 use crate::monkey::setup_monkey;
 
+/// How long to wait before trying to reconnect to the server.
+const RECONNECT_DELAY_MS: u32 = 2000;
+
+/// The close code the server uses when it disconnects us for misbehaving.
+const CLOSE_CODE_RESTART: u16 = 1012;
+
 lazy_static! {
     static ref WASM_ALIVE: Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
     static ref WASM_MONKEY: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
@@ -158,7 +165,7 @@ extern "C" {
 #[wasm_bindgen]
 impl WasmClientController {
     #[wasm_bindgen(js_name = "clientID")]
-    pub fn client_id(&self) -> String {
+    pub fn client_id(&self) -> Option<String> {
         self.state.borrow().client_doc.client_id.clone()
     }
 
@@ -223,13 +230,49 @@ impl WasmClientController {
     /// commands to the server.
     #[wasm_bindgen(js_name = "subscribeServer")]
     pub fn subscribe_server(&self, ws_url: String) -> Result<WebsocketSend, JsValue> {
-        *self.ws.borrow_mut() = Some(web_sys::WebSocket::new(&ws_url)?);
+        self.connect_server(ws_url, false)?;
+
+        let ws = self.ws.clone();
+        Ok({
+            WebsocketSend {
+                closure: Box::new(move |value: String| {
+                    if let Some(ref mut ws) = *ws.borrow_mut() {
+                        let _ = ws.send_with_str(&value);
+                    } else {
+                        unreachable!();
+                    }
+                }),
+            }
+        })
+    }
+
+    /// Opens the websocket connection. When it closes, we reconnect and
+    /// resume where we left off, so edits made in the meantime are kept.
+    fn connect_server(&self, ws_url: String, resume: bool) -> Result<(), JsValue> {
+        let url = if resume {
+            format!("{}?resume", ws_url)
+        } else {
+            ws_url.clone()
+        };
+        *self.ws.borrow_mut() = Some(web_sys::WebSocket::new(&url)?);
+
+        // Failed attempts to reconnect close without having opened.
+        let opened = Rc::new(Cell::new(false));
 
         {
+            let mut controller = self.clone();
+            let opened = opened.clone();
             let closure = Closure::wrap(Box::new(move |_event: web_sys::Event| {
                 // console.debug('server socket opened.');
                 // DEBUG.measureTime('connect-ready');
                 console_log!("(W) Server socket opened.");
+                opened.set(true);
+
+                if resume {
+                    if let Err(err) = controller.resume_server() {
+                        console_error!("Error resuming: {:?}", err);
+                    }
+                }
             }) as Box<dyn FnMut(_)>);
             if let Some(ref mut ws) = *self.ws.borrow_mut() {
                 ws.add_event_listener_with_callback("open", closure.as_ref().unchecked_ref())?;
@@ -281,11 +324,39 @@ impl WasmClientController {
 
         {
             let mut controller = self.clone();
-            let closure = Closure::wrap(Box::new(move |_event: web_sys::CloseEvent| {
-                console_log!("#### SERVER DISCONNECT");
-                controller
-                    .handle_task(Task::ClientCommand(ClientCommand::ServerDisconnect))
-                    .expect("Client task failed");
+            let closure = Closure::wrap(Box::new(move |event: web_sys::CloseEvent| {
+                if opened.get() {
+                    console_log!("#### SERVER DISCONNECT");
+                    controller
+                        .handle_task(Task::ClientCommand(ClientCommand::ServerDisconnect))
+                        .expect("Client task failed");
+                }
+
+                if !WASM_ALIVE.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                // The server evicted us for sending invalid operations, so
+                // stay disconnected rather than trying them again.
+                if event.code() == CLOSE_CODE_RESTART {
+                    console_error!("Evicted by the server: {}", event.reason());
+                    return;
+                }
+
+                // Try again after a delay. We can only resume once the
+                // server has told us who we are.
+                let reconnect = {
+                    let controller = controller.clone();
+                    let ws_url = ws_url.clone();
+                    Closure::wrap(Box::new(move || {
+                        let resume = controller.client_id().is_some();
+                        if let Err(err) = controller.connect_server(ws_url.clone(), resume) {
+                            console_error!("Error reconnecting: {:?}", err);
+                        }
+                    }) as Box<dyn FnMut()>)
+                };
+                setTimeout(&reconnect, RECONNECT_DELAY_MS);
+                reconnect.forget();
             }) as Box<dyn FnMut(_)>);
             if let Some(ref mut ws) = *self.ws.borrow_mut() {
                 ws.add_event_listener_with_callback("close", closure.as_ref().unchecked_ref())?;
@@ -295,18 +366,7 @@ impl WasmClientController {
             closure.forget();
         }
 
-        let ws = self.ws.clone();
-        Ok({
-            WebsocketSend {
-                closure: Box::new(move |value: String| {
-                    if let Some(ref mut ws) = *ws.borrow_mut() {
-                        let _ = ws.send_with_str(&value);
-                    } else {
-                        unreachable!();
-                    }
-                }),
-            }
-        })
+        Ok(())
     }
 }
 
@@ -317,11 +377,9 @@ pub fn wasm_setup(server_url: String) -> WasmClientController {
     // Set the panic hook to log to console.error.
     console_error_panic_hook::set_once();
 
-    let editor_id = "$$$$$$".to_string();
-
     // Setup monkey tasks.
     let client = Rc::new(RefCell::new(Client {
-        client_doc: ClientDoc::new(editor_id.clone()),
+        client_doc: ClientDoc::new(None),
         last_controls: None,
         last_caret_state: None,
 
@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_client::*;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+fn caret(client_id: &str, focus: bool) -> Attrs {
+    Attrs::Caret {
+        client_id: client_id.to_string(),
+        focus,
+    }
+}
+
+/// "hello" with all of it selected by the client "left".
+fn selected_ctx() -> ActionContext {
+    let doc = doc![DocGroup(Attrs::Para, [
+        DocGroup(caret("left", false), []),
+        DocText("hello"),
+        DocGroup(caret("left", true), []),
+    ])];
+    ActionContext::new(doc, "left".to_string())
+}
+
+#[test]
+fn set_link_refuses_unsafe_urls() {
+    for url in &["javascript:alert(1)", " JavaScript:alert(1)", "data:text/html,hi"] {
+        assert!(set_link(selected_ctx(), Some(url.to_string())).is_err());
+    }
+}
+
+#[test]
+fn set_link_allows_safe_urls() {
+    for url in &["https://example.com/", "mailto:hi@example.com", "/page"] {
+        let op = set_link(selected_ctx(), Some(url.to_string())).unwrap();
+        assert_ne!(op, Op::empty());
+    }
+}
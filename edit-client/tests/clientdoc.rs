@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_client::*;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+fn caret(client_id: &str) -> Attrs {
+    Attrs::Caret {
+        client_id: client_id.to_string(),
+        focus: true,
+    }
+}
+
+#[test]
+fn undo_leaves_out_other_carets() {
+    let mut client_doc = ClientDoc::new(Some("left".to_string()));
+    client_doc.init(
+        &doc![DocGroup(Attrs::Para, [
+            DocText("ab"),
+            DocGroup(caret("right"), []),
+            DocText("cd"),
+            DocGroup(caret("left"), []),
+        ])],
+        100,
+        0,
+    );
+
+    // Deleting the text also deletes the other client's caret.
+    let op = Op(
+        vec![DelWithGroup(vec![DelText(2), DelGroupAll, DelText(2)])],
+        vec![],
+    );
+    client_doc.apply_local_op(&op);
+    let deleted = client_doc.doc.clone();
+
+    // Undo restores the text, but not the caret.
+    client_doc.undo().unwrap();
+    assert_eq!(
+        client_doc.doc,
+        doc![DocGroup(Attrs::Para, [
+            DocText("abcd"),
+            DocGroup(caret("left"), []),
+        ])]
+    );
+
+    client_doc.redo().unwrap();
+    assert_eq!(client_doc.doc, deleted);
+}
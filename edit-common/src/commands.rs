@@ -8,7 +8,11 @@ use wasm_typescript_definition::*;
 #[serde(tag = "tag", content = "fields")]
 pub enum ServerCommand {
     // Connect(String),
-    Commit(String, Op<RtfSchema>, usize),
+    // Client id, operation, version, page generation
+    Commit(String, Op<RtfSchema>, usize, usize),
+    // Sent first after reconnecting: client id, last acknowledged version,
+    // page generation, and the operation that wasn't acknowledged (if any)
+    Resume(String, usize, usize, Option<Op<RtfSchema>>),
     Log(String),
     TerminateProxy,
 }
@@ -17,15 +21,30 @@ pub enum ServerCommand {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TypescriptDefinition)]
 #[serde(tag = "tag", content = "fields")]
 pub enum ClientCommand {
-    // Client id assignment, initial doc, initial version
-    Init(String, DocSpan<RtfSchema>, usize),
+    // Client id assignment, initial doc, initial version, page generation
+    Init(String, DocSpan<RtfSchema>, usize, usize),
 
     // New document, version, client-id, operation
     Update(usize, String, Op<RtfSchema>),
 
+    // The server rejected our last operation
+    Error { code: ErrorCode, message: String },
+
     ServerDisconnect,
 }
 
+// Why the server rejected an operation from a client.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, TypescriptDefinition)]
+#[serde(tag = "tag", content = "fields")]
+pub enum ErrorCode {
+    // The operation was made against a version the server doesn't have
+    InvalidVersion,
+    // The operation doesn't apply to the document
+    InvalidOperation,
+    // Applying the operation results in an invalid document
+    InvalidDocument,
+}
+
 // Controller is the client interface that is exposed to the frnontend.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, TypescriptDefinition)]
 #[serde(tag = "tag", content = "fields")]
@@ -48,6 +67,14 @@ pub enum ControllerCommand {
     InsertText {
         text: String,
     },
+    // Replaces the selection with the HTML contents of the clipboard.
+    PasteHtml {
+        html: String,
+    },
+    // Sent in reply to PromptString; an empty URL removes the link.
+    Link {
+        url: String,
+    },
     RenameGroup {
         tag: String,
         curspan: CurSpan,
@@ -59,6 +86,10 @@ pub enum ControllerCommand {
     CursorSelectWord {
         focus: CurSpan,
     },
+    // Checks or unchecks the task list item the cursor points to.
+    ToggleTask {
+        curspan: CurSpan,
+    },
     RandomTarget {
         position: f64,
     },
@@ -73,6 +104,8 @@ pub enum ControllerCommand {
 pub enum FrontendCommand {
     Init(String),
     Controls(Controls),
+    // Label, default value, and a command with one field, sent back with
+    // the answer in that field.
     PromptString(String, String, ControllerCommand),
     // Bytecode, Op
     RenderDelta(String, Op<RtfSchema>),
@@ -82,6 +115,7 @@ pub enum FrontendCommand {
     ServerCommand(ServerCommand),
 
     ServerDisconnect,
+    ServerReconnect,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -0,0 +1,216 @@
+//! Syntax highlighting for code blocks. This is a small tokenizer rather
+//! than a full grammar: it finds comments, strings, numbers, and keywords,
+//! which is enough to make code readable in rendered pages. (syntect needs
+//! the Oniguruma C library for its grammars, so it isn't used here.)
+//!
+//! Only these languages are highlighted, by any of their names in `SYNTAXES`:
+//! Rust, JavaScript and TypeScript, Python, Go, C and C++, Java, Kotlin,
+//! Swift and C#, shell scripts, JSON, and TOML and INI. Code in any other
+//! language is rendered as plain text.
+
+use htmlescape::encode_minimal;
+
+struct Syntax {
+    names: &'static [&'static str],
+    /// Space-separated keywords.
+    keywords: &'static str,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const C_KEYWORDS: &str = concat!(
+    "auto break case char const continue default do double else enum extern float for ",
+    "goto if int long register return short signed sizeof static struct switch typedef ",
+    "union unsigned void volatile while class namespace template typename public ",
+    "private protected virtual new delete this true false nullptr bool"
+);
+
+const SYNTAXES: &[Syntax] = &[
+    Syntax {
+        names: &["rust", "rs"],
+        keywords: concat!(
+            "as async await break const continue crate dyn else enum extern false fn for if ",
+            "impl in let loop match mod move mut pub ref return self Self static struct ",
+            "super trait true type unsafe use where while"
+        ),
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Syntax {
+        names: &["javascript", "js", "typescript", "ts", "jsx", "tsx"],
+        keywords: concat!(
+            "async await break case catch class const continue default delete do else ",
+            "export extends false finally for from function if import in instanceof ",
+            "interface let new null return static super switch this throw true try type ",
+            "typeof undefined var void while yield"
+        ),
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Syntax {
+        names: &["python", "py"],
+        keywords: concat!(
+            "and as assert async await break class continue def del elif else except False ",
+            "finally for from global if import in is lambda None nonlocal not or pass raise ",
+            "return True try while with yield"
+        ),
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        names: &["go", "golang"],
+        keywords: concat!(
+            "break case chan const continue default defer else fallthrough false for func ",
+            "go goto if import interface map nil package range return select struct switch ",
+            "true type var"
+        ),
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '`'],
+    },
+    Syntax {
+        names: &["c", "h", "cpp", "c++", "cc", "hpp"],
+        keywords: C_KEYWORDS,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        names: &["java", "kotlin", "swift", "csharp", "cs"],
+        keywords: concat!(
+            "abstract break case catch class const continue default do else enum extends ",
+            "false final finally for fun func if implements import interface let new null ",
+            "override package private protected public return static super switch this ",
+            "throw true try val var void while"
+        ),
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        names: &["sh", "bash", "shell", "zsh"],
+        keywords: concat!(
+            "case do done elif else esac export fi for function if in local return then ",
+            "while"
+        ),
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        names: &["json"],
+        keywords: "true false null",
+        line_comments: &[],
+        block_comment: None,
+        quotes: &['"'],
+    },
+    Syntax {
+        names: &["toml", "ini"],
+        keywords: "true false",
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+];
+
+fn find_syntax(language: &str) -> Option<&'static Syntax> {
+    let language = language.to_lowercase();
+    SYNTAXES
+        .iter()
+        .find(|syntax| syntax.names.contains(&language.as_str()))
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Finds the token at the start of `rest`, returning its kind and byte
+/// length. Words that aren't keywords have an empty kind.
+fn scan(syntax: &Syntax, rest: &str) -> Option<(&'static str, usize)> {
+    for comment in syntax.line_comments {
+        if rest.starts_with(comment) {
+            return Some(("comment", rest.find('\n').unwrap_or(rest.len())));
+        }
+    }
+
+    if let Some((start, end)) = syntax.block_comment {
+        if rest.starts_with(start) {
+            let len = rest[start.len()..]
+                .find(end)
+                .map(|i| start.len() + i + end.len())
+                .unwrap_or(rest.len());
+            return Some(("comment", len));
+        }
+    }
+
+    let first = rest.chars().next()?;
+    if syntax.quotes.contains(&first) {
+        let mut escaped = false;
+        for (i, c) in rest.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == first {
+                return Some(("string", i + c.len_utf8()));
+            }
+        }
+        return Some(("string", rest.len()));
+    }
+
+    if first.is_ascii_digit() {
+        let len = rest
+            .find(|c: char| !(is_ident(c) || c == '.'))
+            .unwrap_or(rest.len());
+        return Some(("number", len));
+    }
+
+    if is_ident(first) {
+        let len = rest.find(|c: char| !is_ident(c)).unwrap_or(rest.len());
+        let word = &rest[..len];
+        if syntax.keywords.split(' ').any(|keyword| keyword == word) {
+            return Some(("keyword", len));
+        }
+        // Skip the whole word, so keywords inside identifiers aren't matched.
+        return Some(("", len));
+    }
+
+    None
+}
+
+/// Renders code as HTML with each comment, string, number, and keyword
+/// wrapped in a span with a class like "hl-keyword". Returns None if the
+/// language isn't one we can highlight.
+pub fn highlight_html(language: &str, code: &str) -> Option<String> {
+    let syntax = find_syntax(language)?;
+
+    let mut out = String::new();
+    let mut plain = 0;
+    let mut i = 0;
+    while i < code.len() {
+        match scan(syntax, &code[i..]) {
+            Some((kind, len)) if !kind.is_empty() => {
+                out.push_str(&encode_minimal(&code[plain..i]));
+                out.push_str(&format!(
+                    r#"<span class="hl-{}">{}</span>"#,
+                    kind,
+                    encode_minimal(&code[i..i + len])
+                ));
+                i += len;
+                plain = i;
+            }
+            Some((_, len)) => {
+                i += len;
+            }
+            None => {
+                i += code[i..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+            }
+        }
+    }
+    out.push_str(&encode_minimal(&code[plain..]));
+    Some(out)
+}
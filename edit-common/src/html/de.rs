@@ -0,0 +1,484 @@
+//! Converts HTML, like the contents of the clipboard, to a document. Only
+//! markup the document can represent is kept; other tags are dropped, but the
+//! text inside of them is not.
+
+use failure::Error;
+use htmlescape::decode_html;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::writer::DocWriter;
+use std::cmp;
+use std::collections::HashMap;
+
+/// Elements that never have contents or an end tag.
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose contents aren't text to paste.
+const SKIPPED_TAGS: &[&str] = &["script", "style", "template", "title"];
+
+/// Elements that lay out blocks, but that have no group of their own. Their
+/// start and end separate the text around them into paragraphs.
+const LAYOUT_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "body",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "html",
+    "main",
+    "nav",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A start tag, with its lowercased name and its attributes.
+    Start(String, HashMap<String, String>),
+    End(String),
+    Text(String),
+}
+
+/// Decodes character references, leaving text with invalid ones as it is.
+fn decode(text: &str) -> String {
+    decode_html(text).unwrap_or_else(|_| text.to_string())
+}
+
+/// The length of the tag or attribute name at the start of the input.
+fn name_len(input: &str) -> usize {
+    input
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+        .unwrap_or(input.len())
+}
+
+/// Splits HTML into tags and text. This is much more forgiving than it is
+/// correct: comments, doctypes, and the contents of scripts and styles are
+/// dropped, and anything that doesn't parse as a tag is text.
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            rest = rest[4..].find("-->").map(|i| &rest[i + 7..]).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map(|i| &rest[i + 1..]).unwrap_or("");
+            continue;
+        }
+
+        let is_end = rest.starts_with("</");
+        let name_start = if is_end { 2 } else { 1 };
+        let is_tag = rest.starts_with('<')
+            && rest[name_start..]
+                .chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic())
+                .unwrap_or(false);
+        if !is_tag {
+            // Text runs until the next tag.
+            let first = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(0);
+            let len = rest[first..]
+                .find('<')
+                .map(|i| i + first)
+                .unwrap_or(rest.len());
+            tokens.push(Token::Text(decode(&rest[..len])));
+            rest = &rest[len..];
+            continue;
+        }
+
+        rest = &rest[name_start..];
+        let len = name_len(rest);
+        let name = rest[..len].to_ascii_lowercase();
+        rest = &rest[len..];
+
+        // Read attributes until the end of the tag.
+        let mut attrs = HashMap::new();
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+            if rest.is_empty() {
+                break;
+            }
+            if rest.starts_with('>') {
+                rest = &rest[1..];
+                break;
+            }
+            let len = cmp::max(name_len(rest), 1);
+            let key = rest[..len].to_ascii_lowercase();
+            rest = rest[len..].trim_start();
+            let mut value = String::new();
+            if rest.starts_with('=') {
+                rest = rest[1..].trim_start();
+                let (len, skip) = match rest.chars().next() {
+                    Some(quote @ '"') | Some(quote @ '\'') => {
+                        (rest[1..].find(quote).unwrap_or(rest.len() - 1), 1)
+                    }
+                    _ => (
+                        rest.find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(rest.len()),
+                        0,
+                    ),
+                };
+                value = decode(&rest[skip..skip + len]);
+                rest = &rest[cmp::min(skip * 2 + len, rest.len())..];
+            }
+            attrs.insert(key, value);
+        }
+
+        if is_end {
+            tokens.push(Token::End(name));
+        } else if SKIPPED_TAGS.contains(&name.as_str()) {
+            // Skip past the element's end tag.
+            let end = format!("</{}", name);
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&end)
+                .map(|i| &rest[i..])
+                .unwrap_or("");
+        } else {
+            tokens.push(Token::Start(name, attrs));
+        }
+    }
+    tokens
+}
+
+/// An element we're in.
+enum Open {
+    /// A group that holds blocks, like a list item, and whether any blocks
+    /// have been added to it yet.
+    Container(Attrs, bool),
+    /// A block that holds text, like a paragraph.
+    Block(Attrs),
+    /// A list, with the number it starts counting from if it's numbered.
+    List(Option<u64>),
+    Style(RtfStyle),
+    /// An element with no group or style of its own.
+    Other,
+}
+
+struct Ctx<'a> {
+    body: &'a mut DocWriter<RtfSchema>,
+    /// The tag name of each element we're in, and what it opened.
+    stack: Vec<(String, Open)>,
+    /// Whether a paragraph has been opened around text outside of a block.
+    bare_para: bool,
+    /// Whether nothing has been written to the current line yet.
+    line_start: bool,
+    /// The styles of whitespace skipped since the last text written.
+    pending_space: Option<StyleSet>,
+}
+
+impl<'a> Ctx<'a> {
+    fn run(&mut self, tokens: Vec<Token>) {
+        for token in tokens {
+            match token {
+                Token::Start(name, attrs) => self.start_tag(name, attrs),
+                Token::End(name) => self.end_tag(&name),
+                Token::Text(text) => self.text(&text),
+            }
+        }
+        self.pop_to(0);
+        self.close_bare_para();
+    }
+
+    fn styles(&self) -> StyleSet {
+        let mut styles = StyleSet::new();
+        for (_, open) in &self.stack {
+            if let Open::Style(style) = open {
+                styles.insert(style.clone());
+            }
+        }
+        styles
+    }
+
+    /// The index of the block we're in, if any.
+    fn block_index(&self) -> Option<usize> {
+        self.stack.iter().rposition(|(_, open)| match open {
+            Open::Block(..) => true,
+            _ => false,
+        })
+    }
+
+    fn in_code(&self) -> bool {
+        match self.block_index().map(|i| &self.stack[i].1) {
+            Some(Open::Block(Attrs::Code(..))) => true,
+            _ => false,
+        }
+    }
+
+    /// Records that a group was added to the container we're in.
+    fn added_child(&mut self) {
+        for (_, open) in self.stack.iter_mut().rev() {
+            if let Open::Container(_, ref mut has_children) = open {
+                *has_children = true;
+                return;
+            }
+        }
+    }
+
+    fn open_bare_para(&mut self) {
+        // Text outside of a block, as in a tight list item, is wrapped in a
+        // paragraph that lasts until the next block starts or ends.
+        if self.block_index().is_none() && !self.bare_para {
+            self.body.begin();
+            self.bare_para = true;
+            self.line_start = true;
+        }
+    }
+
+    fn close_bare_para(&mut self) {
+        if self.bare_para {
+            self.body.close(Attrs::Para);
+            self.bare_para = false;
+            self.added_child();
+        }
+    }
+
+    /// Ends the block or bare paragraph we're in, if any.
+    fn close_block(&mut self) {
+        if let Some(index) = self.block_index() {
+            self.pop_to(index);
+        }
+        self.close_bare_para();
+    }
+
+    /// Closes every element from the given index of the stack on.
+    fn pop_to(&mut self, index: usize) {
+        while self.stack.len() > index {
+            match self.stack.pop().unwrap().1 {
+                Open::Container(attrs, has_children) => {
+                    self.close_bare_para();
+                    // Containers can't be empty.
+                    if !has_children {
+                        self.body.begin();
+                        self.body.close(Attrs::Para);
+                    }
+                    self.body.close(attrs);
+                    self.added_child();
+                }
+                Open::Block(attrs) => {
+                    self.body.close(attrs);
+                    self.added_child();
+                }
+                Open::List(..) | Open::Style(..) | Open::Other => {}
+            }
+        }
+    }
+
+    fn start_block(&mut self, name: String, attrs: Attrs) {
+        self.close_block();
+        self.body.begin();
+        self.stack.push((name, Open::Block(attrs)));
+        self.line_start = true;
+        self.pending_space = None;
+    }
+
+    fn start_container(&mut self, name: String, attrs: Attrs) {
+        self.close_block();
+        self.body.begin();
+        self.stack.push((name, Open::Container(attrs, false)));
+    }
+
+    fn start_tag(&mut self, name: String, attrs: HashMap<String, String>) {
+        let open = match name.as_str() {
+            // Blocks
+            "p" => return self.start_block(name, Attrs::Para),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                return self.start_block(name, Attrs::Header(level));
+            }
+            "pre" => return self.start_block(name, Attrs::Code(None)),
+            "code" if self.in_code() => {
+                // Code blocks name their language in a class on the code
+                // element inside of them, like "language-rust".
+                let language = attrs.get("class").and_then(|class| {
+                    class
+                        .split_whitespace()
+                        .find(|x| x.starts_with("language-"))
+                        .map(|x| x["language-".len()..].to_string())
+                });
+                if let Some(index) = self.block_index() {
+                    if language.is_some() {
+                        self.stack[index].1 = Open::Block(Attrs::Code(language));
+                    }
+                }
+                Open::Other
+            }
+
+            // Lists and quotes
+            "ul" => {
+                self.close_block();
+                Open::List(None)
+            }
+            "ol" => {
+                self.close_block();
+                let start = attrs
+                    .get("start")
+                    .and_then(|x| x.trim().parse().ok())
+                    .unwrap_or(1);
+                Open::List(Some(start))
+            }
+            "li" => {
+                // A list item ends the previous item in its list.
+                let item = self.stack.iter().rposition(|(name, open)| match open {
+                    Open::List(..) => true,
+                    _ => name == "li",
+                });
+                if let Some(index) = item.filter(|&i| self.stack[i].0 == "li") {
+                    self.pop_to(index);
+                }
+
+                let list = self.stack.iter().rev().find_map(|(_, open)| match open {
+                    Open::List(start) => Some(*start),
+                    _ => None,
+                });
+                let attrs = match list {
+                    Some(Some(start)) => Attrs::OrderedListItem(start),
+                    _ => Attrs::ListItem,
+                };
+                return self.start_container(name, attrs);
+            }
+            "blockquote" => return self.start_container(name, Attrs::BlockQuote),
+
+            // Block objects
+            "hr" => {
+                self.close_block();
+                self.body.begin();
+                self.body.close(Attrs::Rule);
+                self.added_child();
+                return;
+            }
+
+            // Spans
+            "b" | "strong" => Open::Style(RtfStyle::Bold),
+            "i" | "em" => Open::Style(RtfStyle::Italic),
+            "code" | "kbd" | "samp" | "tt" => Open::Style(RtfStyle::Code),
+            "s" | "strike" | "del" => Open::Style(RtfStyle::Strikethrough),
+            "a" => match attrs.get("href") {
+                Some(href) if !href.is_empty() => Open::Style(RtfStyle::Link(href.to_string())),
+                _ => Open::Other,
+            },
+            "br" => {
+                self.place_text("\n", self.styles());
+                self.line_start = true;
+                self.pending_space = None;
+                return;
+            }
+
+            // Inline objects
+            "img" => {
+                // Code blocks only hold text.
+                if let Some(src) = attrs.get("src").filter(|_| !self.in_code()) {
+                    self.open_bare_para();
+                    self.flush_space();
+                    self.body.begin();
+                    self.body.close(Attrs::Image {
+                        src: src.to_string(),
+                        alt: attrs.get("alt").cloned().unwrap_or_default(),
+                    });
+                    self.line_start = false;
+                }
+                return;
+            }
+
+            _ => {
+                if LAYOUT_TAGS.contains(&name.as_str()) {
+                    self.close_block();
+                }
+                Open::Other
+            }
+        };
+
+        if !VOID_TAGS.contains(&name.as_str()) {
+            self.stack.push((name, open));
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        // End tags without a matching start tag are ignored.
+        if let Some(index) = self.stack.iter().rposition(|(open, _)| open == name) {
+            self.pop_to(index);
+        }
+        if LAYOUT_TAGS.contains(&name) || name == "ul" || name == "ol" {
+            self.close_block();
+        }
+    }
+
+    /// Writes a space for whitespace skipped between words.
+    fn flush_space(&mut self) {
+        if let Some(styles) = self.pending_space.take() {
+            if !self.line_start {
+                self.place_text(" ", styles);
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        // Code blocks keep their whitespace, except for a newline right
+        // after the "<pre>" tag.
+        if self.in_code() {
+            let text = if self.line_start && text.starts_with('\n') {
+                &text[1..]
+            } else {
+                text
+            };
+            if !text.is_empty() {
+                self.place_text(text, StyleSet::new());
+            }
+            return;
+        }
+
+        // Elsewhere, runs of whitespace are collapsed into a single space,
+        // and dropped at the start and end of blocks.
+        for (i, word) in text.split(|c: char| c.is_ascii_whitespace()).enumerate() {
+            if i > 0 && self.pending_space.is_none() {
+                self.pending_space = Some(self.styles());
+            }
+            if !word.is_empty() {
+                self.open_bare_para();
+                self.flush_space();
+                self.place_text(word, self.styles());
+            }
+        }
+    }
+
+    fn place_text(&mut self, text: &str, styles: StyleSet) {
+        self.open_bare_para();
+        self.line_start = false;
+        self.body.place(&DocText(styles, DocString::from_str(text)));
+    }
+}
+
+pub fn html_to_doc(input: &str) -> Result<DocSpan<RtfSchema>, Error> {
+    let mut doc_writer = DocWriter::new();
+    {
+        let mut ctx = Ctx {
+            body: &mut doc_writer,
+            stack: vec![],
+            bare_para: false,
+            line_start: true,
+            pending_space: None,
+        };
+        ctx.run(tokenize(input));
+    }
+    doc_writer.result()
+}
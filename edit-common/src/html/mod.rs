@@ -0,0 +1,25 @@
+pub mod de;
+pub mod ser;
+
+pub use self::de::html_to_doc;
+pub use self::ser::doc_to_html;
+
+/// Whether a URL is safe to write into an `href` or `src` attribute: it's
+/// relative, or its scheme is http, https or mailto. Browsers ignore
+/// whitespace and control characters in a scheme, so they're skipped before
+/// reading it, which keeps `" java\tscript:"` from slipping through.
+pub fn is_safe_url(url: &str) -> bool {
+    let url = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>();
+    let scheme = match url.find(|c: char| c == ':' || c == '/' || c == '?' || c == '#') {
+        Some(end) if url[end..].starts_with(':') => url[..end].to_lowercase(),
+        // Without a scheme, the URL is relative.
+        _ => return true,
+    };
+    match scheme.as_str() {
+        "http" | "https" | "mailto" => true,
+        _ => false,
+    }
+}
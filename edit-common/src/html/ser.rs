@@ -0,0 +1,275 @@
+//! Converts a document to semantic HTML, for exporting pages. Unlike
+//! `doc_as_html`, which writes the markup the editor renders, this writes
+//! standard elements and leaves out carets.
+
+use super::is_safe_url;
+use htmlescape::{
+    encode_attribute,
+    encode_minimal,
+};
+use oatie::doc::*;
+use oatie::rtf::*;
+
+/// An inline element wrapping styled text. Elements are nested in the order
+/// they are listed here, so that inline code is always innermost.
+#[derive(Clone, Debug, PartialEq)]
+enum Span {
+    Link(String),
+    Strong,
+    Emphasis,
+    Strikethrough,
+    Code,
+}
+
+impl Span {
+    /// The elements text with these styles is wrapped in, outermost first.
+    fn from_styles(styles: &StyleSet) -> Vec<Span> {
+        let mut spans = vec![];
+        // Links to unsafe URLs are written as plain text.
+        if let Some(url) = styles.link().filter(|url| is_safe_url(url)) {
+            spans.push(Span::Link(url.to_string()));
+        }
+        if styles.contains(&RtfStyle::Bold) {
+            spans.push(Span::Strong);
+        }
+        if styles.contains(&RtfStyle::Italic) {
+            spans.push(Span::Emphasis);
+        }
+        if styles.contains(&RtfStyle::Strikethrough) {
+            spans.push(Span::Strikethrough);
+        }
+        if styles.contains(&RtfStyle::Code) {
+            spans.push(Span::Code);
+        }
+        spans
+    }
+
+    fn start(&self) -> String {
+        match self {
+            Span::Link(url) => format!(r#"<a href="{}">"#, encode_attribute(url)),
+            Span::Strong => "<strong>".to_string(),
+            Span::Emphasis => "<em>".to_string(),
+            Span::Strikethrough => "<s>".to_string(),
+            Span::Code => "<code>".to_string(),
+        }
+    }
+
+    fn end(&self) -> &'static str {
+        match self {
+            Span::Link(_) => "</a>",
+            Span::Strong => "</strong>",
+            Span::Emphasis => "</em>",
+            Span::Strikethrough => "</s>",
+            Span::Code => "</code>",
+        }
+    }
+}
+
+/// The list element a list item is written in.
+fn list_tag(attrs: &Attrs) -> Option<&'static str> {
+    match attrs {
+        Attrs::ListItem | Attrs::TaskListItem { .. } => Some("ul"),
+        Attrs::OrderedListItem(..) => Some("ol"),
+        _ => None,
+    }
+}
+
+/// The text of a block, without its styles.
+fn plain_text(span: &DocSpan<RtfSchema>) -> String {
+    span.iter()
+        .filter_map(|elem| match elem {
+            DocText(_, text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+struct DocToHtml {
+    out: String,
+    /// Inline elements currently open, outermost first.
+    spans: Vec<Span>,
+}
+
+impl DocToHtml {
+    /// Closes open inline elements until only the first `keep` remain.
+    fn close_spans(&mut self, keep: usize) {
+        while self.spans.len() > keep {
+            let span = self.spans.pop().unwrap();
+            self.out.push_str(span.end());
+        }
+    }
+
+    /// Closes and opens inline elements so exactly `spans` are open, leaving
+    /// open any that are already open in the same order.
+    fn set_spans(&mut self, spans: Vec<Span>) {
+        let keep = self
+            .spans
+            .iter()
+            .zip(&spans)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.close_spans(keep);
+        for span in spans.into_iter().skip(keep) {
+            self.out.push_str(&span.start());
+            self.spans.push(span);
+        }
+    }
+
+    /// Writes a span of elements. Runs of list items are wrapped in a list,
+    /// and table cells write their paragraph's contents without a `<p>`.
+    fn write_span(&mut self, span: &DocSpan<RtfSchema>, in_cell: bool) {
+        let mut list = None;
+        for elem in span {
+            // Carets aren't part of the document's content.
+            if let DocGroup(Attrs::Caret { .. }, _) = elem {
+                continue;
+            }
+
+            // End the open list before anything that isn't an item in it.
+            let elem_list = match elem {
+                DocGroup(attrs, _) => list_tag(attrs),
+                _ => None,
+            };
+            if list.is_some() && list != elem_list {
+                self.out.push_str(&format!("</{}>\n", list.take().unwrap()));
+            }
+
+            match elem {
+                DocText(styles, text) => {
+                    self.set_spans(Span::from_styles(styles));
+                    for (i, line) in text.as_str().split('\n').enumerate() {
+                        if i > 0 {
+                            self.out.push_str("<br>");
+                        }
+                        self.out.push_str(&encode_minimal(line));
+                    }
+                }
+                DocGroup(Attrs::Image { src, alt }, _) => {
+                    // Images from unsafe URLs are written as their alt text.
+                    if !is_safe_url(src) {
+                        self.out.push_str(&encode_minimal(alt));
+                        continue;
+                    }
+                    self.out.push_str(&format!(
+                        r#"<img src="{}" alt="{}">"#,
+                        encode_attribute(src),
+                        encode_attribute(alt),
+                    ));
+                }
+                DocGroup(attrs, span) => {
+                    // Start a list at its first item.
+                    if let (None, Some(tag)) = (list, elem_list) {
+                        list = elem_list;
+                        self.out.push_str(&match attrs {
+                            Attrs::OrderedListItem(start) if *start != 1 => {
+                                format!("<ol start=\"{}\">\n", start)
+                            }
+                            _ => format!("<{}>\n", tag),
+                        });
+                    }
+                    self.write_group(attrs, span, in_cell);
+                }
+            }
+        }
+        self.close_spans(0);
+        if let Some(tag) = list {
+            self.out.push_str(&format!("</{}>\n", tag));
+        }
+    }
+
+    /// Wraps a group's contents in an element.
+    fn write_element(&mut self, tag: &str, span: &DocSpan<RtfSchema>, in_cell: bool) {
+        self.out.push_str(&format!("<{}>", tag));
+        self.write_span(span, in_cell);
+        self.out.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn write_group(&mut self, attrs: &Attrs, span: &DocSpan<RtfSchema>, in_cell: bool) {
+        match attrs {
+            Attrs::Para if in_cell => self.write_span(span, false),
+            Attrs::Para => self.write_element("p", span, false),
+            Attrs::Header(level) => self.write_element(&format!("h{}", level), span, false),
+            Attrs::Code(language) => {
+                match language {
+                    Some(language) => self.out.push_str(&format!(
+                        r#"<pre><code class="language-{}">"#,
+                        encode_attribute(language)
+                    )),
+                    None => self.out.push_str("<pre><code>"),
+                }
+                self.out.push_str(&encode_minimal(&plain_text(span)));
+                self.out.push_str("</code></pre>\n");
+            }
+            // HTML blocks are shown as source rather than written as they
+            // are, since pages can't be trusted to hold safe markup.
+            Attrs::Html => {
+                self.out.push_str("<pre>");
+                self.out.push_str(&encode_minimal(&plain_text(span)));
+                self.out.push_str("</pre>\n");
+            }
+            Attrs::ListItem | Attrs::OrderedListItem(..) => self.write_element("li", span, false),
+            Attrs::TaskListItem { checked } => {
+                self.out.push_str(if *checked {
+                    r#"<li><input type="checkbox" checked disabled> "#
+                } else {
+                    r#"<li><input type="checkbox" disabled> "#
+                });
+                self.write_span(span, false);
+                self.out.push_str("</li>\n");
+            }
+            Attrs::BlockQuote => self.write_element("blockquote", span, false),
+            Attrs::Rule => self.out.push_str("<hr>\n"),
+            Attrs::Table => {
+                // The first row is the table's header.
+                self.out.push_str("<table>\n");
+                for (i, row) in span.iter().enumerate() {
+                    let cells = match row {
+                        DocGroup(_, cells) => cells,
+                        _ => continue,
+                    };
+                    let cell_tag = match i {
+                        0 => {
+                            self.out.push_str("<thead>\n");
+                            "th"
+                        }
+                        1 => {
+                            self.out.push_str("<tbody>\n");
+                            "td"
+                        }
+                        _ => "td",
+                    };
+                    self.out.push_str("<tr>");
+                    for cell in cells {
+                        if let DocGroup(_, blocks) = cell {
+                            self.out.push_str(&format!("<{}>", cell_tag));
+                            self.write_span(blocks, true);
+                            self.out.push_str(&format!("</{}>", cell_tag));
+                        }
+                    }
+                    self.out.push_str("</tr>\n");
+                    if i == 0 {
+                        self.out.push_str("</thead>\n");
+                    }
+                }
+                if span.len() > 1 {
+                    self.out.push_str("</tbody>\n");
+                }
+                self.out.push_str("</table>\n");
+            }
+            // Rows and cells are written with their table.
+            Attrs::TableRow => self.write_element("tr", span, false),
+            Attrs::TableCell => self.write_element("td", span, true),
+            // Written in place by write_span.
+            Attrs::Caret { .. } | Attrs::Image { .. } => {}
+        }
+    }
+}
+
+pub fn doc_to_html(doc: &DocSpan<RtfSchema>) -> String {
+    let mut writer = DocToHtml {
+        out: String::new(),
+        spans: vec![],
+    };
+    writer.write_span(doc, false);
+    writer.out
+}
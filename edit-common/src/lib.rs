@@ -9,14 +9,18 @@ extern crate serde_derive;
 extern crate wasm_typescript_definition;
 
 pub mod commands;
+pub mod highlight;
+pub mod html;
 pub mod markdown;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod simple_ws;
 
-use htmlescape::encode_minimal;
+use htmlescape::{
+    encode_attribute,
+    encode_minimal,
+};
 use oatie::doc::*;
 use oatie::rtf::*;
-use serde_json;
 use std::collections::HashMap;
 
 /// Formats a tag and a list of attributes into an HTML tag.
@@ -26,11 +30,7 @@ fn html_start_tag(tag: &str, attrs: HashMap<String, String>) -> String {
         tag,
         attrs
             .into_iter()
-            .map(|(k, v)| format!(
-                "{}={}",
-                k,
-                serde_json::to_string(&v).unwrap_or("".to_string())
-            ))
+            .map(|(k, v)| format!(r#"{}="{}""#, k, encode_attribute(&v)))
             .collect::<Vec<String>>()
             .join(" ")
     )
@@ -39,20 +39,58 @@ fn html_start_tag(tag: &str, attrs: HashMap<String, String>) -> String {
 // TODO this should take a Doc, not DocSpan (probably)
 /// Converts a DocSpan to an HTML string.
 pub fn doc_as_html(doc: &DocSpan<RtfSchema>) -> String {
+    doc_as_html_inner(doc, false)
+}
+
+/// Converts a DocSpan to an HTML string for display, with code blocks syntax
+/// highlighted. The editor can't use this HTML, since highlighting breaks up
+/// the text of code blocks into more spans than there are text elements.
+pub fn doc_as_highlighted_html(doc: &DocSpan<RtfSchema>) -> String {
+    doc_as_html_inner(doc, true)
+}
+
+fn doc_as_html_inner(doc: &DocSpan<RtfSchema>, highlight: bool) -> String {
     use oatie::doc::*;
 
     // let mut select_active = false;
     let mut out = String::new();
+    // Number of the last item in a run of numbered list items.
+    let mut list_number: Option<u64> = None;
     for elem in doc {
         match elem {
             &DocGroup(ref attrs, ref span) => {
+                list_number = match attrs {
+                    Attrs::OrderedListItem(start) => {
+                        Some(list_number.map(|n| n + 1).unwrap_or(*start))
+                    }
+                    _ => None,
+                };
+
+                // Images are a single element with no contents. Unsafe URLs
+                // are left out so the image shows its alt text.
+                if let Attrs::Image { src, alt } = attrs {
+                    let src = if html::is_safe_url(src) { src } else { "" };
+                    out.push_str(&format!(
+                        r#"<img data-tag="img" src="{}" alt="{}">"#,
+                        encode_attribute(src),
+                        encode_attribute(alt),
+                    ));
+                    continue;
+                }
+
                 out.push_str(&match attrs {
                     Attrs::Para => {
                         html_start_tag("div", hashmap!{ "data-tag".into() => "p".into() })
                     }
-                    Attrs::Code => {
+                    Attrs::Code(None) => {
                         html_start_tag("div", hashmap!{ "data-tag".into() => "pre".into() })
                     }
+                    Attrs::Code(Some(language)) => {
+                        html_start_tag("div", hashmap!{
+                            "data-tag".into() => "pre".to_string(),
+                            "data-language".into() => language.to_string(),
+                        })
+                    }
                     Attrs::Html => {
                         html_start_tag("div", hashmap!{ "data-tag".into() => "html".into() })
                     }
@@ -62,9 +100,33 @@ pub fn doc_as_html(doc: &DocSpan<RtfSchema>) -> String {
                     Attrs::ListItem => {
                         html_start_tag("div", hashmap!{ "data-tag".into() => "bullet".into() })
                     }
+                    Attrs::OrderedListItem(_) => {
+                        html_start_tag("div", hashmap!{
+                            "data-tag".into() => "ordered".to_string(),
+                            "data-number".into() => list_number.unwrap_or(1).to_string(),
+                        })
+                    }
+                    Attrs::TaskListItem { checked } => {
+                        html_start_tag("div", hashmap!{
+                            "data-tag".into() => "task".to_string(),
+                            "data-checked".into() => if *checked { "true".into() } else { "false".into() },
+                        })
+                    }
+                    Attrs::BlockQuote => {
+                        html_start_tag("div", hashmap!{ "data-tag".into() => "blockquote".into() })
+                    }
                     Attrs::Rule => {
                         html_start_tag("div", hashmap!{ "data-tag".into() => "hr".into() })
                     }
+                    Attrs::Table => {
+                        html_start_tag("div", hashmap!{ "data-tag".into() => "table".into() })
+                    }
+                    Attrs::TableRow => {
+                        html_start_tag("div", hashmap!{ "data-tag".into() => "tr".into() })
+                    }
+                    Attrs::TableCell => {
+                        html_start_tag("div", hashmap!{ "data-tag".into() => "td".into() })
+                    }
                     Attrs::Caret { ref client_id, ref focus } => {
                         html_start_tag("div", hashmap!{
                             "data-tag".into() => "caret".to_string(),
@@ -73,24 +135,57 @@ pub fn doc_as_html(doc: &DocSpan<RtfSchema>) -> String {
                             "data-anchor".into() => if !*focus { "true".into() } else { "false".into() },
                         })
                     },
+                    Attrs::Image { .. } => unreachable!(),
                 });
 
-                out.push_str(&doc_as_html(span));
+                let highlighted = match attrs {
+                    Attrs::Code(Some(language)) if highlight => {
+                        let code = span
+                            .iter()
+                            .filter_map(|child| match child {
+                                DocText(_, text) => Some(text.as_str()),
+                                _ => None,
+                            })
+                            .collect::<String>();
+                        highlight::highlight_html(language, &code)
+                    }
+                    _ => None,
+                };
+                if let Some(highlighted) = highlighted {
+                    out.push_str(&highlighted);
+                } else {
+                    out.push_str(&doc_as_html_inner(span, highlight));
+                }
                 out.push_str(r"</div>");
             }
             &DocText(ref styles, ref text) => {
-                let classes = styles.styles();
+                list_number = None;
+                let mut classes = styles
+                    .styles()
+                    .into_iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>();
+                classes.sort();
+                classes.dedup();
+                let classes = classes.join(" ");
 
-                out.push_str(&format!(
-                    r#"<span class="{}">"#,
-                    classes
-                        .into_iter()
-                        .map(|e| e.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" "),
-                ));
-                out.push_str(&encode_minimal(text.as_str()));
-                out.push_str(r"</span>");
+                // Linked text is rendered as an anchor in place of a span.
+                // Unsafe URLs are kept in the style but not linked to.
+                if let Some(url) = styles.link() {
+                    let href = if html::is_safe_url(url) { url } else { "" };
+                    out.push_str(&format!(
+                        r#"<a class="{}" href="{}" data-style-Link="{}">"#,
+                        classes,
+                        encode_attribute(href),
+                        encode_attribute(url),
+                    ));
+                    out.push_str(&encode_minimal(text.as_str()));
+                    out.push_str(r"</a>");
+                } else {
+                    out.push_str(&format!(r#"<span class="{}">"#, classes));
+                    out.push_str(&encode_minimal(text.as_str()));
+                    out.push_str(r"</span>");
+                }
             }
         }
     }
@@ -16,40 +16,191 @@ use pulldown_cmark::{
     },
     Parser,
     Tag,
+    OPTION_ENABLE_TABLES,
 };
+use std::iter::Peekable;
 
-struct Ctx<'b, I> {
-    iter: I,
+/// Whether a tag is for a block, as opposed to an inline span.
+fn is_block(tag: &Tag<'_>) -> bool {
+    match tag {
+        Tag::Link(..) | Tag::Strong | Tag::Emphasis | Tag::Code | Tag::Image(..) => false,
+        _ => true,
+    }
+}
+
+/// Splits the checkbox of a GFM task list item, like "[x] ", off the start
+/// of its text. Returns whether it's checked, and the rest of the text.
+fn split_task_marker(text: &str) -> Option<(bool, &str)> {
+    let checked = match text.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    match &text[3..] {
+        "" => Some((checked, "")),
+        rest if rest.starts_with(' ') => Some((checked, &rest[1..])),
+        _ => None,
+    }
+}
+
+/// Splits text at the "~~" delimiter runs that open or close GFM
+/// strikethrough, given the characters around the text (None for whitespace
+/// or the edge of a block) and whether strikethrough is already on. Returns
+/// each piece of text and whether the style toggles before it.
+///
+/// pulldown-cmark 0.1 doesn't parse strikethrough, so this applies the
+/// left- and right-flanking rules from the GFM spec by hand: a run opens if
+/// it's left-flanking and closes if it's right-flanking. Runs of any other
+/// length than two are left as text.
+fn split_strikethrough(
+    before: Option<char>,
+    text: &str,
+    after: Option<char>,
+    mut struck: bool,
+) -> Vec<(bool, &str)> {
+    let is_space = |c: Option<char>| c.map(char::is_whitespace).unwrap_or(true);
+    let is_punct = |c: Option<char>| c.map(|c| c.is_ascii_punctuation()).unwrap_or(false);
+
+    let mut pieces = vec![];
+    let mut toggle = false;
+    let mut start = 0;
+    let mut i = 0;
+    while let Some(offset) = text[i..].find('~') {
+        let run_start = i + offset;
+        let run_end = text[run_start..]
+            .find(|c| c != '~')
+            .map(|len| run_start + len)
+            .unwrap_or(text.len());
+        i = run_end;
+        if run_end - run_start != 2 {
+            continue;
+        }
+
+        let prev = text[..run_start].chars().next_back().or(before);
+        let next = text[run_end..].chars().next().or(after);
+        let flanking = if struck {
+            // Right-flanking.
+            !is_space(prev) && (!is_punct(prev) || is_space(next) || is_punct(next))
+        } else {
+            // Left-flanking.
+            !is_space(next) && (!is_punct(next) || is_space(prev) || is_punct(prev))
+        };
+        if flanking {
+            pieces.push((toggle, &text[start..run_start]));
+            toggle = true;
+            struck = !struck;
+            start = run_end;
+        }
+    }
+    pieces.push((toggle, &text[start..]));
+    pieces
+}
+
+struct Ctx<'a, 'b, I: Iterator<Item = Event<'a>>> {
+    iter: Peekable<I>,
     body: &'b mut DocWriter<RtfSchema>,
     styles: StyleSet,
     bare_text: bool,
+    /// Whether a paragraph has been opened around bare text.
+    bare_para: bool,
+    /// The text of the code block we're in.
+    code_block: Option<String>,
+    /// The source and alt text of the image we're in.
+    image: Option<(String, String)>,
+    /// The start number of each list we're in, or None for bulleted lists.
+    lists: Vec<Option<usize>>,
+    /// For each list item we're in, whether it's a checked or unchecked
+    /// task, or None if it isn't a task.
+    items: Vec<Option<bool>>,
+    /// Whether we're at the start of a bulleted list item, where a task's
+    /// checkbox can be.
+    item_start: bool,
+    /// Whether we're at the start of a table cell, before any text.
+    cell_start: bool,
+    /// The last character of the source before the current event, or None
+    /// for whitespace or the start of a block. Inline markup stands in as
+    /// '*', since all of its delimiters are punctuation.
+    last_char: Option<char>,
 }
 
-impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
+impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'a, 'b, I> {
     pub fn run(&mut self) {
         while let Some(event) = self.iter.next() {
+            // A task's checkbox comes before anything else in its item.
+            match event {
+                Start(Tag::Paragraph) | Text(..) => {}
+                _ => self.item_start = false,
+            }
+
             match event {
                 Start(tag) => {
+                    if is_block(&tag) {
+                        self.close_bare_para();
+                    }
+                    self.last_char = if is_block(&tag) { None } else { Some('*') };
                     self.start_tag(tag);
                 }
                 End(tag) => {
+                    if is_block(&tag) {
+                        self.close_bare_para();
+                    }
+                    self.last_char = if is_block(&tag) { None } else { Some('*') };
                     self.end_tag(tag);
                 }
+                Text(ref text) if self.code_block.is_some() => {
+                    self.code_block.as_mut().unwrap().push_str(text);
+                }
+                Text(ref text) if self.image.is_some() => {
+                    self.image.as_mut().unwrap().1.push_str(text);
+                }
                 Text(text) => {
-                    // TODO wrapping bare txt in a paragraph makes the result
-                    // validate, but 1) the wrapping element should be a div,
-                    // since it lacks any margin and 2) it should be contiguous
-                    // with all other elements that follow it, so text<b>with</b>bold
-                    // doesn't have three block elements generated, 1 for each span.
-                    if self.bare_text {
-                        self.body.begin();
+                    let mut text = text.as_ref();
+                    if self.item_start {
+                        self.item_start = false;
+                        if let Some((checked, rest)) = split_task_marker(text) {
+                            *self.items.last_mut().unwrap() = Some(checked);
+                            // Tasks without text still have a paragraph.
+                            self.open_bare_para();
+                            text = rest;
+                        }
+                    }
+
+                    // Table cells are padded with spaces, which aren't part
+                    // of their content.
+                    if self.cell_start {
+                        text = text.trim_start();
                     }
-                    self.body.place(&DocText(
-                        self.styles.clone(),
-                        DocString::from_str(text.as_ref()),
-                    ));
-                    if self.bare_text {
-                        self.body.close(Attrs::Para);
+                    if let Some(End(Tag::TableCell)) = self.iter.peek() {
+                        text = text.trim_end();
+                    }
+
+                    if self.styles.contains(&RtfStyle::Code) {
+                        if !text.is_empty() {
+                            self.place_text(text);
+                        }
+                    } else {
+                        let after = match self.iter.peek() {
+                            Some(Text(next)) => next.chars().next(),
+                            Some(Start(tag)) | Some(End(tag)) if !is_block(tag) => Some('*'),
+                            _ => None,
+                        };
+                        let struck = self.styles.contains(&RtfStyle::Strikethrough);
+                        let pieces = split_strikethrough(self.last_char, text, after, struck);
+                        for (toggle, part) in pieces {
+                            if toggle {
+                                if self.styles.contains(&RtfStyle::Strikethrough) {
+                                    self.styles.remove(&RtfStyle::Strikethrough);
+                                } else {
+                                    self.styles.insert(RtfStyle::Strikethrough);
+                                }
+                            }
+                            if !part.is_empty() {
+                                self.place_text(part);
+                            }
+                        }
+                        if let Some(c) = text.chars().next_back() {
+                            self.last_char = Some(c);
+                        }
                     }
                 }
                 SoftBreak => {
@@ -57,29 +208,58 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
                     // if we should soft-space like HTML does. whitespace is
                     // significant in the document model so we can't always
                     // just add a space
-                    if self.bare_text {
-                        self.body.begin();
-                    }
-                    self.body
-                        .place(&DocText(self.styles.clone(), DocString::from_str(" ")));
-                    if self.bare_text {
-                        self.body.close(Attrs::Para);
-                    }
+                    self.place_text(" ");
                 }
                 HardBreak => {
-                    self.body
-                        .place(&DocText(self.styles.clone(), DocString::from_str("\n")));
+                    self.place_text("\n");
                 }
                 Html(html) => {
+                    self.close_bare_para();
+                    // The source of an HTML block ends in a newline, which
+                    // isn't part of the block.
+                    let html = html.trim_end_matches('\n');
                     self.body.begin();
-                    self.body
-                        .place(&DocText(StyleSet::new(), DocString::from_str(&html)));
+                    if !html.is_empty() {
+                        self.body
+                            .place(&DocText(StyleSet::new(), DocString::from_str(html)));
+                    }
                     self.body.close(Attrs::Html);
                 }
 
                 InlineHtml(..) | FootnoteReference(..) => {}
             }
         }
+        self.close_bare_para();
+    }
+
+    fn place_text(&mut self, text: &str) {
+        if let Some((_, ref mut alt)) = self.image {
+            alt.push_str(text);
+            return;
+        }
+        self.open_bare_para();
+        self.cell_start = false;
+        self.last_char = text.chars().next_back();
+        self.body
+            .place(&DocText(self.styles.clone(), DocString::from_str(text)));
+    }
+
+    fn open_bare_para(&mut self) {
+        // Bare text, as in the items of a tight list, is wrapped in a
+        // paragraph that lasts until the next block starts or ends.
+        if self.bare_text && !self.bare_para {
+            self.body.begin();
+            self.bare_para = true;
+        }
+    }
+
+    fn close_bare_para(&mut self) {
+        if self.bare_para {
+            self.body.close(Attrs::Para);
+            self.bare_para = false;
+            // An unclosed "~~" doesn't run into the next block.
+            self.styles.remove(&RtfStyle::Strikethrough);
+        }
     }
 
     fn start_tag(&mut self, tag: Tag<'a>) {
@@ -96,12 +276,24 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
             Tag::CodeBlock(_info) => {
                 self.body.begin();
                 self.bare_text = false;
+                self.code_block = Some(String::new());
             }
 
             // List items
+            Tag::List(start) => {
+                self.lists.push(start);
+            }
             Tag::Item => {
                 self.body.begin();
                 self.bare_text = true;
+                self.items.push(None);
+                self.item_start = self.lists.last() == Some(&None);
+            }
+
+            // Quotes
+            Tag::BlockQuote => {
+                self.body.begin();
+                self.bare_text = true;
             }
 
             // Block objects
@@ -111,8 +303,7 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
 
             // Spans
             Tag::Link(dest, _title) => {
-                // TODO link styles
-                // self.styles.insert(Style::Link, Some(dest.to_string()));
+                self.styles.insert(RtfStyle::Link(dest.to_string()));
             }
             Tag::Strong => {
                 self.styles.insert(RtfStyle::Bold);
@@ -120,16 +311,30 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
             Tag::Emphasis => {
                 self.styles.insert(RtfStyle::Italic);
             }
+            Tag::Code => {
+                self.styles.insert(RtfStyle::Code);
+            }
+
+            // Inline objects
+            Tag::Image(src, _title) => {
+                // The text inside the image tag is its alt text.
+                self.image = Some((src.to_string(), String::new()));
+            }
+
+            // Tables
+            Tag::Table(..) | Tag::TableHead | Tag::TableRow => {
+                self.body.begin();
+            }
+            Tag::TableCell => {
+                // Cells hold inline content, which goes in one paragraph.
+                // Open it now so empty cells still have one.
+                self.body.begin();
+                self.bare_text = true;
+                self.open_bare_para();
+                self.cell_start = true;
+            }
 
-            Tag::Table(..)
-            | Tag::TableHead
-            | Tag::TableRow
-            | Tag::TableCell
-            | Tag::BlockQuote
-            | Tag::Code
-            | Tag::List(_)
-            | Tag::Image(..)
-            | Tag::FootnoteDefinition(_) => {}
+            Tag::FootnoteDefinition(_) => {}
         }
     }
 
@@ -139,19 +344,48 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
             Tag::Paragraph => {
                 self.body.close(Attrs::Para);
                 self.bare_text = true;
+                // An unclosed "~~" doesn't run into the next block.
+                self.styles.remove(&RtfStyle::Strikethrough);
             }
             Tag::Header(level) => {
                 self.body.close(Attrs::Header(level as u8));
                 self.bare_text = true;
+                self.styles.remove(&RtfStyle::Strikethrough);
             }
-            Tag::CodeBlock(_) => {
-                self.body.close(Attrs::Code);
+            Tag::CodeBlock(info) => {
+                // Code blocks always end in a newline, which isn't part of
+                // the code.
+                let mut code = self.code_block.take().unwrap_or_default();
+                if code.ends_with('\n') {
+                    code.pop();
+                }
+                if !code.is_empty() {
+                    self.body
+                        .place(&DocText(StyleSet::new(), DocString::from_str(&code)));
+                }
+                // The language is the first word of the info string.
+                let language = info.split_whitespace().next().map(|x| x.to_string());
+                self.body.close(Attrs::Code(language));
                 self.bare_text = true;
             }
 
             // List items
+            Tag::List(_) => {
+                self.lists.pop();
+            }
             Tag::Item => {
-                self.body.close(Attrs::ListItem);
+                let task = self.items.pop().and_then(|task| task);
+                self.body.close(match (task, self.lists.last()) {
+                    (Some(checked), _) => Attrs::TaskListItem { checked },
+                    (None, Some(Some(start))) => Attrs::OrderedListItem(*start as u64),
+                    (None, _) => Attrs::ListItem,
+                });
+                self.bare_text = true;
+            }
+
+            // Quotes
+            Tag::BlockQuote => {
+                self.body.close(Attrs::BlockQuote);
                 self.bare_text = true;
             }
 
@@ -159,12 +393,10 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
             Tag::Rule => {
                 self.body.close(Attrs::Rule);
             }
-            Tag::Image(_, _) => (), // shouldn't happen, handled in start
 
             // Spans
-            Tag::Link(..) => {
-                // TODO add link styles
-                // self.styles.remove(&RtfStyle::Link);
+            Tag::Link(dest, _title) => {
+                self.styles.remove(&RtfStyle::Link(dest.to_string()));
             }
             Tag::Strong => {
                 self.styles.remove(&RtfStyle::Bold);
@@ -172,28 +404,54 @@ impl<'a, 'b, I: Iterator<Item = Event<'a>>> Ctx<'b, I> {
             Tag::Emphasis => {
                 self.styles.remove(&RtfStyle::Italic);
             }
+            Tag::Code => {
+                self.styles.remove(&RtfStyle::Code);
+            }
+
+            // Inline objects
+            Tag::Image(..) => {
+                if let Some((src, alt)) = self.image.take() {
+                    self.open_bare_para();
+                    self.cell_start = false;
+                    self.body.begin();
+                    self.body.close(Attrs::Image { src, alt });
+                }
+            }
+
+            // Tables
+            Tag::Table(_) => {
+                self.body.close(Attrs::Table);
+                self.bare_text = true;
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.body.close(Attrs::TableRow);
+            }
+            Tag::TableCell => {
+                self.body.close(Attrs::TableCell);
+            }
 
-            Tag::FootnoteDefinition(_)
-            | Tag::Code
-            | Tag::TableCell
-            | Tag::Table(_)
-            | Tag::TableHead
-            | Tag::TableRow
-            | Tag::List(_)
-            | Tag::BlockQuote => {}
+            Tag::FootnoteDefinition(_) => {}
         }
     }
 }
 
 pub fn markdown_to_doc(input: &str) -> Result<DocSpan<RtfSchema>, Error> {
-    let parser = Parser::new(input);
+    let parser = Parser::new_ext(input, OPTION_ENABLE_TABLES);
     let mut doc_writer = DocWriter::new();
     {
         let mut ctx = Ctx {
-            iter: parser,
+            iter: parser.peekable(),
             body: &mut doc_writer,
             styles: StyleSet::new(),
             bare_text: true,
+            bare_para: false,
+            code_block: None,
+            image: None,
+            lists: vec![],
+            items: vec![],
+            item_start: false,
+            last_char: None,
+            cell_start: false,
         };
         ctx.run();
     }
@@ -2,4 +2,7 @@ pub mod de;
 pub mod ser;
 
 pub use self::de::markdown_to_doc;
-pub use self::ser::doc_to_markdown;
+pub use self::ser::{
+    doc_to_highlighted_markdown,
+    doc_to_markdown,
+};
@@ -1,25 +1,187 @@
+use crate::highlight::highlight_html;
 use failure::Error;
+use htmlescape::encode_attribute;
 use oatie::doc::*;
 use oatie::rtf::*;
 use oatie::stepper::DocStepper;
 use pulldown_cmark::{
+    Alignment,
     Event,
     Tag,
 };
 use pulldown_cmark_to_cmark::fmt::cmark;
 
+/// An inline span wrapping styled text. Spans are nested in the order they
+/// are listed here, so that inline code (which can't contain markup) is
+/// always innermost.
+#[derive(Clone, Debug, PartialEq)]
+enum Span {
+    Link(String),
+    Strong,
+    Emphasis,
+    Strikethrough,
+    Code,
+}
+
+impl Span {
+    /// The spans text with these styles is wrapped in, outermost first.
+    fn from_styles(styles: &StyleSet) -> Vec<Span> {
+        let mut spans = vec![];
+        if let Some(url) = styles.link() {
+            spans.push(Span::Link(url.to_string()));
+        }
+        if styles.contains(&RtfStyle::Bold) {
+            spans.push(Span::Strong);
+        }
+        if styles.contains(&RtfStyle::Italic) {
+            spans.push(Span::Emphasis);
+        }
+        if styles.contains(&RtfStyle::Strikethrough) {
+            spans.push(Span::Strikethrough);
+        }
+        if styles.contains(&RtfStyle::Code) {
+            spans.push(Span::Code);
+        }
+        spans
+    }
+
+    fn start<'b>(&self) -> Event<'b> {
+        match self {
+            Span::Link(url) => Event::Start(Tag::Link(url.clone().into(), "".into())),
+            Span::Strong => Event::Start(Tag::Strong),
+            Span::Emphasis => Event::Start(Tag::Emphasis),
+            // GFM strikethrough has no pulldown-cmark tag, so write the
+            // delimiters as text.
+            Span::Strikethrough => Event::Text("~~".into()),
+            Span::Code => Event::Start(Tag::Code),
+        }
+    }
+
+    fn end<'b>(&self) -> Event<'b> {
+        match self {
+            Span::Link(url) => Event::End(Tag::Link(url.clone().into(), "".into())),
+            Span::Strong => Event::End(Tag::Strong),
+            Span::Emphasis => Event::End(Tag::Emphasis),
+            Span::Strikethrough => Event::Text("~~".into()),
+            Span::Code => Event::End(Tag::Code),
+        }
+    }
+}
+
+/// Escapes characters Markdown would otherwise parse as markup. Some
+/// characters are only special at the start of a line.
+fn escape_text(text: &str, line_start: bool) -> String {
+    let mut out = String::new();
+    let mut leading_digits = line_start;
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            // "|" separates table cells.
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '&' | '~' | '|' => out.push('\\'),
+            '#' | '>' | '-' | '+' | '=' if line_start && i == 0 => out.push('\\'),
+            // "1." would start a numbered list.
+            '.' | ')' if leading_digits && i > 0 => out.push('\\'),
+            _ => {}
+        }
+        leading_digits = leading_digits && c.is_ascii_digit();
+        out.push(c);
+    }
+    out
+}
+
 struct DocToMarkdown<'a, 'b> {
     doc_stepper: DocStepper<'a, RtfSchema>,
     queue: Vec<Event<'b>>,
+    /// Inline spans currently open, outermost first.
+    spans: Vec<Span>,
+    in_code_block: bool,
+    /// Whether we're in a table cell, whose paragraph isn't written.
+    in_table_cell: bool,
+    /// The number of rows of the current table written so far.
+    table_rows: usize,
+    /// Whether the task list item just started is checked, until its
+    /// checkbox is written.
+    task_marker: Option<bool>,
+    /// Whether the next text starts a line.
+    line_start: bool,
+    /// Whether to write code blocks as syntax highlighted HTML.
+    highlight: bool,
 }
 
 impl<'a, 'b> DocToMarkdown<'a, 'b> {
-    fn new(doc: &'a DocSpan<RtfSchema>) -> Self {
+    fn new(doc: &'a DocSpan<RtfSchema>, highlight: bool) -> Self {
         DocToMarkdown {
             doc_stepper: DocStepper::new(doc),
             queue: vec![],
+            spans: vec![],
+            in_code_block: false,
+            in_table_cell: false,
+            table_rows: 0,
+            task_marker: None,
+            line_start: true,
+            highlight,
+        }
+    }
+
+    /// Closes open spans until only the first `keep` remain.
+    fn close_spans(&mut self, keep: usize) {
+        while self.spans.len() > keep {
+            let span = self.spans.pop().unwrap();
+            self.queue.push(span.end());
+        }
+    }
+
+    /// Closes and opens spans so exactly `spans` are open, leaving open any
+    /// spans that are already open in the same order.
+    fn set_spans(&mut self, spans: Vec<Span>) {
+        let keep = self
+            .spans
+            .iter()
+            .zip(&spans)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.close_spans(keep);
+        for span in spans.into_iter().skip(keep) {
+            self.queue.push(span.start());
+            self.spans.push(span);
+        }
+    }
+
+    /// The attributes of the group before the head, skipping carets.
+    fn prev_group_attrs(&self) -> Option<Attrs> {
+        let mut stepper = self.doc_stepper.clone();
+        loop {
+            match stepper.unhead() {
+                Some(DocGroup(Attrs::Caret { .. }, _)) => stepper.prev(),
+                Some(DocGroup(attrs, _)) => return Some(attrs.clone()),
+                _ => return None,
+            }
         }
     }
+
+    /// The attributes of the group at the head, skipping carets.
+    fn next_group_attrs(&self) -> Option<Attrs> {
+        let mut stepper = self.doc_stepper.clone();
+        loop {
+            match stepper.head() {
+                Some(DocGroup(Attrs::Caret { .. }, _)) => stepper.next(),
+                Some(DocGroup(attrs, _)) => return Some(attrs.clone()),
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Whether two list items belong in the same list, i.e. are both bulleted or
+/// both numbered. Task list items are bulleted.
+fn same_list(a: &Attrs, b: &Attrs) -> bool {
+    match (a, b) {
+        (Attrs::ListItem, Attrs::ListItem)
+        | (Attrs::ListItem, Attrs::TaskListItem { .. })
+        | (Attrs::TaskListItem { .. }, Attrs::ListItem)
+        | (Attrs::TaskListItem { .. }, Attrs::TaskListItem { .. }) => true,
+        (Attrs::OrderedListItem(..), Attrs::OrderedListItem(..)) => true,
+        _ => false,
+    }
 }
 
 impl<'a, 'b> Iterator for DocToMarkdown<'a, 'b> {
@@ -32,10 +194,52 @@ impl<'a, 'b> Iterator for DocToMarkdown<'a, 'b> {
 
         match self.doc_stepper.head() {
             Some(DocGroup(ref attrs, ref body)) => {
+                self.line_start = true;
+                // A task's checkbox is written at the start of its first
+                // paragraph.
+                let task_marker = match attrs {
+                    Attrs::Caret { .. } => None,
+                    _ => self.task_marker.take(),
+                };
                 let res = Some(match attrs {
-                    Attrs::Para => Event::Start(Tag::Paragraph),
+                    Attrs::Para if self.in_table_cell => {
+                        // GFM table cells hold inline content only.
+                        self.doc_stepper.enter();
+                        return self.next();
+                    }
+                    Attrs::Para => {
+                        if let Some(checked) = task_marker {
+                            let marker = if checked { "[x] " } else { "[ ] " };
+                            self.queue.push(Event::Text(marker.into()));
+                            self.line_start = false;
+                        }
+                        Event::Start(Tag::Paragraph)
+                    }
                     Attrs::Header(level) => Event::Start(Tag::Header(*level as i32)),
-                    Attrs::Code => Event::Start(Tag::CodeBlock("".into())),
+                    Attrs::Code(language) => {
+                        let language = language.clone().unwrap_or_default();
+                        if self.highlight {
+                            let code = body
+                                .iter()
+                                .filter_map(|child| match child {
+                                    DocText(_, text) => Some(text.as_str()),
+                                    _ => None,
+                                })
+                                .collect::<String>();
+                            if let Some(html) = highlight_html(&language, &code) {
+                                self.doc_stepper.next();
+                                let pre = format!(
+                                    r#"<pre class="highlight" data-language="{}">"#,
+                                    encode_attribute(&language)
+                                );
+                                return Some(Event::Html(
+                                    format!("{}<code>{}</code></pre>\n", pre, html).into(),
+                                ));
+                            }
+                        }
+                        self.in_code_block = true;
+                        Event::Start(Tag::CodeBlock(language.into()))
+                    }
                     Attrs::Html => {
                         let mut out = String::new();
                         for child in body {
@@ -46,50 +250,110 @@ impl<'a, 'b> Iterator for DocToMarkdown<'a, 'b> {
                                 _ => {}
                             }
                         }
+                        // End the block's source with a newline, as the
+                        // parser does.
+                        out.push('\n');
                         self.doc_stepper.next();
                         return Some(Event::Html(out.into()));
                     }
-                    Attrs::ListItem => {
-                        if let Some(DocGroup(ref pre_attrs, _)) = self.doc_stepper.unhead() {
-                            if *pre_attrs != Attrs::ListItem {
-                                self.doc_stepper.enter();
-                                return Some(Event::Start(Tag::Item));
-                            }
+                    Attrs::ListItem | Attrs::OrderedListItem(..) | Attrs::TaskListItem { .. } => {
+                        if let Attrs::TaskListItem { checked } = attrs {
+                            self.task_marker = Some(*checked);
+                        }
+                        // Continue the list started by a preceding item.
+                        let continued = self
+                            .prev_group_attrs()
+                            .map(|prev_attrs| same_list(&prev_attrs, attrs))
+                            .unwrap_or(false);
+                        if continued {
+                            self.doc_stepper.enter();
+                            return Some(Event::Start(Tag::Item));
                         }
                         self.queue.push(Event::Start(Tag::Item));
-                        Event::Start(Tag::List(None))
+                        Event::Start(Tag::List(match attrs {
+                            Attrs::OrderedListItem(start) => Some(*start as usize),
+                            _ => None,
+                        }))
+                    }
+                    Attrs::BlockQuote => Event::Start(Tag::BlockQuote),
+                    Attrs::Table => {
+                        // Every row has as many cells as the header row.
+                        let columns = match body.first() {
+                            Some(DocGroup(_, row)) => row.len(),
+                            _ => 0,
+                        };
+                        self.table_rows = 0;
+                        Event::Start(Tag::Table(vec![Alignment::None; columns]))
+                    }
+                    Attrs::TableRow if self.table_rows == 0 => Event::Start(Tag::TableHead),
+                    Attrs::TableRow => Event::Start(Tag::TableRow),
+                    Attrs::TableCell => {
+                        self.in_table_cell = true;
+                        Event::Start(Tag::TableCell)
                     }
                     Attrs::Caret { .. } => {
+                        // Carets aren't part of the document's content.
                         self.doc_stepper.next();
                         return self.next();
                     }
                     Attrs::Rule => Event::Start(Tag::Rule),
-                    // _ => {
-                    //     eprintln!("Unexpected tag {:?}!", attrs);
-                    //     self.doc_stepper.next();
-                    //     return self.next();
-                    // }
+                    Attrs::Image { src, alt } => {
+                        // Images are written whole, with their alt text, and
+                        // aren't entered. Close any spans first, since an
+                        // image can't be inside inline code.
+                        let (src, alt) = (src.clone(), alt.clone());
+                        self.doc_stepper.next();
+                        self.close_spans(0);
+                        self.queue
+                            .push(Event::Start(Tag::Image(src.clone().into(), "".into())));
+                        self.queue
+                            .push(Event::Text(escape_text(&alt, false).into()));
+                        self.queue
+                            .push(Event::End(Tag::Image(src.into(), "".into())));
+                        self.line_start = false;
+                        return self.next();
+                    }
                 });
                 self.doc_stepper.enter();
                 res
             }
             Some(DocText(ref styles, ref text)) => {
-                // Styling.
-                let text_event = Event::Text(text.to_string().replace("\n", "  \n").into());
-                let res = if styles.contains(&RtfStyle::Bold) {
-                    self.queue.push(text_event);
-                    self.queue.push(Event::End(Tag::Strong));
-                    Some(Event::Start(Tag::Strong))
-                } else {
-                    Some(text_event)
-                };
-
+                let styles = styles.clone();
+                let text = text.to_string();
                 self.doc_stepper.next();
-                res
+
+                // Code blocks are written verbatim.
+                if self.in_code_block {
+                    return Some(Event::Text(text.into()));
+                }
+
+                let spans = Span::from_styles(&styles);
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        self.close_spans(0);
+                        self.queue.push(Event::HardBreak);
+                        self.line_start = true;
+                    }
+                    if !line.is_empty() {
+                        self.set_spans(spans.clone());
+                        let line = if spans.contains(&Span::Code) {
+                            line.to_string()
+                        } else {
+                            escape_text(line, self.line_start)
+                        };
+                        self.queue.push(Event::Text(line.into()));
+                        self.line_start = false;
+                    }
+                }
+                self.next()
             }
             None => {
                 if self.doc_stepper.is_done() {
                     None
+                } else if !self.spans.is_empty() {
+                    // Close inline spans before their block ends.
+                    self.close_spans(0);
+                    self.next()
                 } else {
                     let mut stepper_clone = self.doc_stepper.clone();
                     stepper_clone.unenter();
@@ -99,22 +363,46 @@ impl<'a, 'b> Iterator for DocToMarkdown<'a, 'b> {
                     };
                     self.doc_stepper.exit();
                     Some(match attrs {
+                        Attrs::Para if self.in_table_cell => {
+                            return self.next();
+                        }
                         Attrs::Para => Event::End(Tag::Paragraph),
                         Attrs::Header(level) => Event::End(Tag::Header(level as i32)),
-                        Attrs::Code => {
+                        Attrs::Code(..) => {
+                            self.in_code_block = false;
                             self.queue.push(Event::End(Tag::CodeBlock("".into())));
                             Event::Text("\n".to_string().into())
                         }
-                        Attrs::ListItem => {
-                            if let Some(DocGroup(ref post_attrs, _)) = self.doc_stepper.head() {
-                                if *post_attrs != Attrs::ListItem {
-                                    self.queue.push(Event::End(Tag::List(None)));
-                                }
+                        Attrs::ListItem
+                        | Attrs::OrderedListItem(..)
+                        | Attrs::TaskListItem { .. } => {
+                            // End the list unless another item follows.
+                            match self.next_group_attrs() {
+                                Some(ref next_attrs) if same_list(next_attrs, &attrs) => {}
+                                _ => self.queue.push(Event::End(Tag::List(None))),
                             }
                             Event::End(Tag::Item)
                         }
+                        Attrs::BlockQuote => Event::End(Tag::BlockQuote),
+                        Attrs::Table => Event::End(Tag::Table(vec![])),
+                        Attrs::TableRow => {
+                            self.table_rows += 1;
+                            if self.table_rows == 1 {
+                                Event::End(Tag::TableHead)
+                            } else {
+                                Event::End(Tag::TableRow)
+                            }
+                        }
+                        Attrs::TableCell => {
+                            self.in_table_cell = false;
+                            Event::End(Tag::TableCell)
+                        }
                         Attrs::Rule => Event::End(Tag::Rule),
-                        _ => unimplemented!(),
+                        // HTML blocks, carets, and images are stepped over
+                        // without being entered, so never end here.
+                        Attrs::Html | Attrs::Caret { .. } | Attrs::Image { .. } => {
+                            return self.next();
+                        }
                     })
                 }
             }
@@ -123,7 +411,16 @@ impl<'a, 'b> Iterator for DocToMarkdown<'a, 'b> {
 }
 
 pub fn doc_to_markdown(doc: &DocSpan<RtfSchema>) -> Result<String, Error> {
-    let to_mark = DocToMarkdown::new(&doc);
+    let to_mark = DocToMarkdown::new(&doc, false);
+    let mut buf = String::new();
+    cmark(to_mark, &mut buf, None)?;
+    Ok(buf)
+}
+
+/// Like `doc_to_markdown`, but writes code blocks in a known language as
+/// syntax highlighted HTML, for rendering the Markdown for display.
+pub fn doc_to_highlighted_markdown(doc: &DocSpan<RtfSchema>) -> Result<String, Error> {
+    let to_mark = DocToMarkdown::new(&doc, true);
     let mut buf = String::new();
     cmark(to_mark, &mut buf, None)?;
     Ok(buf)
@@ -0,0 +1,69 @@
+use edit_common::highlight::highlight_html;
+
+#[test]
+fn highlight_strings_and_comments() {
+    // Keywords inside strings and comments aren't highlighted on their own.
+    assert_eq!(
+        highlight_html("rust", r#"let s = "fn let"; // if else"#).unwrap(),
+        concat!(
+            r#"<span class="hl-keyword">let</span> s = "#,
+            r#"<span class="hl-string">&quot;fn let&quot;</span>; "#,
+            r#"<span class="hl-comment">// if else</span>"#,
+        )
+    );
+    assert_eq!(
+        highlight_html("rust", "/* \"return\" */ return 1\nx").unwrap(),
+        concat!(
+            r#"<span class="hl-comment">/* &quot;return&quot; */</span> "#,
+            r#"<span class="hl-keyword">return</span> "#,
+            "<span class=\"hl-number\">1</span>\nx",
+        )
+    );
+    assert_eq!(
+        highlight_html("python", r##"x = "# def" # def"##).unwrap(),
+        concat!(
+            r##"x = <span class="hl-string">&quot;# def&quot;</span> "##,
+            r#"<span class="hl-comment"># def</span>"#,
+        )
+    );
+}
+
+#[test]
+fn highlight_escapes_and_unterminated_strings() {
+    assert_eq!(
+        highlight_html("rust", r#""say \"if\"" if"#).unwrap(),
+        concat!(
+            r#"<span class="hl-string">&quot;say \&quot;if\&quot;&quot;</span> "#,
+            r#"<span class="hl-keyword">if</span>"#,
+        )
+    );
+    assert_eq!(
+        highlight_html("rust", r#""if"#).unwrap(),
+        r#"<span class="hl-string">&quot;if</span>"#
+    );
+}
+
+#[test]
+fn highlight_keywords_in_identifiers() {
+    assert_eq!(
+        highlight_html("rust", "letter = iffy_if;").unwrap(),
+        "letter = iffy_if;"
+    );
+}
+
+#[test]
+fn highlight_supported_languages() {
+    // The languages listed in docs/src/markdown.md.
+    let languages = concat!(
+        "rust rs javascript js typescript ts jsx tsx python py go golang c h cpp c++ cc hpp ",
+        "java kotlin swift csharp cs sh bash shell zsh json toml ini Rust"
+    );
+    for language in languages.split(' ') {
+        assert!(highlight_html(language, "1").is_some(), "{}", language);
+    }
+}
+
+#[test]
+fn highlight_unknown_language() {
+    assert_eq!(highlight_html("cobol", "IF X"), None);
+}
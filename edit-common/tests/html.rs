@@ -0,0 +1,192 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_common::html::*;
+use htmlescape::encode_attribute;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::validate::validate_doc;
+
+/// Converts HTML to a document, checking that the document is valid.
+fn convert(input: &str) -> Doc<RtfSchema> {
+    let doc = Doc(html_to_doc(input).unwrap());
+    validate_doc(&doc).unwrap();
+    doc
+}
+
+#[test]
+fn html_blocks_and_styles() {
+    assert_eq!(
+        convert(
+            "<h2>Notes</h2>\n<p>Some <b>bold</b>,\n  <em>italic</em> and \
+             <a href=\"http://example.com/\">linked</a> text.</p><hr>",
+        ),
+        doc![
+            DocGroup(Attrs::Header(2), [DocText("Notes")]),
+            DocGroup(Attrs::Para, [
+                DocText("Some "),
+                DocText({RtfStyle::Bold}, "bold"),
+                DocText(", "),
+                DocText({RtfStyle::Italic}, "italic"),
+                DocText(" and "),
+                DocText({RtfStyle::Link("http://example.com/".to_string())}, "linked"),
+                DocText(" text."),
+            ]),
+            DocGroup(Attrs::Rule, []),
+        ]
+    );
+}
+
+#[test]
+fn html_lists() {
+    assert_eq!(
+        convert("<ul><li>one<li><p>two</p><ol start=\"3\"><li>three</ol></ul>"),
+        doc![
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("one")]),
+            ]),
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("two")]),
+                DocGroup(Attrs::OrderedListItem(3), [
+                    DocGroup(Attrs::Para, [DocText("three")]),
+                ]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn html_code_block() {
+    assert_eq!(
+        convert("<pre><code class=\"language-rust\">\nfn main() {\n    <b>x</b> &lt; 1\n}</code></pre>"),
+        doc![
+            DocGroup(Attrs::Code(Some("rust".to_string())), [
+                DocText("fn main() {\n    x < 1\n}"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn html_unknown_markup() {
+    // Unknown tags are dropped, but their text is kept. Scripts, styles,
+    // and comments aren't text.
+    assert_eq!(
+        convert(
+            "<html><head><style>p { color: red; }</style></head><body>\
+             <!--StartFragment--><div><span class=\"x\">kept</span> &amp; \
+             <font>also kept</font></div><div>next<script>alert(1)</script></div>\
+             </body></html>",
+        ),
+        doc![
+            DocGroup(Attrs::Para, [DocText("kept & also kept")]),
+            DocGroup(Attrs::Para, [DocText("next")]),
+        ]
+    );
+}
+
+#[test]
+fn html_export() {
+    let doc = doc![
+        DocGroup(Attrs::Header(1), [DocText("Title")]),
+        DocGroup(Attrs::Para, [
+            DocText("a "),
+            DocText({RtfStyle::Bold}, "b"),
+            DocGroup(Attrs::Caret {
+                client_id: "left".to_string(),
+                focus: true,
+            }, []),
+            DocText({RtfStyle::Bold, RtfStyle::Italic}, "c"),
+            DocText(" <\"d\">"),
+        ]),
+        DocGroup(Attrs::OrderedListItem(2), [
+            DocGroup(Attrs::Para, [DocText("two")]),
+        ]),
+        DocGroup(Attrs::OrderedListItem(2), [
+            DocGroup(Attrs::Para, [
+                DocText({RtfStyle::Link("three".to_string())}, "three"),
+            ]),
+        ]),
+        DocGroup(Attrs::Code(Some("rust".to_string())), [DocText("x < y")]),
+        DocGroup(Attrs::Rule, []),
+    ];
+
+    assert_eq!(
+        doc_to_html(&doc.0),
+        "<h1>Title</h1>\n\
+         <p>a <strong>b<em>c</em></strong> &lt;&quot;d&quot;&gt;</p>\n\
+         <ol start=\"2\">\n\
+         <li><p>two</p>\n</li>\n\
+         <li><p><a href=\"three\">three</a></p>\n</li>\n\
+         </ol>\n\
+         <pre><code class=\"language-rust\">x &lt; y</code></pre>\n\
+         <hr>\n"
+    );
+}
+
+#[test]
+fn html_export_html_block() {
+    let doc = doc![
+        DocGroup(Attrs::Html, [DocText("<script>alert(1)</script>")]),
+    ];
+
+    assert_eq!(
+        doc_to_html(&doc.0),
+        "<pre>&lt;script&gt;alert(1)&lt;/script&gt;</pre>\n"
+    );
+}
+
+#[test]
+fn html_export_unsafe_urls() {
+    let link = |url: &str| {
+        doc![DocGroup(Attrs::Para, [
+            DocText({RtfStyle::Link(url.to_string())}, "x"),
+        ])]
+    };
+
+    for url in &[
+        "http://example.com/",
+        "HTTPS://example.com/",
+        "mailto:a@example.com",
+        "/page",
+        "page?a=b:c",
+        "#top",
+    ] {
+        assert_eq!(
+            doc_to_html(&link(url).0),
+            format!("<p><a href=\"{}\">x</a></p>\n", encode_attribute(url)),
+        );
+    }
+
+    for url in &[
+        "javascript:alert(1)",
+        "JavaScript:alert(1)",
+        " java\tscript:alert(1)",
+        "data:text/html,<script>alert(1)</script>",
+        "vbscript:msgbox(1)",
+    ] {
+        assert!(!is_safe_url(url));
+        assert_eq!(doc_to_html(&link(url).0), "<p>x</p>\n");
+    }
+
+    let doc = doc![
+        DocGroup(Attrs::Para, [
+            DocGroup(Attrs::Image {
+                src: "javascript:alert(1)".to_string(),
+                alt: "<cat>".to_string(),
+            }, []),
+            DocGroup(Attrs::Image {
+                src: "cat.png".to_string(),
+                alt: "cat".to_string(),
+            }, []),
+        ]),
+    ];
+
+    assert_eq!(
+        doc_to_html(&doc.0),
+        format!(
+            "<p>&lt;cat&gt;<img src=\"{}\" alt=\"cat\"></p>\n",
+            encode_attribute("cat.png"),
+        ),
+    );
+}
@@ -0,0 +1,325 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_common::markdown::*;
+use oatie::doc::*;
+use oatie::random::random_doc;
+use oatie::rtf::*;
+use rand::{
+    SeedableRng,
+    XorShiftRng,
+};
+
+/// Converts Markdown to a document and back again, checking that the
+/// document survives the trip.
+fn round_trip(input: &str) -> Doc<RtfSchema> {
+    let doc = Doc(markdown_to_doc(input).unwrap());
+    let output = doc_to_markdown(&doc.0).unwrap();
+    assert_eq!(
+        Doc(markdown_to_doc(&output).unwrap()),
+        doc,
+        "document changed after serializing to {:?}",
+        output
+    );
+    doc
+}
+
+#[test]
+fn markdown_blockquote() {
+    assert_eq!(
+        round_trip("> quoted\n>\n> - item\n\nafter\n"),
+        doc![
+            DocGroup(Attrs::BlockQuote, [
+                DocGroup(Attrs::Para, [DocText("quoted")]),
+                DocGroup(Attrs::ListItem, [
+                    DocGroup(Attrs::Para, [DocText("item")]),
+                ]),
+            ]),
+            DocGroup(Attrs::Para, [DocText("after")]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_nested_blockquote() {
+    assert_eq!(
+        round_trip("> outer\n>\n> > inner\n"),
+        doc![
+            DocGroup(Attrs::BlockQuote, [
+                DocGroup(Attrs::Para, [DocText("outer")]),
+                DocGroup(Attrs::BlockQuote, [
+                    DocGroup(Attrs::Para, [DocText("inner")]),
+                ]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_nested_list() {
+    assert_eq!(
+        round_trip("- one\n  - two\n    - three\n  - four\n- five\n"),
+        doc![
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("one")]),
+                DocGroup(Attrs::ListItem, [
+                    DocGroup(Attrs::Para, [DocText("two")]),
+                    DocGroup(Attrs::ListItem, [
+                        DocGroup(Attrs::Para, [DocText("three")]),
+                    ]),
+                ]),
+                DocGroup(Attrs::ListItem, [
+                    DocGroup(Attrs::Para, [DocText("four")]),
+                ]),
+            ]),
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("five")]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_ordered_list() {
+    assert_eq!(
+        round_trip("3. three\n4. four\n   - bullet\n\n- after\n"),
+        doc![
+            DocGroup(Attrs::OrderedListItem(3), [
+                DocGroup(Attrs::Para, [DocText("three")]),
+            ]),
+            DocGroup(Attrs::OrderedListItem(3), [
+                DocGroup(Attrs::Para, [DocText("four")]),
+                DocGroup(Attrs::ListItem, [
+                    DocGroup(Attrs::Para, [DocText("bullet")]),
+                ]),
+            ]),
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("after")]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_task_list() {
+    assert_eq!(
+        round_trip("- [ ] todo\n- [x] done\n  - [ ] nested\n- \\[ \\] not a task\n"),
+        doc![
+            DocGroup(Attrs::TaskListItem { checked: false }, [
+                DocGroup(Attrs::Para, [DocText("todo")]),
+            ]),
+            DocGroup(Attrs::TaskListItem { checked: true }, [
+                DocGroup(Attrs::Para, [DocText("done")]),
+                DocGroup(Attrs::TaskListItem { checked: false }, [
+                    DocGroup(Attrs::Para, [DocText("nested")]),
+                ]),
+            ]),
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [DocText("[ ] not a task")]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_link() {
+    assert_eq!(
+        round_trip("[a **b**](http://example.com/) c\n"),
+        doc![
+            DocGroup(Attrs::Para, [
+                DocText({RtfStyle::Link("http://example.com/".to_string())}, "a "),
+                DocText({
+                    RtfStyle::Link("http://example.com/".to_string()),
+                    RtfStyle::Bold,
+                }, "b"),
+                DocText(" c"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_code_and_strikethrough() {
+    assert_eq!(
+        round_trip("run `cargo test` ~~now~~ **~~later~~**\n"),
+        doc![
+            DocGroup(Attrs::Para, [
+                DocText("run "),
+                DocText({RtfStyle::Code}, "cargo test"),
+                DocText(" "),
+                DocText({RtfStyle::Strikethrough}, "now"),
+                DocText(" "),
+                DocText({RtfStyle::Bold, RtfStyle::Strikethrough}, "later"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_strikethrough_flanking() {
+    assert_eq!(
+        Doc(markdown_to_doc("a ~~ b and ~~~c~~~ stay, but ~~d *e*~~ strikes\n").unwrap()),
+        doc![
+            DocGroup(Attrs::Para, [
+                DocText("a ~~ b and ~~~c~~~ stay, but "),
+                DocText({RtfStyle::Strikethrough}, "d "),
+                DocText({RtfStyle::Italic, RtfStyle::Strikethrough}, "e"),
+                DocText(" strikes"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_code_language() {
+    assert_eq!(
+        round_trip("```rust\nfn main() {}\n```\n\n```\nplain\n```\n"),
+        doc![
+            DocGroup(Attrs::Code(Some("rust".to_string())), [
+                DocText("fn main() {}"),
+            ]),
+            DocGroup(Attrs::Code(None), [
+                DocText("plain"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_highlighted_code() {
+    let doc = markdown_to_doc("```rust\nlet x = \"<hi>\"; // 1\n```\n").unwrap();
+    let output = doc_to_highlighted_markdown(&doc).unwrap();
+    assert!(output.contains(r#"<pre class="highlight" data-language="rust">"#));
+    assert!(output.contains(r#"<span class="hl-keyword">let</span>"#));
+    assert!(output.contains(r#"<span class="hl-string">&quot;&lt;hi&gt;&quot;</span>"#));
+    assert!(output.contains(r#"<span class="hl-comment">// 1</span>"#));
+}
+
+#[test]
+fn markdown_image() {
+    assert_eq!(
+        round_trip("see **![a chart](chart.png)** below\n\n- ![](x.png)\n"),
+        doc![
+            DocGroup(Attrs::Para, [
+                DocText("see "),
+                DocGroup(Attrs::Image {
+                    src: "chart.png".to_string(),
+                    alt: "a chart".to_string(),
+                }, []),
+                DocText(" below"),
+            ]),
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [
+                    DocGroup(Attrs::Image {
+                        src: "x.png".to_string(),
+                        alt: "".to_string(),
+                    }, []),
+                ]),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn markdown_table() {
+    assert_eq!(
+        round_trip("| Name | *Notes* |\n| --- | --- |\n| one | two |\n"),
+        doc![
+            DocGroup(Attrs::Table, [
+                DocGroup(Attrs::TableRow, [
+                    DocGroup(Attrs::TableCell, [
+                        DocGroup(Attrs::Para, [DocText("Name")]),
+                    ]),
+                    DocGroup(Attrs::TableCell, [
+                        DocGroup(Attrs::Para, [DocText({RtfStyle::Italic}, "Notes")]),
+                    ]),
+                ]),
+                DocGroup(Attrs::TableRow, [
+                    DocGroup(Attrs::TableCell, [
+                        DocGroup(Attrs::Para, [DocText("one")]),
+                    ]),
+                    DocGroup(Attrs::TableCell, [
+                        DocGroup(Attrs::Para, [DocText("two")]),
+                    ]),
+                ]),
+            ]),
+        ]
+    );
+}
+
+/// Rewrites a document into the part of it Markdown can represent:
+///
+/// - Carets aren't content, so they aren't written.
+/// - Code and HTML blocks are written verbatim, so their text loses its
+///   styles and their images are dropped.
+/// - Markdown has no syntax for an empty paragraph, so those are dropped,
+///   except in table cells, which always hold one.
+/// - Rules are dropped from the start of list items, since bullets are
+///   written as "*" and "* ***" reads back as a single rule.
+/// - GFM reads a task's checkbox from the start of its first paragraph, so
+///   task list items that don't start with one become bulleted items.
+/// - A numbered list has one start number, so each run of numbered items
+///   takes the first item's.
+fn markdown_subset(span: &DocSpan<RtfSchema>, verbatim: bool) -> DocSpan<RtfSchema> {
+    let mut out: DocSpan<RtfSchema> = vec![];
+    for elem in span {
+        match elem {
+            DocGroup(Attrs::Caret { .. }, _) => {}
+            DocGroup(Attrs::Image { .. }, _) if verbatim => {}
+            DocGroup(attrs, body) => {
+                let verbatim = match attrs {
+                    Attrs::Code(..) | Attrs::Html => true,
+                    _ => false,
+                };
+                let mut body = markdown_subset(body, verbatim);
+                match attrs {
+                    Attrs::Para if body.is_empty() => continue,
+                    Attrs::TableCell if body.is_empty() => {
+                        body.push(DocGroup(Attrs::Para, vec![]));
+                    }
+                    Attrs::ListItem | Attrs::OrderedListItem(..) | Attrs::TaskListItem { .. } => {
+                        while let Some(DocGroup(Attrs::Rule, _)) = body.first() {
+                            body.remove(0);
+                        }
+                    }
+                    _ => {}
+                }
+                let attrs = match (out.last(), attrs) {
+                    (
+                        Some(DocGroup(Attrs::OrderedListItem(start), _)),
+                        Attrs::OrderedListItem(..),
+                    ) => Attrs::OrderedListItem(*start),
+                    (_, Attrs::TaskListItem { .. }) => match body.first() {
+                        Some(DocGroup(Attrs::Para, _)) => attrs.clone(),
+                        _ => Attrs::ListItem,
+                    },
+                    _ => attrs.clone(),
+                };
+                out.push(DocGroup(attrs, body));
+            }
+            DocText(_, text) if verbatim => {
+                out.place(&DocText(StyleSet::new(), text.clone()));
+            }
+            DocText(..) => {
+                out.place(elem);
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn markdown_random_round_trip() {
+    for seed in 0..200 {
+        let mut rng = XorShiftRng::from_seed([seed + 1, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]);
+        let doc = Doc(markdown_subset(&random_doc(&mut rng).0, false));
+        let output = doc_to_markdown(&doc.0).unwrap();
+        assert_eq!(
+            Doc(markdown_to_doc(&output).unwrap()),
+            doc,
+            "seed {} serialized to {:?}",
+            seed,
+            output
+        );
+    }
+}
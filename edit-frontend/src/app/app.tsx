@@ -243,6 +243,8 @@ function Modal(props: any) {
 
 
 export type NoticeProps = {
+  // Identifies a notice that's removed once it no longer applies.
+  id?: string,
   element: React.ReactNode,
   level: 'notice' | 'error',
 };
@@ -604,19 +606,18 @@ export class EditorFrame extends React.Component {
 
       case 'ServerDisconnect': {
         this.showNotification({
-          element: <div>The editor has disconnected from the server. Sorry. This page will automatically reload, or you can manually <button onClick={(e) => { window.location.reload(); }}>Refresh the page</button></div>,
+          id: 'disconnect',
+          element: <div>The editor has disconnected from the server and is reconnecting. Your edits will be sent once it's back.</div>,
           level: 'error',
         });
 
-        // Start refresh poller.
-        setTimeout(() => {
-          setInterval(() => {
-            graphqlPage('home').then(() => {
-              // Can access server, continue
-              window.location.reload();
-            });
-          }, 2000);
-        }, 3000);
+        break;
+      }
+
+      case 'ServerReconnect': {
+        this.setState({
+          notices: this.state.notices.filter(x => x.id !== 'disconnect'),
+        });
 
         break;
       }
@@ -626,7 +627,17 @@ export class EditorFrame extends React.Component {
       }
 
       case 'PromptString': {
-        // unsure what these should do, if anything
+        // Ask the user for a value, then send it back as the command's only
+        // field. Cancelling the prompt sends nothing.
+        let [label, value, responseCommand] = command.fields;
+        let response = window.prompt(label, value);
+        if (response !== null) {
+          let sendCommand: any = responseCommand;
+          Object.keys(sendCommand.fields).forEach((key) => {
+            sendCommand.fields[key] = response;
+          });
+          this.client.sendCommand(sendCommand);
+        }
         break;
       }
     }
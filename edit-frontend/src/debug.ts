@@ -131,7 +131,7 @@ const DEBUG = {
             .map((x): Attr => x.getAttributeNode('data-focus')!);
     },
 
-    clientID: (): String => {
+    clientID: (): string | undefined => {
         return globalClientBindings!.clientID();
     },
 
@@ -33,13 +33,19 @@ function isBlock(
 function isEmptyBlock(
   el: Node | null
 ) {
-  return isBlock(el) && (el as Element).querySelector('span') == null;
+  return isBlock(el) && (el as Element).querySelector('span, a, img') == null;
+}
+
+function isImage(
+  el: Node | null,
+) {
+  return el !== null && el.nodeType == 1 && (el as Element).tagName.toLowerCase() == 'img';
 }
 
 function isSpan(
   el: Node | null,
 ) {
-  return el !== null && el.nodeType == 1 && (el as Element).tagName.toLowerCase() == 'span';
+  return el !== null && el.nodeType == 1 && ['span', 'a'].indexOf((el as Element).tagName.toLowerCase()) > -1;
 }
 
 function isElement(
@@ -189,6 +195,11 @@ function resolveCursorFromPositionInner(
       return curto(node);
     }
 
+    // Images are selected whole, like a character.
+    if (isImage(node)) {
+      return curto(node);
+    }
+
     // Skip empty groups.
     if (node.childNodes.length == 0) {
       return resolveCursorFromPositionInner(node.previousSibling, parent);
@@ -314,7 +325,7 @@ function getCursorFromPoint(root: Element, x: number, y: number): any {
     ? resolveCursorFromPosition(text.textNode, text.offset)
     : (element !== null
       ? resolveCursorFromPositionInner(element.textNode.childNodes[element.offset - 1], element.textNode)
-      : (isEmptyBlock(target)
+      : (isEmptyBlock(target) || isImage(target)
         ? curto(target as any)
         : null));
 }
@@ -336,9 +347,16 @@ export class Editor extends React.Component {
   onClick(e: MouseEvent) {
     let option = e.ctrlKey || e.metaKey;
     let isAnchor = e.target ? util.matchesSelector(e.target as Node, '[data-style-Link]') : false;
-    if (option && isAnchor) {
-      let url = (e.target as HTMLElement).dataset['styleLink'];
-      (window as any).open(url, '_blank').focus();
+    if (isAnchor) {
+      // Links are edited like any other text, and only followed with a
+      // modifier key held.
+      e.preventDefault();
+      if (option) {
+        let url = (e.target as HTMLElement).dataset['styleLink'];
+        if (url !== undefined && util.isSafeUrl(url)) {
+          (window as any).open(url, '_blank').focus();
+        }
+      }
     }
   }
 
@@ -349,6 +367,23 @@ export class Editor extends React.Component {
       return;
     }
 
+    // Clicking the checkbox in the margin of a task list item checks or
+    // unchecks it without moving the caret.
+    let target = e.target as HTMLElement;
+    if (util.matchesSelector(target, 'div[data-tag="task"]')) {
+      let margin = parseFloat(window.getComputedStyle(target).paddingLeft || '0');
+      if (e.clientX < target.getBoundingClientRect().left + margin) {
+        this.props.controller.sendCommand({
+          'tag': 'ToggleTask',
+          'fields': {
+            'curspan': curto(target),
+          },
+        });
+        e.preventDefault();
+        return;
+      }
+    }
+
     // Manually detect doubleclick.
     if (Date.now() - this.lastClickTime < 400) {
       let destCursor = getCursorFromPoint(this.el, e.clientX, e.clientY);
@@ -441,6 +476,19 @@ export class Editor extends React.Component {
       return;
     }
 
+    // Paste formatted text when the clipboard has any, and plain text
+    // otherwise.
+    const html = e.clipboardData.getData('text/html');
+    if (html) {
+      this.props.controller.sendCommand({
+        'tag': 'PasteHtml',
+        'fields': {
+          html: html,
+        },
+      });
+      return;
+    }
+
     const text = e.clipboardData.getData('text/plain');
     console.info('(c) got pasted text: ', text);
     this.props.controller.sendCommand({
@@ -68,3 +68,15 @@ export function matchesSelector(
 
   return matches.call(el, selector);
 }
+
+// Whether a URL is safe to link to or open: it's relative, or its scheme is
+// http, https or mailto. This matches is_safe_url in edit-common.
+export function isSafeUrl(url: string): boolean {
+  url = url.replace(/[\s\x00-\x1f\x7f-\x9f]/g, '');
+  let match = url.match(/^([^:\/?#]*):/);
+  if (match === null) {
+    // Without a scheme, the URL is relative.
+    return true;
+  }
+  return ['http', 'https', 'mailto'].indexOf(match[1].toLowerCase()) !== -1;
+}
@@ -49,20 +49,45 @@ export function vm(el: Node) {
             // TODO If this element is following a text node, we just add it
             // to the previous element. right?
 
-            let span = document.createElement('span');
+            // Styles are a list of names, or objects for styles with a
+            // value such as {"Link": url}.
+            let classes: Array<string> = [];
+            let link: string | null = null;
+            (Array.isArray(styles) ? styles : Object.keys(styles)).forEach((style: any) => {
+                if (typeof style == 'string') {
+                    classes.push(style);
+                } else if ('Link' in style) {
+                    classes.push('Link');
+                    // Pick the same link as the server when there are several.
+                    if (link === null || style.Link < link) {
+                        link = style.Link;
+                    }
+                }
+            });
+
+            // Linked text is an anchor in place of a span.
+            let span = document.createElement(link !== null ? 'a' : 'span');
             span.appendChild(document.createTextNode(text));
-            Object.keys(styles).map(key => {
+            classes.forEach(key => {
                 span.classList.add(key);
             });
+            if (link !== null) {
+                // Unsafe links are still marked, but can't be followed.
+                if (util.isSafeUrl(link)) {
+                    span.setAttribute('href', link);
+                }
+                span.setAttribute('data-style-Link', link);
+            }
 
             // Excessive matching function in JS, where this shouldn't happen
             function hasMatchingTextStyles(left: any, right: any) {
                 if (left != null) {
-                    if (util.matchesSelector(left, 'span')) {
+                    if (util.matchesSelector(left, 'span, a') && left.tagName == right.tagName) {
                         let leftClasses = Array.from(lastNode().classList).sort();
                         let rightClasses = Array.from(span.classList).sort();
     
-                        if (leftClasses.join(' ') == rightClasses.join(' ')) {
+                        if (leftClasses.join(' ') == rightClasses.join(' ') &&
+                            left.getAttribute('href') == right.getAttribute('href')) {
                             return true;
                         }
                     }
@@ -78,6 +103,17 @@ export function vm(el: Node) {
             cur()[0] += 1;
         },
         WrapPrevious([n, attrs]: [number, any]) {
+            // Images are an empty <img> element in place of a group.
+            if (attrs !== null && typeof attrs == 'object' && 'Image' in attrs) {
+                let img = document.createElement('img');
+                img.setAttribute('data-tag', 'img');
+                img.setAttribute('src', attrs.Image.src);
+                img.setAttribute('alt', attrs.Image.alt);
+                cur()[1].insertBefore(img, curNode());
+                cur()[0] += 1;
+                return;
+            }
+
             let div = document.createElement('div');
             Object.keys(attrs).forEach(key => {
             	div.setAttribute('data-' + key, attrs[key]);
@@ -88,6 +124,18 @@ export function vm(el: Node) {
             }
             cur()[0] += 1;
         },
+        ReplaceAttrs(attrs: any) {
+            let node = lastNode();
+            assert(node.nodeType == 1);
+            Array.from(node.attributes).forEach((attr: any) => {
+                if (attr.name.startsWith('data-')) {
+                    node.removeAttribute(attr.name);
+                }
+            });
+            Object.keys(attrs).forEach(key => {
+                node.setAttribute('data-' + key, attrs[key]);
+            });
+        },
         UnwrapSelf() {
             let node = cur()[1];
             stack.pop();
@@ -100,7 +148,7 @@ export function vm(el: Node) {
         // Take current text node, merge it left, and move on
         JoinTextLeft() {
             let right = curNode();
-            assert!(util.matchesSelector(right, 'span'));
+            assert!(util.matchesSelector(right, 'span, a'));
 
             let left = right.previousSibling;
             while (right.childNodes.length) {
@@ -144,6 +192,9 @@ export function vm(el: Node) {
                     assert(typeof fields[0] == 'number');
                     assert(typeof fields[1] == 'object');
                     return handlers.WrapPrevious(fields);
+                case 'ReplaceAttrs':
+                    assert(typeof fields == 'object');
+                    return handlers.ReplaceAttrs(fields);
                 default:
                     return handlers[tag]!();
             }
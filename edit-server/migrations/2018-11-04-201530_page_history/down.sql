@@ -0,0 +1,2 @@
+DROP TABLE page_snapshots;
+DROP TABLE page_ops
\ No newline at end of file
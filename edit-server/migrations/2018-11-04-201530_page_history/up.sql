@@ -0,0 +1,16 @@
+CREATE TABLE page_ops (
+  page_id VARCHAR NOT NULL,
+  version INTEGER NOT NULL,
+  client_id VARCHAR NOT NULL,
+  timestamp BIGINT NOT NULL,
+  op TEXT NOT NULL,
+  summary TEXT NOT NULL,
+  PRIMARY KEY (page_id, version)
+);
+
+CREATE TABLE page_snapshots (
+  page_id VARCHAR NOT NULL,
+  version INTEGER NOT NULL,
+  body TEXT NOT NULL,
+  PRIMARY KEY (page_id, version)
+)
\ No newline at end of file
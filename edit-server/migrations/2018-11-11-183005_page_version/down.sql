@@ -0,0 +1,7 @@
+CREATE TABLE posts_old (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  body TEXT NOT NULL
+);
+INSERT INTO posts_old SELECT id, body FROM posts;
+DROP TABLE posts;
+ALTER TABLE posts_old RENAME TO posts
\ No newline at end of file
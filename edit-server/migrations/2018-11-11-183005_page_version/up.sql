@@ -0,0 +1,2 @@
+ALTER TABLE posts ADD COLUMN version INTEGER NOT NULL DEFAULT 100;
+ALTER TABLE posts ADD COLUMN generation INTEGER NOT NULL DEFAULT 0
\ No newline at end of file
@@ -15,9 +15,9 @@ extern crate serde_json;
 use crypto::digest::Digest;
 use crypto::md5::Md5;
 use edit_common::{
-    doc_as_html,
+    doc_as_highlighted_html,
     markdown::{
-        doc_to_markdown,
+        doc_to_highlighted_markdown,
         markdown_to_doc,
     },
 };
@@ -273,7 +273,7 @@ fn run_http_server(port: u16, client_proxy: bool) {
                                 Err(err) => {
                                     eprintln!("Error decoding document: {:?}", err);
                                     doc![
-                                        DocGroup(Attrs::Code, [
+                                        DocGroup(Attrs::Code(None), [
                                             DocText("Error decoding document."),
                                         ]),
                                     ]
@@ -287,7 +287,7 @@ fn run_http_server(port: u16, client_proxy: bool) {
                                 Err(err) => {
                                     eprintln!("Error decoding document: {:?}", err);
                                     doc![
-                                        DocGroup(Attrs::Code, [
+                                        DocGroup(Attrs::Code(None), [
                                             DocText("Error decoding document."),
                                         ]),
                                     ]
@@ -415,7 +415,7 @@ fn run_http_server(port: u16, client_proxy: bool) {
                 )).to_owned().to_string();
 
                 // Preload content into the file using the db connection.
-                let body: String = doc_to_markdown(
+                let body: String = doc_to_highlighted_markdown(
                     &get_or_create_page_graphql(
                         &id,
                         &doc![DocGroup(Attrs::Header(1), [DocText(&id)])],
@@ -437,6 +437,24 @@ fn run_http_server(port: u16, client_proxy: bool) {
             },
 
             (GET) ["/{id}", id: String] => {
+                // Export the page as HTML from "/{id}.html".
+                if id.ends_with(".html") {
+                    let id = &id[..id.len() - ".html".len()];
+                    if !valid_page_id(id) {
+                        return Response::empty_404();
+                    }
+                    return match get_page_html_graphql(id) {
+                        Ok(Some(html)) => Response::html(format!(
+                            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                             <title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+                            id,
+                            html,
+                        )),
+                        Ok(None) => Response::empty_404(),
+                        Err(err) => Response::text(err.to_string()).with_status_code(500),
+                    };
+                }
+
                 // Check id validity.
                 if !valid_page_id(&id) {
                     // Redirect to /welcome-{remote ip}
@@ -456,7 +474,7 @@ fn run_http_server(port: u16, client_proxy: bool) {
                 )).to_owned().to_string();
 
                 // Preload content into the file using the db connection.
-                let body: String = doc_as_html(
+                let body: String = doc_as_highlighted_html(
                     &get_or_create_page_graphql(
                         &id,
                         &doc![DocGroup(Attrs::Header(1), [
@@ -1,7 +1,13 @@
 use failure::Error;
+use oatie::diff::diff;
 use oatie::doc::*;
 use oatie::rtf::*;
+use oatie::transform::{
+    transform_cursor,
+    Gravity,
+};
 use oatie::writer::*;
+use oatie::OT;
 
 fn remove_carets_span(span: &DocSpan<RtfSchema>) -> Result<DocSpan<RtfSchema>, Error> {
     let mut ret: DocSpan<RtfSchema> = vec![];
@@ -66,3 +72,118 @@ pub fn remove_carets_op(doc: &Doc<RtfSchema>, filter: Vec<String>) -> Result<Op<
     remove_carets_op_span(&mut writer, &doc.0, &filter)?;
     Ok(Op(writer.result(), vec![]))
 }
+
+/// Collects every caret in a span, in document order.
+fn collect_carets(span: &DocSpan<RtfSchema>, carets: &mut Vec<Attrs>) {
+    for elem in span {
+        if let DocGroup(ref attrs, ref inner) = elem {
+            if let Attrs::Caret { .. } = attrs {
+                carets.push(attrs.clone());
+            } else {
+                collect_carets(inner, carets);
+            }
+        }
+    }
+}
+
+/// The path of groups to descend into from the root to reach the first
+/// block in the top-level group at `index` that can hold a caret.
+fn caret_block_path(span: &DocSpan<RtfSchema>, index: usize) -> Option<Vec<usize>> {
+    match span.get(index) {
+        Some(DocGroup(attrs, inner)) => {
+            let supports_text = RtfSchema::track_type_from_attrs(attrs)
+                .map_or(false, |track| track.supports_text());
+            if supports_text {
+                Some(vec![index])
+            } else {
+                caret_block_path(inner, 0).map(|mut path| {
+                    path.insert(0, index);
+                    path
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Adds a caret at the start of the block at `path`.
+fn add_caret_span(path: &[usize], attrs: &Attrs) -> AddSpan<RtfSchema> {
+    let mut span = vec![];
+    match path.split_first() {
+        Some((&index, rest)) => {
+            if index > 0 {
+                span.push(AddSkip(index));
+            }
+            span.push(AddWithGroup(add_caret_span(rest, attrs)));
+        }
+        None => span.push(AddGroup(attrs.clone(), vec![])),
+    }
+    span
+}
+
+/// Computes an operation that turns `doc` into `new_doc`, which has no
+/// carets, while keeping the carets of the clients in `filter`. Carets
+/// are taken out before diffing and put back after, and carets in a block
+/// that's removed or replaced move to the start of the block that takes its
+/// place, the way removing a table row moves the carets in it.
+pub fn overwrite_carets_op(
+    doc: &Doc<RtfSchema>,
+    new_doc: &Doc<RtfSchema>,
+    filter: Vec<String>,
+) -> Result<Op<RtfSchema>, Error> {
+    let strip = remove_carets_op(doc, filter.clone())?;
+    let stripped = Op::apply(doc, &strip);
+    let restore = Op::invert(doc, &strip);
+
+    let (op, _) = Op::transform(&restore, &diff(&stripped, new_doc));
+    let result = Op::apply(doc, &op);
+
+    let mut kept = vec![];
+    collect_carets(&result.0, &mut kept);
+
+    // Find the carets that were deleted along with their block, and where
+    // that block ends up.
+    let mut moved = Op::empty();
+    for (index, elem) in doc.0.iter().enumerate() {
+        let mut carets = vec![];
+        if let DocGroup(_, ref inner) = elem {
+            collect_carets(inner, &mut carets);
+        }
+        carets.retain(|attrs| match attrs {
+            Attrs::Caret { client_id, .. } => filter.contains(client_id) && !kept.contains(attrs),
+            _ => false,
+        });
+        if carets.is_empty() {
+            continue;
+        }
+
+        let mut cur = vec![];
+        if index > 0 {
+            cur.push(CurSkip(index));
+        }
+        cur.push(CurGroup);
+        let target = transform_cursor(&cur, &op, Gravity::Left)
+            .iter()
+            .map(|elem| match elem {
+                CurSkip(count) => *count,
+                _ => 0,
+            })
+            .sum::<usize>();
+
+        // Use the nearest block that can hold a caret, looking forward
+        // first.
+        let len = result.0.len();
+        let path = (target..len)
+            .chain((0..target.min(len)).rev())
+            .filter_map(|index| caret_block_path(&result.0, index))
+            .next();
+        if let Some(path) = path {
+            for attrs in &carets {
+                let add = Op(vec![], add_caret_span(&path, attrs));
+                moved = Op::compose(&moved, &add);
+            }
+        }
+    }
+
+    Ok(Op::compose(&op, &moved))
+}
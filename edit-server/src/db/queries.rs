@@ -1,3 +1,4 @@
+use crate::carets::remove_carets;
 use crate::db::*;
 use diesel::{
     self,
@@ -8,6 +9,10 @@ use failure::Error;
 use oatie::doc::*;
 use oatie::rtf::*;
 use std::collections::HashMap;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
 
 /// Retry a SQLite method, if a "database is locked" error is thrown, repeating
 /// it until it is successful (or another type of error is thrown).
@@ -29,25 +34,72 @@ where
 }
 
 // TODO usize is not useful.
-// also is this always upsert? shoudl be named that then
+/// Stores the document of a page, creating the page if it doesn't exist.
+/// The page's version and generation are left as they are.
 pub fn create_page<'a>(conn: &SqliteConnection, id: &'a str, doc: &Doc<RtfSchema>) -> usize {
     use super::schema::posts;
 
     let body = serde_json::to_string(doc).unwrap();
 
+    let updated = lock_retry(|| {
+        diesel::update(posts::table.filter(posts::id.eq(id)))
+            .set(posts::body.eq(&body))
+            .execute(conn)
+    })
+    .expect("Error saving post");
+    if updated > 0 {
+        return updated;
+    }
+
     let new_post = NewPost {
         id: id,
         body: &body,
     };
 
     lock_retry(|| {
-        diesel::replace_into(posts::table)
+        diesel::insert_into(posts::table)
             .values(&new_post)
             .execute(conn)
     })
     .expect("Error saving new post")
 }
 
+/// Stores the version of a page's document, so numbering continues from it
+/// after a restart.
+pub fn set_page_version(
+    conn: &SqliteConnection,
+    input_id: &str,
+    input_version: usize,
+) -> Result<usize, Error> {
+    use super::schema::posts::dsl::*;
+
+    Ok(lock_retry(|| {
+        diesel::update(posts.filter(id.eq(input_id)))
+            .set(version.eq(input_version as i32))
+            .execute(conn)
+    })?)
+}
+
+/// Starts a new generation of a page, returning its number. Each time a page
+/// is loaded for syncing it gets a new generation, so clients can tell if
+/// their version of the page came from an earlier load of it.
+pub fn next_page_generation(conn: &SqliteConnection, input_id: &str) -> Result<usize, Error> {
+    use super::schema::posts::dsl::*;
+
+    lock_retry(|| {
+        diesel::update(posts.filter(id.eq(input_id)))
+            .set(generation.eq(generation + 1))
+            .execute(conn)
+    })?;
+    let value = lock_retry(|| {
+        posts
+            .filter(id.eq(input_id))
+            .select(generation)
+            .first::<i32>(conn)
+    })?;
+    Ok(value as usize)
+}
+
 pub fn all_posts(db: &SqliteConnection) -> HashMap<String, String> {
     use super::schema::posts::dsl::*;
 
@@ -68,7 +120,7 @@ pub fn get_single_page(db: &SqliteConnection, input_id: &str) -> Option<Doc<RtfS
     post.map_err::<Error, _>(|x| x.into())
         .map(|x| x.body.to_string())
         .and_then(|x| {
-            Ok(oatie::deserialize::doc_ron(&x).or(serde_json::from_str::<Doc<RtfSchema>>(&x))?)
+            Ok(oatie::deserialize::doc_ron(&x).or(oatie::deserialize::doc_json(&x))?)
         })
         .ok()
 }
@@ -79,6 +131,179 @@ pub fn get_single_page_raw(db: &SqliteConnection, input_id: &str) -> Option<Post
     lock_retry(|| posts.filter(id.eq(input_id)).first::<Post>(db)).ok()
 }
 
+// Page history
+
+/// Records an operation committed to a page, which took the page from
+/// `input_version` to the next version. `summary` describes the change for
+/// the page's revisions, and is empty if the operation only moved carets.
+/// Those are still recorded, since later operations are made against a
+/// document with the carets in it, but aren't listed as revisions.
+pub fn create_page_op(
+    conn: &SqliteConnection,
+    input_id: &str,
+    input_version: usize,
+    client_id: &str,
+    op: &Op<RtfSchema>,
+    summary: &str,
+) -> Result<usize, Error> {
+    use super::schema::page_ops;
+
+    let body = serde_json::to_string(op)?;
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let timestamp = since_epoch.as_secs() * 1000 + u64::from(since_epoch.subsec_millis());
+
+    let new_op = NewPageOp {
+        page_id: input_id,
+        version: input_version as i32,
+        client_id,
+        timestamp: timestamp as i64,
+        op: &body,
+        summary,
+    };
+
+    Ok(lock_retry(|| {
+        diesel::insert_into(page_ops::table)
+            .values(&new_op)
+            .execute(conn)
+    })?)
+}
+
+/// Records the whole document of a page at a version, so loading that
+/// version (or a later one) doesn't have to replay every operation before it.
+/// The document should include carets, as the operations after it do.
+pub fn create_page_snapshot(
+    conn: &SqliteConnection,
+    input_id: &str,
+    input_version: usize,
+    doc: &Doc<RtfSchema>,
+) -> Result<usize, Error> {
+    use super::schema::page_snapshots;
+
+    let body = serde_json::to_string(doc)?;
+
+    let new_snapshot = NewPageSnapshot {
+        page_id: input_id,
+        version: input_version as i32,
+        body: &body,
+    };
+
+    Ok(lock_retry(|| {
+        diesel::replace_into(page_snapshots::table)
+            .values(&new_snapshot)
+            .execute(conn)
+    })?)
+}
+
+/// Operations recorded for a page from `start_version` up to (but not
+/// including) `end_version`, oldest first.
+pub fn select_page_ops(
+    db: &SqliteConnection,
+    input_id: &str,
+    start_version: usize,
+    end_version: usize,
+) -> Result<Vec<PageOp>, Error> {
+    use super::schema::page_ops::dsl::*;
+
+    Ok(lock_retry(|| {
+        page_ops
+            .filter(page_id.eq(input_id))
+            .filter(version.ge(start_version as i32))
+            .filter(version.lt(end_version as i32))
+            .order(version.asc())
+            .load::<PageOp>(db)
+    })?)
+}
+
+/// Up to `limit` operations recorded for a page before `end_version` that
+/// changed more than carets, newest first.
+pub fn select_page_revisions_before(
+    db: &SqliteConnection,
+    input_id: &str,
+    end_version: usize,
+    limit: usize,
+) -> Result<Vec<PageOp>, Error> {
+    use super::schema::page_ops::dsl::*;
+
+    Ok(lock_retry(|| {
+        page_ops
+            .filter(page_id.eq(input_id))
+            .filter(version.lt(end_version as i32))
+            .filter(summary.ne(""))
+            .order(version.desc())
+            .limit(limit as i64)
+            .load::<PageOp>(db)
+    })?)
+}
+
+/// The version following the last recorded operation of a page, if any
+/// operations have been recorded.
+pub fn next_page_version(db: &SqliteConnection, input_id: &str) -> Result<Option<usize>, Error> {
+    use super::schema::page_ops::dsl::*;
+    use diesel::dsl::max;
+
+    let last = lock_retry(|| {
+        page_ops
+            .filter(page_id.eq(input_id))
+            .select(max(version))
+            .first::<Option<i32>>(db)
+    })?;
+    Ok(last.map(|x| x as usize + 1))
+}
+
+/// Loads a page as it was at a version, by replaying the operations
+/// recorded since the closest snapshot before it. Carets are kept.
+pub fn replay_page_history(
+    db: &SqliteConnection,
+    input_id: &str,
+    input_version: usize,
+) -> Result<Doc<RtfSchema>, Error> {
+    use super::schema::page_snapshots::dsl::*;
+
+    let snapshot = lock_retry(|| {
+        page_snapshots
+            .filter(page_id.eq(input_id))
+            .filter(version.le(input_version as i32))
+            .order(version.desc())
+            .first::<PageSnapshot>(db)
+            .optional()
+    })?
+    .ok_or_else(|| {
+        format_err!(
+            "No history of page {:?} at version {}",
+            input_id,
+            input_version
+        )
+    })?;
+
+    // Snapshots are only ever written as JSON.
+    let mut doc = oatie::deserialize::doc_json(&snapshot.body)?;
+
+    let start_version = snapshot.version as usize;
+    let ops = select_page_ops(db, input_id, start_version, input_version)?;
+    if ops.len() != input_version - start_version {
+        bail!(
+            "Missing operations for page {:?} before version {}",
+            input_id,
+            input_version
+        );
+    }
+    for page_op in ops {
+        let op: Op<RtfSchema> = serde_json::from_str(&page_op.op)?;
+        doc = Op::try_apply(&doc, &op)?;
+    }
+
+    Ok(doc)
+}
+
+/// Loads a page as it was at a past version, with carets removed.
+pub fn get_page_at_version(
+    db: &SqliteConnection,
+    input_id: &str,
+    input_version: usize,
+) -> Result<Doc<RtfSchema>, Error> {
+    remove_carets(&replay_page_history(db, input_id, input_version)?)
+}
+
 // Logs
 
 pub fn create_log<'a>(
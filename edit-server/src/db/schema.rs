@@ -6,11 +6,32 @@ table! {
     }
 }
 
+table! {
+    page_ops (page_id, version) {
+        page_id -> Text,
+        version -> Integer,
+        client_id -> Text,
+        timestamp -> BigInt,
+        op -> Text,
+        summary -> Text,
+    }
+}
+
+table! {
+    page_snapshots (page_id, version) {
+        page_id -> Text,
+        version -> Integer,
+        body -> Text,
+    }
+}
+
 table! {
     posts (id) {
         id -> Text,
         body -> Text,
+        version -> Integer,
+        generation -> Integer,
     }
 }
 
-allow_tables_to_appear_in_same_query!(logs, posts,);
+allow_tables_to_appear_in_same_query!(logs, page_ops, page_snapshots, posts,);
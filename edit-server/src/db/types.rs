@@ -2,6 +2,8 @@
 pub struct Post {
     pub id: String,
     pub body: String,
+    pub version: i32,
+    pub generation: i32,
 }
 
 use super::schema::posts;
@@ -28,3 +30,43 @@ pub struct NewLog<'a> {
     pub source: &'a str,
     pub body: &'a str,
 }
+
+#[derive(Queryable, Debug)]
+pub struct PageOp {
+    pub page_id: String,
+    pub version: i32,
+    pub client_id: String,
+    pub timestamp: i64,
+    pub op: String,
+    pub summary: String,
+}
+
+use super::schema::page_ops;
+
+#[derive(Insertable)]
+#[table_name = "page_ops"]
+pub struct NewPageOp<'a> {
+    pub page_id: &'a str,
+    pub version: i32,
+    pub client_id: &'a str,
+    pub timestamp: i64,
+    pub op: &'a str,
+    pub summary: &'a str,
+}
+
+#[derive(Queryable, Debug)]
+pub struct PageSnapshot {
+    pub page_id: String,
+    pub version: i32,
+    pub body: String,
+}
+
+use super::schema::page_snapshots;
+
+#[derive(Insertable)]
+#[table_name = "page_snapshots"]
+pub struct NewPageSnapshot<'a> {
+    pub page_id: &'a str,
+    pub version: i32,
+    pub body: &'a str,
+}
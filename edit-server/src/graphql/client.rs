@@ -64,6 +64,29 @@ query ($id: String!) {
     Some(Doc(body))
 }
 
+/// Returns a page as semantic HTML, or None if it doesn't exist.
+pub fn get_page_html_graphql(input_id: &str) -> Result<Option<String>, Error> {
+    let ret = graphql_request(
+        r#"
+
+query ($id: String!) {
+    page(id: $id) {
+        html
+    }
+}
+
+"#,
+        &json!({
+            "id": input_id,
+        }),
+    )?;
+
+    Ok(ret
+        .pointer("/data/page/html")
+        .and_then(|x| x.as_str())
+        .map(|x| x.to_string()))
+}
+
 pub fn graphql_request(
     query: &str,
     variables: &serde_json::Value,
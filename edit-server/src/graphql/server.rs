@@ -10,6 +10,7 @@ use crate::{
 
 use crossbeam_channel::Sender as CCSender;
 use diesel::sqlite::SqliteConnection;
+use edit_common::html::doc_to_html;
 use edit_common::markdown::*;
 use juniper::{
     self,
@@ -26,9 +27,15 @@ use r2d2;
 use r2d2_diesel::ConnectionManager;
 use rouille;
 use serde_json;
+use std::cmp;
+use std::i32;
 use std::io::prelude::*;
 
+/// The most revisions returned at once.
+const MAX_REVISIONS: i32 = 100;
+
 struct Page {
+    id: String,
     doc: String,
 }
 
@@ -37,16 +44,83 @@ struct PageId {
     id: String,
 }
 
-graphql_object!(Page: () |&self| {
+/// A change made to a page.
+#[derive(GraphQLObject)]
+struct Revision {
+    /// The version the change was made to, so `at(version)` is the page
+    /// before it.
+    version: i32,
+    /// The version the change resulted in, so `at(nextVersion)` is the page
+    /// after it.
+    next_version: i32,
+    /// When the change was made, in milliseconds since the Unix epoch.
+    timestamp: f64,
+    /// The client that made the change.
+    client_id: String,
+    /// A short description of what changed.
+    summary: String,
+}
+
+graphql_object!(Page: Ctx |&self| {
     field doc() -> &str {
         self.doc.as_str()
     }
 
     field markdown() -> String {
         let doc = oatie::deserialize::doc_ron(&self.doc)
-            .or(serde_json::from_str::<Doc<RtfSchema>>(&self.doc)).unwrap();
+            .or(oatie::deserialize::doc_json(&self.doc)).unwrap();
         doc_to_markdown(&doc.0).unwrap()
     }
+
+    field html() -> String {
+        let doc = oatie::deserialize::doc_ron(&self.doc)
+            .or(oatie::deserialize::doc_json(&self.doc)).unwrap();
+        doc_to_html(&doc.0)
+    }
+
+    // Revisions are listed newest first, leaving out changes that only move
+    // carets. At most `first` are returned (up to 100), from before the
+    // version `before` if given, so older ones can be paged through.
+    field revisions(
+        &executor,
+        first: Option<i32>,
+        before: Option<i32>,
+    ) -> FieldResult<Vec<Revision>> {
+        let first = cmp::min(first.unwrap_or(MAX_REVISIONS), MAX_REVISIONS);
+        let before = before.unwrap_or(i32::MAX);
+        if first < 0 || before < 0 {
+            return Err(FieldError::new("Invalid range", juniper::Value::null()));
+        }
+        let conn = executor.context().db_pool.get().unwrap();
+
+        let page_ops = select_page_revisions_before(&conn, &self.id, before as usize, first as usize)?;
+        let revisions = page_ops
+            .into_iter()
+            .map(|page_op| Revision {
+                version: page_op.version,
+                next_version: page_op.version + 1,
+                timestamp: page_op.timestamp as f64,
+                client_id: page_op.client_id,
+                summary: page_op.summary,
+            })
+            .collect();
+        Ok(revisions)
+    }
+
+    // The page as it was at a version, before the change made to that
+    // version, with carets removed.
+    field at(&executor, version: i32) -> FieldResult<Page> {
+        if version < 0 {
+            return Err(FieldError::new("Invalid version", juniper::Value::null()));
+        }
+        let conn = executor.context().db_pool.get().unwrap();
+
+        let doc = get_page_at_version(&conn, &self.id, version as usize)?;
+        Ok(Page {
+            id: self.id.clone(),
+            doc: serde_json::to_string(&doc)?,
+        })
+    }
 });
 
 struct Query;
@@ -58,6 +132,7 @@ graphql_object!(Query: Ctx |&self| {
         let page = get_single_page_raw(&conn, &id);
 
         Ok(page.map(|x| Page {
+            id: x.id,
             doc: x.body
         }))
     }
@@ -103,7 +178,7 @@ graphql_object!(Mutations: Ctx |&self| {
                         eprintln!("Error in doc: {:?}", doc);
                         eprintln!("Error decoding document: {:?}", err);
                         doc![
-                            DocGroup(Attrs::Code, [
+                            DocGroup(Attrs::Code(None), [
                                 DocText("Error decoding document."),
                             ]),
                         ]
@@ -119,17 +194,15 @@ graphql_object!(Mutations: Ctx |&self| {
         create_page(&conn, &id, &doc);
         let page = get_single_page_raw(&conn, &id);
 
-        // Kick off all current clients.
+        // Send the new document to all current clients as an edit.
         let _ = executor.context().tx_master.send(ClientNotify(id.clone(), ClientUpdate::Overwrite {
             doc,
         }));
 
-        // TODO there is probably a race condition between create_page and the overwrite
-        // kicking off users from sync. This should be fixed
-
         // TODO can the below executor code in getOrCreatePage also be the same code here?
 
         Ok(page.map(|x| Page {
+            id: x.id,
             doc: x.body
         }).unwrap())
     }
@@ -143,7 +216,7 @@ graphql_object!(Mutations: Ctx |&self| {
 
         let doc = get_single_page_raw(&conn, &id)
             .map(|x| x.body)
-            .unwrap_or_else(move || {
+            .unwrap_or_else(|| {
                 let doc = Doc(::ron::de::from_str(&default).unwrap());
                 create_page(&conn, &id, &doc);
 
@@ -155,9 +228,35 @@ graphql_object!(Mutations: Ctx |&self| {
             });
 
         Ok(Page {
+            id,
             doc
         })
     }
+
+    // Sends the page as it was at a past version to all current clients as
+    // an edit, so the restore can itself be undone.
+    field restorePage(
+        &executor,
+        id: String,
+        version: i32,
+    ) -> FieldResult<Page> {
+        if version < 0 {
+            return Err(FieldError::new("Invalid version", juniper::Value::null()));
+        }
+        let conn = executor.context().db_pool.get().unwrap();
+
+        let doc = get_page_at_version(&conn, &id, version as usize)?;
+        let body = serde_json::to_string(&doc)?;
+
+        let _ = executor.context().tx_master.send(ClientNotify(id.clone(), ClientUpdate::Overwrite {
+            doc,
+        }));
+
+        Ok(Page {
+            id,
+            doc: body,
+        })
+    }
 });
 
 // Arbitrary context data.
@@ -1,5 +1,6 @@
 //! Sync state. This is a candidate file to be moved into Oatie.
 
+use edit_common::commands::ErrorCode;
 use failure::Error;
 use oatie::{
     doc::*,
@@ -7,11 +8,29 @@ use oatie::{
     validate::validate_doc,
 };
 use std::collections::HashMap;
+use std::fmt;
+
+/// Why an operation couldn't be committed.
+#[derive(Debug, Fail)]
+#[fail(display = "{:?}: {}", code, message)]
+pub struct CommitError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CommitError {
+    pub fn new<T: fmt::Display>(code: ErrorCode, message: T) -> CommitError {
+        CommitError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
 
 pub struct SyncState {
     pub version: usize,
     pub clients: HashMap<String, usize>, // client_id -> client_version
-    pub history: HashMap<usize, Op<RtfSchema>>, // version -> op
+    pub history: HashMap<usize, (String, Op<RtfSchema>)>, // version -> (client_id, op)
     pub doc: Doc<RtfSchema>,
 }
 
@@ -37,11 +56,11 @@ impl SyncState {
         // Transform against all more recent operations.
         while input_version < target_version {
             // If the version exists (it should) transform against it.
-            let version_op = self
+            let (_, version_op) = self
                 .history
                 .get(&input_version)
                 .ok_or(format_err!("Version missing from history"))?;
-            let (updated_op, _) = Op::transform(version_op, &op);
+            let (updated_op, _) = Op::try_transform(version_op, &op)?;
             op = updated_op;
 
             input_version += 1;
@@ -54,11 +73,31 @@ impl SyncState {
         client_id: &str,
         op: Op<RtfSchema>,
         input_version: usize,
-    ) -> Result<Op<RtfSchema>, Error> {
+    ) -> Result<Op<RtfSchema>, CommitError> {
         let target_version = self.version;
 
+        // The operation must be made against a version we can transform
+        // it from.
+        if input_version > target_version
+            || (input_version..target_version).any(|v| !self.history.contains_key(&v))
+        {
+            return Err(CommitError::new(
+                ErrorCode::InvalidVersion,
+                format!("Version {} is not in history", input_version),
+            ));
+        }
+
         // Update the operation so we can apply it to the document.
-        let op = self.update_operation_to_current(op, input_version, target_version)?;
+        let op = self
+            .update_operation_to_current(op, input_version, target_version)
+            .map_err(|err| CommitError::new(ErrorCode::InvalidOperation, err))?;
+
+        // Update the document with this operation.
+        let new_doc = Op::try_apply(&self.doc, &op)
+            .map_err(|err| CommitError::new(ErrorCode::InvalidOperation, err))?;
+
+        // Gut check.
+        validate_doc(&new_doc).map_err(|err| CommitError::new(ErrorCode::InvalidDocument, err))?;
 
         if let Some(version) = self.clients.get_mut(client_id) {
             *version = target_version;
@@ -69,13 +108,8 @@ impl SyncState {
 
         // Prune history entries.
         self.prune_history();
-        self.history.insert(target_version, op.clone());
-
-        // Update the document with this operation.
-        let new_doc = Op::apply(&self.doc, &op);
-
-        // Gut check.
-        validate_doc(&self.doc).map_err(|_| format_err!("Validation error"))?;
+        self.history
+            .insert(target_version, (client_id.to_string(), op.clone()));
 
         // Commit chhanges.
         self.doc = new_doc;
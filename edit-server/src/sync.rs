@@ -19,6 +19,7 @@ use edit_common::simple_ws::*;
 use failure::Error;
 use oatie::doc::*;
 use oatie::rtf::*;
+use oatie::stepper::DocStepper;
 use rand::{
     thread_rng,
     Rng,
@@ -26,7 +27,11 @@ use rand::{
 use serde_json;
 use std::env;
 use std::{
-    collections::HashMap,
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    sync::Arc,
     thread,
     time::Duration,
 };
@@ -40,7 +45,17 @@ fn debug_sync_delay() -> Option<u64> {
 }
 
 const INITIAL_SYNC_VERSION: usize = 100; // Arbitrarily select version 100
+
+/// How many versions apart snapshots of a page's history are recorded.
+const SNAPSHOT_INTERVAL: usize = 100;
+
+/// How many operations a client can have rejected before it's disconnected.
+const MAX_CLIENT_ERRORS: usize = 3;
+
+/// Client ID used for operations originating from the server itself.
+const SERVER_CLIENT_ID: &str = "$server";
 const PAGE_TITLE_LEN: usize = 100; // 100 chars is the limit
+const CLIENT_ID_LEN: usize = 6;
 
 pub fn default_new_doc(id: &str) -> Doc<RtfSchema> {
     doc![DocGroup(Attrs::Header(1), [DocText(id),])]
@@ -55,8 +70,13 @@ pub fn valid_page_id(input: &str) -> bool {
         .all(|x| x.is_digit(10) || x.is_ascii_alphabetic() || x == '_' || x == '-')
 }
 
-fn generate_random_page_id() -> String {
-    thread_rng().gen_ascii_chars().take(6).collect()
+/// Whether a client ID is one `generate_random_client_id` could have made.
+pub fn valid_client_id(input: &str) -> bool {
+    input.len() == CLIENT_ID_LEN && input.chars().all(|x| x.is_ascii_alphanumeric())
+}
+
+fn generate_random_client_id() -> String {
+    thread_rng().gen_ascii_chars().take(CLIENT_ID_LEN).collect()
 }
 
 // Target Page ID, ClientUpdate
@@ -72,9 +92,20 @@ pub enum ClientUpdate {
         client_id: String,
         op: Op<RtfSchema>,
         version: usize,
+        generation: usize,
     },
-    Disconnect {
+    Resume {
         client_id: String,
+        // The id this connection was given, which it's initialized with
+        // instead if it can't resume.
+        new_client_id: String,
+        out: simple_ws::Sender,
+        version: usize,
+        generation: usize,
+        op: Option<Op<RtfSchema>>,
+    },
+    Disconnect {
+        out: simple_ws::Sender,
     },
     Overwrite {
         doc: Doc<RtfSchema>,
@@ -86,6 +117,29 @@ struct ClientSocket {
     page_id: String,
     client_id: String,
     tx_master: CCSender<ClientNotify>,
+    out: simple_ws::Sender,
+    // A reconnecting client waits to say who it is before we notify the
+    // sync thread of its connection.
+    connected: bool,
+}
+
+impl ClientSocket {
+    fn connect(&mut self) {
+        eprintln!(
+            "(!) Client {:?} connected to {:?}",
+            self.client_id, self.page_id
+        );
+
+        // Notify sync thread of our having connected.
+        let _ = self.tx_master.send(ClientNotify(
+            self.page_id.to_string(),
+            ClientUpdate::Connect {
+                client_id: self.client_id.to_string(),
+                out: self.out.clone(),
+            },
+        ));
+        self.connected = true;
+    }
 }
 
 /// Websocket implementation.
@@ -112,23 +166,21 @@ impl SimpleSocket for ClientSocket {
             "home".to_string()
         };
 
-        eprintln!("(!) Client {:?} connected to {:?}", client_id, page_id);
-
-        // Notify sync thread of our having connected.
-        let _ = tx_master.send(ClientNotify(
-            page_id.to_string(),
-            ClientUpdate::Connect {
-                client_id: client_id.to_string(),
-                out: out,
-            },
-        ));
-
         // Store client state in a ClientSocket.
-        Ok(ClientSocket {
+        let mut socket = ClientSocket {
             page_id: page_id.to_string(),
             client_id: client_id.to_string(),
             tx_master,
-        })
+            out,
+            connected: false,
+        };
+
+        // Clients reconnecting with "?resume" send ServerCommand::Resume first.
+        if !url.query_pairs().any(|(key, _)| key == "resume") {
+            socket.connect();
+        }
+
+        Ok(socket)
     }
 
     fn handle_message(&mut self, data: &[u8]) -> Result<(), Error> {
@@ -138,19 +190,49 @@ impl SimpleSocket for ClientSocket {
         // log_sync!("SERVER", ClientPacket(command.clone()));
         // println!("-----> {:?}", command);
 
+        if !self.connected {
+            if let ServerCommand::Resume(ref client_id, version, generation, ref op) = command {
+                if valid_client_id(client_id) {
+                    eprintln!("(!) Client {:?} resumed on {:?}", client_id, self.page_id);
+                    let new_client_id = self.client_id.clone();
+                    self.client_id = client_id.to_string();
+                    self.connected = true;
+                    let _ = self.tx_master.send(ClientNotify(
+                        self.page_id.to_string(),
+                        ClientUpdate::Resume {
+                            client_id: client_id.to_string(),
+                            new_client_id,
+                            out: self.out.clone(),
+                            version,
+                            generation,
+                            op: op.clone(),
+                        },
+                    ));
+                    return Ok(());
+                }
+            }
+
+            // Otherwise connect as a new client.
+            self.connect();
+        }
+
         match command {
-            ServerCommand::Commit(client_id, op, version) => {
+            ServerCommand::Commit(client_id, op, version, generation) => {
                 let _ = self.tx_master.send(ClientNotify(
                     self.page_id.to_string(),
                     ClientUpdate::Commit {
                         client_id,
                         op,
                         version,
+                        generation,
                     },
                 ));
                 // let mut sync_state = self.sync_state_mutex.lock().unwrap();
                 // sync_state.ops.push_back((client_id.clone(), version, op.clone()));
             }
+            ServerCommand::Resume(..) => {
+                // NOTE we ignore this, it's only valid as a first command
+            }
             ServerCommand::TerminateProxy => {
                 // NOTE we ignore this, it's only used for user proxy
             }
@@ -163,10 +245,14 @@ impl SimpleSocket for ClientSocket {
     }
 
     fn cleanup(&mut self) -> Result<(), Error> {
+        if !self.connected {
+            return Ok(());
+        }
+
         self.tx_master.send(ClientNotify(
             self.page_id.to_owned(),
             ClientUpdate::Disconnect {
-                client_id: self.client_id.to_owned(),
+                out: self.out.clone(),
             },
         ));
 
@@ -174,11 +260,122 @@ impl SimpleSocket for ClientSocket {
     }
 }
 
+/// Describes what an operation changes in `doc`, like "+12 characters, -3
+/// characters". Returns an empty string if it only moves carets.
+fn summarize_op(doc: &Doc<RtfSchema>, op: &Op<RtfSchema>) -> String {
+    fn is_caret(elem: Option<&DocElement<RtfSchema>>) -> bool {
+        match elem {
+            Some(DocGroup(Attrs::Caret { .. }, _)) => true,
+            _ => false,
+        }
+    }
+
+    fn summarize_del(
+        doc: &mut DocStepper<RtfSchema>,
+        span: &DelSpan<RtfSchema>,
+        deleted: &mut usize,
+        blocks: &mut usize,
+        styled: &mut bool,
+    ) {
+        for elem in span {
+            match elem {
+                DelSkip(len) => doc.skip(*len),
+                DelStyles(len, _) => {
+                    *styled = true;
+                    doc.skip(*len);
+                }
+                DelText(len) => {
+                    *deleted += len;
+                    doc.skip(*len);
+                }
+                DelWithGroup(span) => {
+                    doc.enter();
+                    summarize_del(doc, span, deleted, blocks, styled);
+                    doc.exit();
+                }
+                DelGroup(span) => {
+                    if !is_caret(doc.head()) {
+                        *blocks += 1;
+                    }
+                    doc.enter();
+                    summarize_del(doc, span, deleted, blocks, styled);
+                    doc.exit();
+                }
+                DelGroupAll | DelObject => {
+                    if !is_caret(doc.head()) {
+                        *blocks += 1;
+                    }
+                    doc.skip(1);
+                }
+                DelMany(len) => {
+                    for _ in 0..*len {
+                        match doc.head() {
+                            Some(DocText(..)) => *deleted += 1,
+                            head if !is_caret(head) => *blocks += 1,
+                            _ => {}
+                        }
+                        doc.skip(1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn summarize_add(
+        span: &AddSpan<RtfSchema>,
+        inserted: &mut usize,
+        blocks: &mut usize,
+        styled: &mut bool,
+    ) {
+        for elem in span {
+            match elem {
+                AddText(_, text) => *inserted += text.char_len(),
+                AddWithGroup(span) => summarize_add(span, inserted, blocks, styled),
+                AddGroup(Attrs::Caret { .. }, _) => {}
+                AddGroup(_, span) => {
+                    *blocks += 1;
+                    summarize_add(span, inserted, blocks, styled);
+                }
+                AddStyles(..) | AddAttrs(..) => *styled = true,
+                AddSkip(..) => {}
+            }
+        }
+    }
+
+    let (mut inserted, mut deleted, mut blocks, mut styled) = (0, 0, 0, false);
+    let mut stepper = DocStepper::new(&doc.0);
+    summarize_del(&mut stepper, &op.0, &mut deleted, &mut blocks, &mut styled);
+    summarize_add(&op.1, &mut inserted, &mut blocks, &mut styled);
+
+    let mut parts = vec![];
+    if inserted > 0 {
+        parts.push(format!("+{} characters", inserted));
+    }
+    if deleted > 0 {
+        parts.push(format!("-{} characters", deleted));
+    }
+    if blocks > 0 {
+        parts.push(format!("{} blocks changed", blocks));
+    }
+    if styled {
+        parts.push("formatting".to_string());
+    }
+    parts.join(", ")
+}
+
 pub struct PageController {
     page_id: String,
     db_pool: DbPool,
     state: SyncState,
+    // Versions are only comparable within a generation of the page.
+    generation: usize,
     clients: HashMap<String, simple_ws::Sender>,
+    // How many operations each client has had rejected. This is kept after
+    // a client disconnects, so an evicted client can't resume.
+    client_errors: HashMap<String, usize>,
+    // Clients that disconnected and haven't resumed since, which are the
+    // only ones that can resume.
+    disconnected: HashSet<String>,
 }
 
 #[allow(unused)]
@@ -186,23 +383,44 @@ impl PageController {
     // This is just a commit across all operations, and forwarding it to
     // all listening clients. It also is the commit point for all new
     // operations.
-    fn sync_commit(&mut self, client_id: &str, op: Op<RtfSchema>, input_version: usize) {
-        // TODO we should evict the client if this fails.
-        let op = self
-            .state
-            .commit(&client_id, op, input_version)
-            .expect("Could not commit client operation.");
+    fn sync_commit(
+        &mut self,
+        client_id: &str,
+        op: Op<RtfSchema>,
+        input_version: usize,
+    ) -> Result<(), CommitError> {
+        let prev_doc = self.state.doc.clone();
+        let op = self.state.commit(&client_id, op, input_version)?;
 
         // Updates the database with the new document version.
+        let conn = self.db_pool.get().unwrap();
         if let Ok(doc) = remove_carets(&self.state.doc) {
-            let conn = self.db_pool.get().unwrap();
             // TODO why is this "create" page
             create_page(&conn, &self.page_id, &doc);
+            if let Err(err) = set_page_version(&conn, &self.page_id, self.state.version) {
+                eprintln!("could not save version of {:?}: {:?}", self.page_id, err);
+            }
+        }
+
+        // Record the operation in the page's history.
+        let version = self.state.version;
+        let summary = summarize_op(&prev_doc, &op);
+        if let Err(err) =
+            create_page_op(&conn, &self.page_id, version - 1, client_id, &op, &summary)
+        {
+            eprintln!("could not record history of {:?}: {:?}", self.page_id, err);
+        }
+        if version % SNAPSHOT_INTERVAL == 0 {
+            if let Err(err) = create_page_snapshot(&conn, &self.page_id, version, &self.state.doc) {
+                eprintln!("could not record snapshot of {:?}: {:?}", self.page_id, err);
+            }
         }
 
         // Broadcast this operation to all connected websockets.
         let command = ClientCommand::Update(self.state.version, client_id.to_owned(), op);
         self.broadcast_client_command(&command);
+
+        Ok(())
     }
 
     /// Forward command to everyone in our client set.
@@ -222,9 +440,51 @@ impl PageController {
         Ok(client.lock().unwrap().send(json.clone())?)
     }
 
-    fn send_client_restart(&self, client_id: &str) -> Result<(), Error> {
+    /// The command that (re)initializes a client with our document.
+    fn init_command(&self, client_id: &str) -> ClientCommand {
+        ClientCommand::Init(
+            client_id.to_string(),
+            self.state.doc.0.clone(),
+            self.state.version,
+            self.generation,
+        )
+    }
+
+    /// Tells a client its operation was rejected and starts it over with our
+    /// document, since its own no longer matches. Clients with too many
+    /// rejected operations are disconnected.
+    fn reject_client(&mut self, client_id: &str, err: CommitError) {
+        let command = ClientCommand::Error {
+            code: err.code,
+            message: err.message,
+        };
+        let init = self.init_command(client_id);
+        if let Some(client) = self.clients.get(client_id) {
+            let _ = self.send_client_command(client, &command);
+            let _ = self.send_client_command(client, &init);
+        }
+        if let Some(version) = self.state.clients.get_mut(client_id) {
+            *version = self.state.version;
+        }
+
+        let errors = self.client_errors.entry(client_id.to_string()).or_insert(0);
+        *errors += 1;
+        if *errors >= MAX_CLIENT_ERRORS {
+            eprintln!("evicting client {:?} after {} errors", client_id, errors);
+            let _ = self.send_client_restart(client_id, "Too many invalid operations.");
+        }
+    }
+
+    /// Whether a client was disconnected for having too many operations
+    /// rejected.
+    fn is_evicted(&self, client_id: &str) -> bool {
+        self.client_errors
+            .get(client_id)
+            .map_or(false, |errors| *errors >= MAX_CLIENT_ERRORS)
+    }
+
+    fn send_client_restart(&self, client_id: &str, reason: &str) -> Result<(), Error> {
         let code = ws::CloseCode::Restart;
-        let reason = "Server received an updated version of the document.";
 
         // TODO abort if client doesn't exist, or move the client_id referencing
         // to its own function
@@ -234,16 +494,109 @@ impl PageController {
         Ok(())
     }
 
-    /// Forward restart code to everyone in our client set.
-    fn broadcast_restart(&self) -> Result<(), Error> {
-        let code = ws::CloseCode::Restart;
-        let reason = "Server received an updated version of the document.";
-        for (_, client) in &self.clients {
-            let _ = client.lock().unwrap().close_with_reason(code, reason);
+    /// Operations committed from `version` up to our current version, along
+    /// with the client that committed each. Operations pruned from memory are
+    /// read from the page's recorded history.
+    fn history_since(&self, version: usize) -> Result<Vec<(String, Op<RtfSchema>)>, Error> {
+        if version > self.state.version {
+            bail!("Version {} is ahead of the page", version);
+        }
+
+        if let Some(ops) = (version..self.state.version)
+            .map(|v| self.state.history.get(&v).cloned())
+            .collect::<Option<Vec<_>>>()
+        {
+            return Ok(ops);
         }
+
+        let conn = self.db_pool.get()?;
+        let page_ops = select_page_ops(&conn, &self.page_id, version, self.state.version)?;
+        if page_ops.len() != self.state.version - version {
+            bail!("Operations since version {} are missing", version);
+        }
+        page_ops
+            .into_iter()
+            .map(|page_op| Ok((page_op.client_id, serde_json::from_str(&page_op.op)?)))
+            .collect()
+    }
+
+    /// Picks up with a client that reconnected. The operations it missed are
+    /// sent to it, then the operation it had sent before disconnecting is
+    /// committed, unless it was committed before the connection dropped.
+    fn resume(
+        &mut self,
+        client_id: &str,
+        out: simple_ws::Sender,
+        version: usize,
+        generation: usize,
+        op: Option<Op<RtfSchema>>,
+    ) -> Result<(), Error> {
+        let missed = if generation == self.generation {
+            self.history_since(version).ok()
+        } else {
+            None
+        };
+
+        let missed = match missed {
+            Some(missed) => missed,
+            None => {
+                // Start the client over with our document.
+                eprintln!(
+                    "client {:?} can't resume from version {} of {:?}, resyncing",
+                    client_id, version, self.page_id
+                );
+                let command = self.init_command(client_id);
+                self.send_client_command(&out, &command)?;
+                self.state
+                    .clients
+                    .insert(client_id.to_string(), self.state.version);
+                self.clients.insert(client_id.to_string(), out);
+                return Ok(());
+            }
+        };
+
+        // Register the client at the version it left off, which keeps the
+        // history it's missing from being pruned.
+        self.state.clients.insert(client_id.to_string(), version);
+        self.clients.insert(client_id.to_string(), out.clone());
+
+        // Send it each operation it missed.
+        let mut committed = false;
+        for (i, (author, missed_op)) in missed.iter().enumerate() {
+            committed = committed || author == client_id;
+            let command =
+                ClientCommand::Update(version + i + 1, author.to_string(), missed_op.clone());
+            self.send_client_command(&out, &command)?;
+        }
+
+        if let (Some(op), false) = (op, committed) {
+            let op = missed.iter().try_fold(op, |op, (_, missed_op)| {
+                Op::try_transform(missed_op, &op).map(|(op, _)| op)
+            });
+            let version = self.state.version;
+            let res = match op {
+                Ok(op) => self.sync_commit(client_id, op, version),
+                Err(err) => Err(CommitError::new(ErrorCode::InvalidOperation, err)),
+            };
+            if let Err(err) = res {
+                eprintln!(
+                    "received invalid packet from client: {:?} - {:?}",
+                    client_id, err
+                );
+                self.reject_client(client_id, err);
+            }
+        }
+
         Ok(())
     }
 
+    /// Computes an operation that turns our document into `doc`, keeping
+    /// the carets of connected clients.
+    fn overwrite_op(&self, doc: &Doc<RtfSchema>) -> Result<Op<RtfSchema>, Error> {
+        let client_ids = self.state.clients.keys().cloned().collect();
+        overwrite_carets_op(&self.state.doc, doc, client_ids)
+    }
+
     // Handle a client's update.
     fn handle(&mut self, notification: ClientUpdate) {
         match notification {
@@ -251,8 +604,7 @@ impl PageController {
                 let version = self.state.version;
 
                 // Initialize client state on outgoing websocket.
-                let command =
-                    ClientCommand::Init(client_id.to_string(), self.state.doc.0.clone(), version);
+                let command = self.init_command(&client_id);
                 let _ = self.send_client_command(&out, &command);
 
                 // Register with clients list.
@@ -262,22 +614,56 @@ impl PageController {
                 self.clients.insert(client_id.to_string(), out);
             }
 
-            ClientUpdate::Disconnect { client_id } => {
-                // Remove our caret from document.
+            ClientUpdate::Disconnect { out } => {
+                // Find the client by its connection, since the client may
+                // have been given a new id when it couldn't resume.
+                let client_id = match self
+                    .clients
+                    .iter()
+                    .find(|(_, client)| Arc::ptr_eq(client, &out))
+                {
+                    Some((client_id, _)) => client_id.to_string(),
+                    None => return,
+                };
+
+                // Remove our caret from document. This isn't the client's
+                // own edit, so it isn't mistaken for one if it resumes.
                 let op = remove_carets_op(&self.state.doc, vec![client_id.clone()]).unwrap();
                 let version = self.state.version;
-                self.sync_commit(&client_id, op, version);
+                if let Err(err) = self.sync_commit(SERVER_CLIENT_ID, op, version) {
+                    eprintln!("could not remove caret of {:?}: {:?}", client_id, err);
+                }
 
                 // Remove from our client set.
                 self.state.clients.remove(&client_id);
                 self.clients.remove(&client_id);
+                self.disconnected.insert(client_id);
             }
 
             ClientUpdate::Commit {
                 client_id,
                 op,
                 version,
+                generation,
             } => {
+                // The client's version is from an earlier load of this page,
+                // so its operation can't be transformed. Send it our document
+                // to start over from instead.
+                if generation != self.generation {
+                    eprintln!(
+                        "client {:?} is on an old generation of {:?}, resyncing",
+                        client_id, self.page_id
+                    );
+                    let command = self.init_command(&client_id);
+                    if let Some(out) = self.clients.get(&client_id) {
+                        let _ = self.send_client_command(out, &command);
+                    }
+                    self.state
+                        .clients
+                        .insert(client_id.to_string(), self.state.version);
+                    return;
+                }
+
                 // Debug setting to wait a set duration between successive notifications.
                 // This is helpful for artifically forcing a client-side queue of operations.
                 // It's not needed for operation though.
@@ -285,27 +671,66 @@ impl PageController {
                     thread::sleep(Duration::from_millis(delay));
                 }
 
-                // Commit the operation.
-                // TODO remove this AssertUnwindSafe, since it's probably not safe.
-                let sync = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
-                    self.sync_commit(&client_id, op, version);
-                }));
+                // Operations an evicted client sent before it was
+                // disconnected are dropped.
+                if self.is_evicted(&client_id) {
+                    return;
+                }
 
-                if let Err(err) = sync {
+                // Commit the operation.
+                if let Err(err) = self.sync_commit(&client_id, op, version) {
                     eprintln!(
                         "received invalid packet from client: {:?} - {:?}",
                         client_id, err
                     );
-                    // let _ = self.send_client_restart(&client_id);
+                    self.reject_client(&client_id, err);
                 }
             }
 
-            ClientUpdate::Overwrite { doc } => {
-                let _ = self.broadcast_restart();
+            ClientUpdate::Resume {
+                client_id,
+                new_client_id,
+                out,
+                version,
+                generation,
+                op,
+            } => {
+                // Only clients that disconnected can resume, and only once,
+                // so a connection can't claim an id that's in use or that the
+                // page never gave out. Others start over as a new client.
+                if !self.disconnected.remove(&client_id) {
+                    eprintln!("client {:?} is unknown, connecting it as new", client_id);
+                    self.handle(ClientUpdate::Connect {
+                        client_id: new_client_id,
+                        out,
+                    });
+                    return;
+                }
+
+                if self.is_evicted(&client_id) {
+                    eprintln!("refusing to resume evicted client {:?}", client_id);
+                    let _ = out
+                        .lock()
+                        .unwrap()
+                        .close_with_reason(ws::CloseCode::Restart, "Too many invalid operations.");
+                    return;
+                }
 
-                // Rewrite our state.
-                self.state = SyncState::new(doc, INITIAL_SYNC_VERSION);
-                self.clients = HashMap::new();
+                if let Err(err) = self.resume(&client_id, out, version, generation, op) {
+                    eprintln!("could not resume client {:?}: {:?}", client_id, err);
+                }
+            }
+
+            ClientUpdate::Overwrite { doc } => {
+                // Commit the new document as an edit, so connected clients
+                // receive it like any other operation.
+                let version = self.state.version;
+                let res = self
+                    .overwrite_op(&doc)
+                    .and_then(|op| Ok(self.sync_commit(SERVER_CLIENT_ID, op, version)?));
+                if let Err(err) = res {
+                    eprintln!("could not overwrite page {:?}: {:?}", self.page_id, err);
+                }
             }
         }
     }
@@ -316,16 +741,34 @@ pub fn spawn_sync_thread(
     page_id: String,
     rx_notify: CCReceiver<ClientUpdate>,
     inner_doc: Doc<RtfSchema>,
+    version: usize,
+    generation: usize,
     db_pool: DbPool,
 ) -> Result<(), Error> {
     thread::spawn(move || {
+        // Replaying the page's history should give the stored document,
+        // unless it has no history yet or was unloaded with carets in it
+        // (the stored document has none). Then its history starts over here.
+        {
+            let conn = db_pool.get().unwrap();
+            let replayed = replay_page_history(&conn, &page_id, version).ok();
+            if replayed.as_ref() != Some(&inner_doc) {
+                if let Err(err) = create_page_snapshot(&conn, &page_id, version, &inner_doc) {
+                    eprintln!("could not record snapshot of {:?}: {:?}", page_id, err);
+                }
+            }
+        }
+
         // This page ID's state.
         // TODO make this a ::new(...) statement
         let mut sync = PageController {
             page_id,
             db_pool,
-            state: SyncState::new(inner_doc, INITIAL_SYNC_VERSION),
+            state: SyncState::new(inner_doc, version),
+            generation,
             clients: HashMap::new(),
+            client_errors: HashMap::new(),
+            disconnected: HashSet::new(),
         };
 
         while let Some(notification) = rx_notify.recv() {
@@ -366,9 +809,21 @@ impl PageMaster {
             let conn = self.db_pool.get().unwrap();
             let inner_doc = get_single_page(&conn, page_id).unwrap_or_else(|| {
                 eprintln!("warning: could not find page {:?}, using default.", page_id);
-                default_new_doc(page_id)
+                let doc = default_new_doc(page_id);
+                create_page(&conn, page_id, &doc);
+                doc
             });
 
+            // Continue numbering versions from the stored version, or after
+            // the page's recorded history if that's further along.
+            let version = get_single_page_raw(&conn, page_id)
+                .map(|post| post.version as usize)
+                .into_iter()
+                .chain(next_page_version(&conn, page_id).unwrap_or(None))
+                .max()
+                .unwrap_or(INITIAL_SYNC_VERSION);
+            let generation = next_page_generation(&conn, page_id).unwrap_or(0);
+
             let (tx_notify, rx_notify) = unbounded();
             self.pages.insert(page_id.to_string(), tx_notify.clone());
 
@@ -377,6 +832,8 @@ impl PageMaster {
                 page_id.to_owned(),
                 rx_notify,
                 inner_doc,
+                version,
+                generation,
                 self.db_pool.clone(),
             );
             tx_notify
@@ -436,7 +893,7 @@ pub fn sync_socket_server(port: u16) {
             // Listen to commands from the clients and submit to sync server.
             SocketHandler::<ClientSocket>::new(
                 (
-                    generate_random_page_id(), // TODO can we select from unused client IDs?
+                    generate_random_client_id(), // TODO can we select from unused client IDs?
                     tx_master.clone(),
                 ),
                 out,
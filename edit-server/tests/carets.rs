@@ -0,0 +1,98 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_server::carets::*;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::validate::validate_doc;
+use oatie::OT;
+
+fn caret(client_id: &str) -> Attrs {
+    Attrs::Caret {
+        client_id: client_id.to_string(),
+        focus: true,
+    }
+}
+
+#[test]
+fn overwrite_keeps_carets() {
+    let doc = doc![
+        DocGroup(Attrs::Para, [
+            DocText("hel"),
+            DocGroup(caret("left"), []),
+            DocText("lo"),
+        ]),
+        DocGroup(Attrs::Para, [
+            DocText("wor"),
+            DocGroup(caret("right"), []),
+            DocText("ld"),
+        ]),
+    ];
+
+    // The first block is replaced by a list item, and the second is edited.
+    let new_doc = doc![
+        DocGroup(Attrs::ListItem, [
+            DocGroup(Attrs::Para, [DocText("hello")]),
+        ]),
+        DocGroup(Attrs::Para, [DocText("world!")]),
+    ];
+
+    let op = overwrite_carets_op(
+        &doc,
+        &new_doc,
+        vec!["left".to_string(), "right".to_string()],
+    )
+    .unwrap();
+    let result = Op::apply(&doc, &op);
+
+    validate_doc(&result).unwrap();
+    assert_eq!(
+        result,
+        doc![
+            DocGroup(Attrs::ListItem, [
+                DocGroup(Attrs::Para, [
+                    DocGroup(caret("left"), []),
+                    DocText("hello"),
+                ]),
+            ]),
+            DocGroup(Attrs::Para, [
+                DocText("wor"),
+                DocGroup(caret("right"), []),
+                DocText("ld!"),
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn overwrite_removed_block_carets() {
+    let doc = doc![
+        DocGroup(Attrs::Para, [DocText("one")]),
+        DocGroup(Attrs::Para, [
+            DocText("tw"),
+            DocGroup(caret("left"), []),
+            DocText("o"),
+        ]),
+        DocGroup(Attrs::Rule, []),
+    ];
+
+    // With its block gone and only a rule after it, the caret moves back to
+    // the block before.
+    let new_doc = doc![
+        DocGroup(Attrs::Para, [DocText("one")]),
+        DocGroup(Attrs::Rule, []),
+    ];
+
+    let op = overwrite_carets_op(&doc, &new_doc, vec!["left".to_string()]).unwrap();
+
+    assert_eq!(
+        Op::apply(&doc, &op),
+        doc![
+            DocGroup(Attrs::Para, [
+                DocGroup(caret("left"), []),
+                DocText("one"),
+            ]),
+            DocGroup(Attrs::Rule, []),
+        ]
+    );
+}
@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate oatie;
+
+use edit_common::commands::ErrorCode;
+use edit_server::state::*;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+fn state() -> SyncState {
+    let mut state = SyncState::new(doc![DocGroup(Attrs::Para, [DocText("hello")])], 0);
+    state.clients.insert("left".to_string(), 0);
+    state.clients.insert("right".to_string(), 0);
+    state
+}
+
+#[test]
+fn commit_rejects_malformed_op() {
+    let mut state = state();
+    let doc = state.doc.clone();
+
+    // Deletes past the end of the paragraph.
+    let op = Op(vec![DelWithGroup(vec![DelSkip(3), DelText(5)])], vec![]);
+    let err = state.commit("left", op, 0).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidOperation);
+    assert_eq!(state.doc, doc);
+    assert_eq!(state.version, 0);
+}
+
+#[test]
+fn commit_rejects_malformed_op_from_old_version() {
+    let mut state = state();
+    let op = Op(vec![DelWithGroup(vec![DelText(1)])], vec![]);
+    state.commit("left", op, 0).unwrap();
+    let doc = state.doc.clone();
+
+    // Made against the version before the first commit.
+    let op = Op(vec![DelWithGroup(vec![DelSkip(1), DelText(5)])], vec![]);
+    let err = state.commit("right", op, 0).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidOperation);
+    assert_eq!(state.doc, doc);
+    assert_eq!(state.version, 1);
+}
+
+#[test]
+fn commit_rejects_unknown_version() {
+    let mut state = state();
+
+    let op = Op(vec![DelWithGroup(vec![DelText(1)])], vec![]);
+    let err = state.commit("left", op, 1).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidVersion);
+    assert_eq!(state.version, 0);
+}
+
+#[test]
+fn commit_rejects_invalid_document() {
+    let mut state = state();
+    let doc = state.doc.clone();
+
+    // Unwrapping the paragraph leaves its text in the root.
+    let op = Op(vec![DelGroup(vec![])], vec![]);
+    let err = state.commit("left", op, 0).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidDocument);
+    assert_eq!(state.doc, doc);
+    assert_eq!(state.version, 0);
+}
@@ -1,8 +1,11 @@
 //! Essential crates for `oatie`. These are re-exported in oatie/src/lib.rs.
 
 pub mod apply;
+pub mod check;
 pub mod compose;
+pub mod diff;
 pub mod doc;
+pub mod invert;
 pub mod place;
 pub mod schema;
 pub mod transform;
@@ -37,7 +37,7 @@ fn apply_add_inner<M: DocMutator<S>, S: Schema>(
 
         if exhausted {
             match d {
-                AddSkip(..) | AddWithGroup(..) => {
+                AddSkip(..) | AddWithGroup(..) | AddAttrs(..) => {
                     panic!("exhausted document on {:?}", d);
                 }
                 _ => {}
@@ -118,6 +118,17 @@ fn apply_add_inner<M: DocMutator<S>, S: Schema>(
                     panic!("Invalid AddWithGroup");
                 }
             },
+            AddAttrs(ref attrs, ref addspan) => match first.clone().unwrap() {
+                DocGroup(_, ref span) => {
+                    bc.Enter();
+                    res.push(DocGroup(attrs.clone(), apply_add_outer(bc, span, addspan)));
+                    bc.Exit();
+                    bc.ReplaceAttrs(attrs.clone());
+                }
+                _ => {
+                    panic!("Invalid AddAttrs");
+                }
+            },
             AddText(styles, value) => {
                 // TODO where do you skip anything, exactly
                 // need to manifest the place issue externally as well
@@ -277,8 +288,8 @@ fn apply_del_inner<M: DocMutator<S>, S: Schema>(
                         let mut left_styles = chars_styles.clone();
                         left_styles.remove(&styles);
                         bc.delete(1);
-                        bc.InsertDocString(left.clone(), chars_styles.clone());
-                        res.place(&DocText(chars_styles.clone(), left));
+                        bc.InsertDocString(left.clone(), left_styles.clone());
+                        res.place(&DocText(left_styles, left));
                         first = DocText(chars_styles, right);
                         nextfirst = false;
                     } else {
@@ -359,6 +370,46 @@ fn apply_del_inner<M: DocMutator<S>, S: Schema>(
                     panic!("Invalid DelText");
                 }
             },
+            DelMany(count) => match first.clone() {
+                DocText(chars_styles, value) => {
+                    if value.char_len() > count {
+                        let (_, right) = value.split_at(count);
+                        first = DocText(chars_styles, right);
+                        nextfirst = false;
+                    } else if value.char_len() < count {
+                        d = DelMany(count - value.char_len());
+                        nextdel = false;
+                    } else {
+                        bc.DeleteElements(1);
+                    }
+                }
+                DocGroup(..) => {
+                    bc.DeleteElements(1);
+                    if count > 1 {
+                        d = DelMany(count - 1);
+                        nextdel = false;
+                    }
+                }
+            },
+            DelGroupAll => match first.clone() {
+                DocGroup(..) => {
+                    bc.DeleteElements(1);
+                }
+                _ => {
+                    panic!("Invalid DelGroupAll");
+                }
+            },
+            DelObject => match first.clone() {
+                DocGroup(ref attrs, _) => {
+                    if !S::track_type_from_attrs(attrs).map_or(false, |track| track.is_object()) {
+                        panic!("Invalid DelObject");
+                    }
+                    bc.DeleteElements(1);
+                }
+                _ => {
+                    panic!("Invalid DelObject");
+                }
+            },
         }
 
         if nextdel {
@@ -0,0 +1,418 @@
+//! Fallible versions of apply, compose and transform.
+//!
+//! The core methods assume their inputs fit together and panic when they
+//! don't. The methods here first compare the structure each operation
+//! expects against the document or operation it's combined with, and return
+//! an `OpError` locating the first mismatch. Mismatches the comparison can't
+//! see, like those after a `DelGroup` whose contents aren't known, are
+//! returned by the core methods' fallible versions instead of panicking.
+
+use super::apply::{
+    apply_delete,
+    apply_operation,
+};
+use super::compose;
+use super::doc::*;
+use super::transform;
+use std::error;
+use std::fmt;
+
+/// Why an operation didn't fit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpErrorKind {
+    ExpectedText,
+    ExpectedGroup,
+    ExpectedObject,
+    UnexpectedObject,
+    Exhausted,
+    /// An element with a count of zero or no text.
+    Empty,
+    /// The operations' groups can't be combined.
+    Unsupported,
+}
+
+/// An operation didn't fit the document or operation it was combined with.
+/// `path` holds the index of the offending element at each level of nesting,
+/// counting characters and groups alike. It's empty when the mismatch was
+/// found while combining operations, which doesn't track locations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpError {
+    pub kind: OpErrorKind,
+    pub path: Vec<usize>,
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            OpErrorKind::ExpectedText => "expected text but found a group",
+            OpErrorKind::ExpectedGroup => "expected a group but found text",
+            OpErrorKind::ExpectedObject => "expected an object but found another group",
+            OpErrorKind::UnexpectedObject => "expected a group but found an object",
+            OpErrorKind::Exhausted => "reached the end of the document",
+            OpErrorKind::Empty => "found an empty element",
+            OpErrorKind::Unsupported => "the operations' groups can't be combined",
+        };
+        if self.path.is_empty() {
+            write!(f, "{}", reason)
+        } else {
+            write!(f, "{} at {:?}", reason, self.path)
+        }
+    }
+}
+
+impl error::Error for OpError {}
+
+impl OpError {
+    /// A mismatch found while combining operations, at an untracked location.
+    pub(crate) fn new(kind: OpErrorKind) -> OpError {
+        OpError { kind, path: vec![] }
+    }
+}
+
+/// Fails with a mismatch found while combining operations.
+pub(crate) fn mismatch<T>(kind: OpErrorKind) -> Result<T, OpError> {
+    Err(OpError::new(kind))
+}
+
+/// The structure of a single element, as known from a document or inferred
+/// from an operation.
+#[derive(Clone, Debug)]
+enum Shape {
+    /// A character or a group.
+    Any,
+    Char,
+    /// A group, with whether it's an object and the structure of its
+    /// contents if known.
+    Group(Option<bool>, Option<Vec<Shape>>),
+    /// Any number of elements. Ends the comparison of its level.
+    Rest,
+}
+
+fn repeat(res: &mut Vec<Shape>, shape: Shape, count: usize) {
+    for _ in 0..count {
+        res.push(shape.clone());
+    }
+}
+
+fn is_object<S: Schema>(attrs: &S::GroupProperties) -> bool {
+    S::track_type_from_attrs(attrs).map_or(false, |track| track.is_object())
+}
+
+fn doc_shape<S: Schema>(span: &DocSpan<S>) -> Vec<Shape> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            DocText(_, ref text) => repeat(&mut res, Shape::Char, text.char_len()),
+            DocGroup(ref attrs, ref inner) => res.push(Shape::Group(
+                Some(is_object::<S>(attrs)),
+                Some(doc_shape(inner)),
+            )),
+        }
+    }
+    res
+}
+
+/// What a deletion expects of the document it's applied to.
+fn del_input<S: Schema>(span: &DelSpan<S>) -> Vec<Shape> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            DelSkip(count) | DelMany(count) => repeat(&mut res, Shape::Any, count),
+            DelText(count) | DelStyles(count, _) => repeat(&mut res, Shape::Char, count),
+            DelWithGroup(ref inner) | DelGroup(ref inner) => {
+                res.push(Shape::Group(None, Some(del_input(inner))));
+            }
+            DelGroupAll => res.push(Shape::Group(None, None)),
+            DelObject => res.push(Shape::Group(Some(true), None)),
+        }
+    }
+    res.push(Shape::Rest);
+    res
+}
+
+/// What a deletion leaves behind.
+fn del_output<S: Schema>(span: &DelSpan<S>) -> Vec<Shape> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            DelSkip(count) => repeat(&mut res, Shape::Any, count),
+            DelStyles(count, _) => repeat(&mut res, Shape::Char, count),
+            DelWithGroup(ref inner) => res.push(Shape::Group(None, Some(del_output(inner)))),
+            DelGroup(ref inner) => res.extend(del_output(inner)),
+            DelText(..) | DelMany(..) | DelGroupAll | DelObject => {}
+        }
+    }
+    res.push(Shape::Rest);
+    res
+}
+
+/// What an addition expects of the document it's applied to. The contents
+/// of an `AddGroup` only cover what they wrap, so they aren't `open`.
+fn add_input<S: Schema>(span: &AddSpan<S>, open: bool) -> Vec<Shape> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            AddSkip(count) => repeat(&mut res, Shape::Any, count),
+            AddStyles(count, _) => repeat(&mut res, Shape::Char, count),
+            AddText(..) => {}
+            AddWithGroup(ref inner) | AddAttrs(_, ref inner) => {
+                res.push(Shape::Group(None, Some(add_input(inner, true))));
+            }
+            AddGroup(_, ref inner) => res.extend(add_input(inner, false)),
+        }
+    }
+    if open {
+        res.push(Shape::Rest);
+    }
+    res
+}
+
+/// What an addition produces.
+fn add_output<S: Schema>(span: &AddSpan<S>, open: bool) -> Vec<Shape> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            AddSkip(count) => repeat(&mut res, Shape::Any, count),
+            AddStyles(count, _) => repeat(&mut res, Shape::Char, count),
+            AddText(_, ref text) => repeat(&mut res, Shape::Char, text.char_len()),
+            AddWithGroup(ref inner) => {
+                res.push(Shape::Group(None, Some(add_output(inner, true))));
+            }
+            AddAttrs(ref attrs, ref inner) => res.push(Shape::Group(
+                Some(is_object::<S>(attrs)),
+                Some(add_output(inner, true)),
+            )),
+            AddGroup(ref attrs, ref inner) => res.push(Shape::Group(
+                Some(is_object::<S>(attrs)),
+                Some(add_output(inner, false)),
+            )),
+        }
+    }
+    if open {
+        res.push(Shape::Rest);
+    }
+    res
+}
+
+/// Checks that the elements `expected` describes are present in `found`.
+fn check_shape(expected: &[Shape], found: &[Shape], path: &mut Vec<usize>) -> Result<(), OpError> {
+    for (i, shape) in expected.iter().enumerate() {
+        let error = |kind, path: &Vec<usize>| {
+            let mut path = path.clone();
+            path.push(i);
+            Err(OpError { kind, path })
+        };
+
+        let other = match found.get(i) {
+            Some(other) => other,
+            None => match shape {
+                Shape::Rest => return Ok(()),
+                _ => return error(OpErrorKind::Exhausted, path),
+            },
+        };
+
+        match (shape, other) {
+            (Shape::Rest, _) | (_, Shape::Rest) => return Ok(()),
+            (Shape::Char, Shape::Group(..)) => return error(OpErrorKind::ExpectedText, path),
+            (Shape::Group(..), Shape::Char) => return error(OpErrorKind::ExpectedGroup, path),
+            (Shape::Group(object, inner), Shape::Group(other_object, other_inner)) => {
+                match (object, other_object) {
+                    (Some(true), Some(false)) => return error(OpErrorKind::ExpectedObject, path),
+                    (Some(false), Some(true)) => return error(OpErrorKind::UnexpectedObject, path),
+                    _ => {}
+                }
+                if let (Some(inner), Some(other_inner)) = (inner, other_inner) {
+                    path.push(i);
+                    check_shape(inner, other_inner, path)?;
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no element of a deletion is empty. Indices count what the
+/// deletion is applied to.
+fn check_del_lengths<S: Schema>(span: &DelSpan<S>, path: &mut Vec<usize>) -> Result<(), OpError> {
+    let mut i = 0;
+    for elem in span {
+        match *elem {
+            DelSkip(count) | DelText(count) | DelStyles(count, _) | DelMany(count) => {
+                if count == 0 {
+                    let mut path = path.clone();
+                    path.push(i);
+                    return Err(OpError {
+                        kind: OpErrorKind::Empty,
+                        path,
+                    });
+                }
+                i += count;
+            }
+            DelWithGroup(ref inner) | DelGroup(ref inner) => {
+                path.push(i);
+                check_del_lengths(inner, path)?;
+                path.pop();
+                i += 1;
+            }
+            DelGroupAll | DelObject => i += 1,
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no element of an addition is empty. Indices count what the
+/// addition produces.
+fn check_add_lengths<S: Schema>(span: &AddSpan<S>, path: &mut Vec<usize>) -> Result<(), OpError> {
+    let mut i = 0;
+    for elem in span {
+        let count = match *elem {
+            AddSkip(count) | AddStyles(count, _) => count,
+            AddText(_, ref text) => text.char_len(),
+            AddWithGroup(ref inner) | AddAttrs(_, ref inner) | AddGroup(_, ref inner) => {
+                path.push(i);
+                check_add_lengths(inner, path)?;
+                path.pop();
+                1
+            }
+        };
+        if count == 0 {
+            let mut path = path.clone();
+            path.push(i);
+            return Err(OpError {
+                kind: OpErrorKind::Empty,
+                path,
+            });
+        }
+        i += count;
+    }
+    Ok(())
+}
+
+/// Checks that an operation has no empty elements.
+fn check_lengths<S: Schema>(op: &Op<S>) -> Result<(), OpError> {
+    let &Op(ref del, ref add) = op;
+    check_del_lengths(del, &mut vec![])?;
+    check_add_lengths(add, &mut vec![])
+}
+
+/// Checks that an operation's addition fits what its deletion leaves behind.
+fn check_op<S: Schema>(op: &Op<S>) -> Result<(), OpError> {
+    check_lengths(op)?;
+    let &Op(ref del, ref add) = op;
+    check_shape(&add_input(add, true), &del_output(del), &mut vec![])
+}
+
+/// Takes the next element an addition expects.
+fn take_shape(add: &[Shape], pos: &mut usize) -> Shape {
+    match add.get(*pos) {
+        None | Some(Shape::Rest) => Shape::Any,
+        Some(shape) => {
+            *pos += 1;
+            shape.clone()
+        }
+    }
+}
+
+/// Maps a deletion back onto the document it's applied to, refining each
+/// element it keeps with what the addition expects of it. Returns false if
+/// it stopped at a `DelGroup`, after which the two no longer line up.
+fn op_input_span<S: Schema>(
+    del: &DelSpan<S>,
+    add: &[Shape],
+    pos: &mut usize,
+    res: &mut Vec<Shape>,
+) -> bool {
+    for elem in del {
+        match *elem {
+            DelSkip(count) => {
+                for _ in 0..count {
+                    let shape = take_shape(add, pos);
+                    res.push(shape);
+                }
+            }
+            DelStyles(count, _) => {
+                for _ in 0..count {
+                    take_shape(add, pos);
+                    res.push(Shape::Char);
+                }
+            }
+            DelText(count) => repeat(res, Shape::Char, count),
+            DelMany(count) => repeat(res, Shape::Any, count),
+            DelGroupAll => res.push(Shape::Group(None, None)),
+            DelObject => res.push(Shape::Group(Some(true), None)),
+            DelWithGroup(ref inner) => {
+                let (object, inner_add) = match take_shape(add, pos) {
+                    Shape::Group(object, Some(inner_add)) => (object, inner_add),
+                    _ => (None, vec![Shape::Rest]),
+                };
+                let mut inner_pos = 0;
+                let mut inner_res = vec![];
+                if op_input_span(inner, &inner_add, &mut inner_pos, &mut inner_res) {
+                    inner_res.extend(inner_add[inner_pos..].iter().cloned());
+                } else {
+                    inner_res.push(Shape::Rest);
+                }
+                res.push(Shape::Group(object, Some(inner_res)));
+            }
+            DelGroup(ref inner) => {
+                // The contents of the group take its place, but we don't
+                // know how many elements it held past the end of `inner`.
+                let mut inner_res = vec![];
+                op_input_span(inner, add, pos, &mut inner_res);
+                inner_res.push(Shape::Rest);
+                res.push(Shape::Group(None, Some(inner_res)));
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// What an operation expects of the document it's applied to.
+fn op_input<S: Schema>(op: &Op<S>) -> Vec<Shape> {
+    let &Op(ref del, ref add) = op;
+    let add = add_input(add, true);
+    let mut pos = 0;
+    let mut res = vec![];
+    if op_input_span(del, &add, &mut pos, &mut res) {
+        res.extend(add[pos..].iter().cloned());
+    } else {
+        res.push(Shape::Rest);
+    }
+    res
+}
+
+/// Applies `op` to `doc`, or returns where `op` doesn't fit the document.
+pub fn try_apply<S: Schema>(doc: &DocSpan<S>, op: &Op<S>) -> Result<DocSpan<S>, OpError> {
+    let &Op(ref del, ref add) = op;
+
+    check_lengths(op)?;
+    check_shape(&del_input(del), &doc_shape(doc), &mut vec![])?;
+    let deleted = apply_delete(doc, del);
+    check_shape(&add_input(add, true), &doc_shape(&deleted), &mut vec![])?;
+
+    Ok(apply_operation(doc, op))
+}
+
+/// Composes `a` and `b`, or returns where `b` doesn't fit the output of `a`.
+pub fn try_compose<S: Schema>(a: &Op<S>, b: &Op<S>) -> Result<Op<S>, OpError> {
+    check_op(a)?;
+    check_op(b)?;
+    check_shape(&del_input(&b.0), &add_output(&a.1, true), &mut vec![])?;
+    check_shape(&op_input(b), &add_output(&a.1, true), &mut vec![])?;
+
+    compose::try_compose(a, b)
+}
+
+/// Transforms `a` and `b`, or returns where they disagree about the
+/// document they both apply to.
+pub fn try_transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> Result<(Op<S>, Op<S>), OpError> {
+    check_op(a)?;
+    check_op(b)?;
+    check_shape(&del_input(&a.0), &del_input(&b.0), &mut vec![])?;
+    check_shape(&op_input(a), &op_input(b), &mut vec![])?;
+
+    transform::try_transform(a, b)
+}
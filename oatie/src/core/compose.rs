@@ -1,5 +1,10 @@
 //! Composes two operations together.
 
+use super::check::{
+    mismatch,
+    OpError,
+    OpErrorKind,
+};
 use super::doc::*;
 use crate::stepper::*;
 use std::cmp;
@@ -8,7 +13,7 @@ fn compose_del_del_inner<S: Schema>(
     res: &mut DelSpan<S>,
     a: &mut DelStepper<S>,
     b: &mut DelStepper<S>,
-) {
+) -> Result<(), OpError> {
     while !a.is_done() && !b.is_done() {
         match a.get_head() {
             DelSkip(acount) => {
@@ -49,7 +54,7 @@ fn compose_del_del_inner<S: Schema>(
                         }
                     }
                     Some(DelStyles(b_count, b_styles)) => {
-                        res.place(&DelText(cmp::min(acount, b_count)));
+                        res.place(&DelStyles(cmp::min(acount, b_count), b_styles.clone()));
                         if acount > b_count {
                             a.head = Some(DelSkip(acount - b_count));
                             b.next();
@@ -61,29 +66,30 @@ fn compose_del_del_inner<S: Schema>(
                             b.next();
                         }
                     }
+                    Some(DelMany(bcount)) => {
+                        res.place(&DelMany(cmp::min(acount, bcount)));
+                        if acount > bcount {
+                            a.head = Some(DelSkip(acount - bcount));
+                            b.next();
+                        } else if acount < bcount {
+                            b.head = Some(DelMany(bcount - acount));
+                            a.next();
+                        } else {
+                            a.next();
+                            b.next();
+                        }
+                    }
+                    Some(DelGroupAll) | Some(DelObject) => {
+                        if acount > 1 {
+                            a.head = Some(DelSkip(acount - 1));
+                        } else {
+                            a.next();
+                        }
+                        res.place(&b.next().unwrap());
+                    }
                     None => {
                         res.place(&a.next().unwrap());
-                    } // Some(DelMany(bcount)) => {
-                      //     res.place(&DelMany(cmp::min(acount, bcount)));
-                      //     if acount > bcount {
-                      //         a.head = Some(DelSkip(acount - bcount));
-                      //         b.next();
-                      //     } else if acount < bcount {
-                      //         b.head = Some(DelMany(bcount - acount));
-                      //         a.next();
-                      //     } else {
-                      //         a.next();
-                      //         b.next();
-                      //     }
-                      // }
-                      // Some(DelGroupAll) => {
-                      //     if acount > 1 {
-                      //         a.head = Some(DelSkip(acount - 1));
-                      //     } else {
-                      //         a.next();
-                      //     }
-                      //     res.place(&b.next().unwrap());
-                      // }
+                    }
                 }
             }
             DelStyles(a_count, a_styles) => match b.head.clone() {
@@ -92,11 +98,11 @@ fn compose_del_del_inner<S: Schema>(
                     both_styles.extend(&a_styles);
                     res.push(DelStyles(cmp::min(a_count, b_count), both_styles));
                     if a_count > b_count {
-                        b.head = Some(DelStyles(a_count - b_count, a_styles));
+                        a.head = Some(DelStyles(a_count - b_count, a_styles));
                         b.next();
                     } else if a_count < b_count {
-                        a.head = Some(DelStyles(b_count - a_count, b_styles));
                         a.next();
+                        b.head = Some(DelStyles(b_count - a_count, b_styles));
                     } else {
                         a.next();
                         b.next();
@@ -105,18 +111,21 @@ fn compose_del_del_inner<S: Schema>(
                 Some(DelSkip(b_count)) => {
                     res.push(DelStyles(cmp::min(a_count, b_count), a_styles.clone()));
                     if a_count > b_count {
-                        b.head = Some(DelSkip(a_count - b_count));
+                        a.head = Some(DelStyles(a_count - b_count, a_styles));
                         b.next();
                     } else if a_count < b_count {
-                        a.head = Some(DelStyles(b_count - a_count, a_styles));
                         a.next();
+                        b.head = Some(DelSkip(b_count - a_count));
                     } else {
                         a.next();
                         b.next();
                     }
                 }
-                Some(DelWithGroup(..)) | Some(DelGroup(..)) => {
-                    unreachable!();
+                Some(DelWithGroup(..))
+                | Some(DelGroup(..))
+                | Some(DelGroupAll)
+                | Some(DelObject) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
                 }
                 Some(DelText(b_count)) => {
                     res.place(&DelText(cmp::min(a_count, b_count)));
@@ -131,6 +140,19 @@ fn compose_del_del_inner<S: Schema>(
                         b.next();
                     }
                 }
+                Some(DelMany(b_count)) => {
+                    res.place(&DelText(cmp::min(a_count, b_count)));
+                    if a_count > b_count {
+                        a.head = Some(DelStyles(a_count - b_count, a_styles));
+                        b.next();
+                    } else if a_count < b_count {
+                        b.head = Some(DelMany(b_count - a_count));
+                        a.next();
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
                 None => {
                     res.place(&a.next().unwrap());
                 }
@@ -146,45 +168,43 @@ fn compose_del_del_inner<S: Schema>(
                         res.place(&a.next().unwrap());
                     }
                     Some(DelStyles(..)) => {
-                        panic!("DelWithGroup vs DelStyles is bad");
+                        return mismatch(OpErrorKind::ExpectedText);
                     }
                     Some(DelWithGroup(ref bspan)) => {
-                        res.place(&DelWithGroup(compose_del_del(span, bspan)));
+                        res.place(&DelWithGroup(try_compose_del_del(span, bspan)?));
                         a.next();
                         b.next();
                     }
                     Some(DelGroup(ref bspan)) => {
-                        res.place(&DelGroup(compose_del_del(span, bspan)));
+                        res.place(&DelGroup(try_compose_del_del(span, bspan)?));
                         a.next();
                         b.next();
                     }
                     Some(DelText(..)) => {
-                        panic!("DelWithGroup vs DelText is bad");
+                        return mismatch(OpErrorKind::ExpectedText);
+                    }
+                    Some(DelMany(bcount)) => {
+                        if bcount > 1 {
+                            b.head = Some(DelMany(bcount - 1));
+                        } else {
+                            b.next();
+                        }
+                        a.next();
+                        res.place(&DelMany(1));
+                    }
+                    Some(DelGroupAll) | Some(DelObject) => {
+                        a.next();
+                        res.place(&b.next().unwrap());
                     }
                     None => {
                         res.place(&a.next().unwrap());
-                    } // Some(DelMany(bcount)) => {
-                      //     if bcount > 1 {
-                      //         b.head = Some(DelMany(bcount - 1));
-                      //     } else {
-                      //         b.next();
-                      //     }
-                      //     a.next();
-                      //     res.place(&DelMany(1));
-                      // }
-                      // Some(DelObject) => {
-                      //     panic!("DelWithGroup vs DelObject is bad");
-                      // }
-                      // Some(DelGroupAll) => {
-                      //     a.next();
-                      //     res.place(&b.next().unwrap());
-                      // }
+                    }
                 }
             }
             DelGroup(ref span) => {
                 let mut c = DelStepper::new(span);
                 let mut inner: DelSpan<S> = vec![];
-                compose_del_del_inner(&mut inner, &mut c, b);
+                compose_del_del_inner(&mut inner, &mut c, b)?;
                 if !c.is_done() {
                     inner.place(&c.head.unwrap());
                     inner.place_all(&c.rest);
@@ -195,41 +215,35 @@ fn compose_del_del_inner<S: Schema>(
             DelText(count) => {
                 res.place(&DelText(count));
                 a.next();
-            } // DelObject => {
-              //     match b.head.clone() {
-              //         Some(DelObject) => {
-              //             res.place(&DelObject);
-              //             a.next();
-              //             b.next();
-              //         }
-              //         None => {
-              //             res.place(&DelObject);
-              //             a.next();
-              //         }
-              //         _ => {
-              //             panic!("Invalid compose against DelObject");
-              //         }
-              //     }
-              // }
-              // DelMany(count) => {
-              //     res.place(&DelMany(count));
-              //     a.next();
-              // }
-              // DelGroupAll => {
-              //     res.place(&DelGroupAll);
-              //     a.next();
-              // }
+            }
+            DelMany(count) => {
+                res.place(&DelMany(count));
+                a.next();
+            }
+            DelGroupAll => {
+                res.place(&DelGroupAll);
+                a.next();
+            }
+            DelObject => {
+                res.place(&DelObject);
+                a.next();
+            }
         }
     }
+    Ok(())
 }
 
 pub fn compose_del_del<S: Schema>(avec: &DelSpan<S>, bvec: &DelSpan<S>) -> DelSpan<S> {
+    try_compose_del_del(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub(crate) fn try_compose_del_del<S: Schema>(avec: &DelSpan<S>, bvec: &DelSpan<S>) -> Result<DelSpan<S>, OpError> {
     let mut res = Vec::with_capacity(avec.len() + bvec.len());
 
     let mut a = DelStepper::new(avec);
     let mut b = DelStepper::new(bvec);
 
-    compose_del_del_inner(&mut res, &mut a, &mut b);
+    compose_del_del_inner(&mut res, &mut a, &mut b)?;
 
     if !a.is_done() {
         res.place(&a.get_head());
@@ -241,14 +255,14 @@ pub fn compose_del_del<S: Schema>(avec: &DelSpan<S>, bvec: &DelSpan<S>) -> DelSp
         res.place_all(&b.rest);
     }
 
-    res
+    Ok(res)
 }
 
 fn compose_add_add_inner<S: Schema>(
     res: &mut AddSpan<S>,
     a: &mut AddStepper<S>,
     b: &mut AddStepper<S>,
-) {
+) -> Result<(), OpError> {
     while !b.is_done() && !a.is_done() {
         match b.get_head() {
             AddText(..) => {
@@ -256,15 +270,15 @@ fn compose_add_add_inner<S: Schema>(
             }
             AddStyles(b_count, b_styles) => match a.get_head() {
                 AddStyles(a_count, a_styles) => {
-                    let mut both_styles = b_styles.clone();
-                    both_styles.extend(&a_styles);
+                    let mut both_styles = a_styles.clone();
+                    both_styles.extend(&b_styles);
                     res.push(AddStyles(cmp::min(a_count, b_count), both_styles));
                     if a_count > b_count {
-                        b.head = Some(AddStyles(a_count - b_count, a_styles));
+                        a.head = Some(AddStyles(a_count - b_count, a_styles));
                         b.next();
                     } else if a_count < b_count {
-                        a.head = Some(AddStyles(b_count - a_count, b_styles));
                         a.next();
+                        b.head = Some(AddStyles(b_count - a_count, b_styles));
                     } else {
                         a.next();
                         b.next();
@@ -293,17 +307,17 @@ fn compose_add_add_inner<S: Schema>(
                 AddSkip(acount) => {
                     res.push(AddStyles(cmp::min(acount, b_count), b_styles.clone()));
                     if acount > b_count {
-                        b.head = Some(AddSkip(acount - b_count));
+                        a.head = Some(AddSkip(acount - b_count));
                         b.next();
                     } else if acount < b_count {
-                        a.head = Some(AddStyles(b_count - acount, b_styles));
+                        b.head = Some(AddStyles(b_count - acount, b_styles));
                         a.next();
                     } else {
                         a.next();
                         b.next();
                     }
                 }
-                AddWithGroup(..) => {
+                AddWithGroup(..) | AddAttrs(..) => {
                     res.push(a.next().unwrap());
                     if b_count == 1 {
                         b.next();
@@ -362,7 +376,7 @@ fn compose_add_add_inner<S: Schema>(
                         b.next();
                     }
                 }
-                AddWithGroup(_span) => {
+                AddWithGroup(..) | AddAttrs(..) => {
                     res.push(a.next().unwrap());
                     if bcount == 1 {
                         b.next();
@@ -382,7 +396,7 @@ fn compose_add_add_inner<S: Schema>(
             AddGroup(attrs, bspan) => {
                 let mut c = AddStepper::new(&bspan);
                 let mut inner = vec![];
-                compose_add_add_inner(&mut inner, a, &mut c);
+                compose_add_add_inner(&mut inner, a, &mut c)?;
                 if !c.is_done() {
                     inner.place(&c.get_head());
                     inner.place_all(&c.rest);
@@ -391,11 +405,8 @@ fn compose_add_add_inner<S: Schema>(
                 b.next();
             }
             AddWithGroup(ref bspan) => match a.get_head() {
-                AddText(..) => {
-                    panic!("Cannot compose AddWithGroup with AddText");
-                }
-                AddStyles(..) => {
-                    panic!("Cannot compose AddWithGroup with AddStyles");
+                AddText(..) | AddStyles(..) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
                 }
                 AddSkip(acount) => {
                     if acount == 1 {
@@ -406,27 +417,61 @@ fn compose_add_add_inner<S: Schema>(
                     res.push(b.next().unwrap());
                 }
                 AddWithGroup(ref aspan) => {
-                    res.push(AddWithGroup(compose_add_add(aspan, bspan)));
+                    res.push(AddWithGroup(try_compose_add_add(aspan, bspan)?));
+                    a.next();
+                    b.next();
+                }
+                AddAttrs(ref attrs, ref aspan) => {
+                    res.push(AddAttrs(attrs.clone(), try_compose_add_add(aspan, bspan)?));
                     a.next();
                     b.next();
                 }
                 AddGroup(ref attrs, ref aspan) => {
-                    res.push(AddGroup(attrs.clone(), compose_add_add(aspan, bspan)));
+                    res.push(AddGroup(attrs.clone(), try_compose_add_add(aspan, bspan)?));
+                    a.next();
+                    b.next();
+                }
+            },
+            AddAttrs(ref attrs, ref bspan) => match a.get_head() {
+                AddText(..) | AddStyles(..) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
+                }
+                AddSkip(acount) => {
+                    if acount == 1 {
+                        a.next();
+                    } else {
+                        a.head = Some(AddSkip(acount - 1));
+                    }
+                    res.push(b.next().unwrap());
+                }
+                AddWithGroup(ref aspan) | AddAttrs(_, ref aspan) => {
+                    res.push(AddAttrs(attrs.clone(), try_compose_add_add(aspan, bspan)?));
+                    a.next();
+                    b.next();
+                }
+                AddGroup(_, ref aspan) => {
+                    // The group is new, so create it with the latest attributes.
+                    res.push(AddGroup(attrs.clone(), try_compose_add_add(aspan, bspan)?));
                     a.next();
                     b.next();
                 }
             },
         }
     }
+    Ok(())
 }
 
 pub fn compose_add_add<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) -> AddSpan<S> {
+    try_compose_add_add(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn try_compose_add_add<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) -> Result<AddSpan<S>, OpError> {
     let mut res = Vec::with_capacity(avec.len() + bvec.len());
 
     let mut a = AddStepper::new(avec);
     let mut b = AddStepper::new(bvec);
 
-    compose_add_add_inner(&mut res, &mut a, &mut b);
+    compose_add_add_inner(&mut res, &mut a, &mut b)?;
 
     if !b.is_done() {
         res.place(&b.get_head());
@@ -438,17 +483,21 @@ pub fn compose_add_add<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) -> AddSp
         res.place_all(&a.rest);
     }
 
-    res
+    Ok(res)
 }
 
 pub fn compose_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Op<S> {
+    try_compose_add_del(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn try_compose_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Result<Op<S>, OpError> {
     let mut delres: DelSpan<S> = Vec::with_capacity(avec.len() + bvec.len());
     let mut addres: AddSpan<S> = Vec::with_capacity(avec.len() + bvec.len());
 
     let mut a = AddStepper::new(avec);
     let mut b = DelStepper::new(bvec);
 
-    compose_add_del_inner(&mut delres, &mut addres, &mut a, &mut b);
+    compose_add_del_inner(&mut delres, &mut addres, &mut a, &mut b)?;
 
     if !b.is_done() {
         let rest = b.into_span();
@@ -466,7 +515,7 @@ pub fn compose_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Op<S>
         addres.place_all(&rest);
     }
 
-    Op(delres, addres)
+    Ok(Op(delres, addres))
 }
 
 fn compose_add_del_inner<S: Schema>(
@@ -474,7 +523,7 @@ fn compose_add_del_inner<S: Schema>(
     addres: &mut AddSpan<S>,
     a: &mut AddStepper<S>,
     b: &mut DelStepper<S>,
-) {
+) -> Result<(), OpError> {
     while !b.is_done() && !a.is_done() {
         match b.get_head() {
             DelText(bcount) => match a.get_head() {
@@ -504,8 +553,22 @@ fn compose_add_del_inner<S: Schema>(
                         delres.place(&b.next().unwrap());
                     }
                 }
-                _ => {
-                    panic!("Unimplemented or Unexpected");
+                AddStyles(acount, a_styles) => {
+                    // The restyled text is deleted, styles and all.
+                    delres.place(&DelText(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddStyles(acount - bcount, a_styles));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelText(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddWithGroup(..) | AddAttrs(..) | AddGroup(..) => {
+                    return mismatch(OpErrorKind::ExpectedText);
                 }
             },
             DelStyles(b_count, b_styles) => match a.get_head() {
@@ -519,8 +582,9 @@ fn compose_add_del_inner<S: Schema>(
                         b.next();
                     } else if b_count > a_value.char_len() {
                         a_styles.remove(&b_styles);
-                        b.head = Some(DelSkip(b_count - a_value.char_len()));
+                        b.head = Some(DelStyles(b_count - a_value.char_len(), b_styles));
                         addres.place(&AddText(a_styles, a_value));
+                        a.next();
                     } else {
                         a_styles.remove(&b_styles);
                         addres.place(&AddText(a_styles, a_value));
@@ -529,25 +593,28 @@ fn compose_add_del_inner<S: Schema>(
                     }
                 }
                 AddStyles(a_count, a_styles) => {
-                    // a_styles - b_styles
+                    // Styles added by a and removed by b cancel out, while b
+                    // still removes its styles from the underlying text.
                     let mut combined_styles = a_styles.clone();
                     combined_styles.remove(&b_styles);
 
-                    // res.push(AddStyles(cmp::min(a_count, b_count), both_styles));
+                    let count = cmp::min(a_count, b_count);
+                    if combined_styles.is_empty() {
+                        addres.place(&AddSkip(count));
+                    } else {
+                        addres.place(&AddStyles(count, combined_styles));
+                    }
+                    delres.place(&DelStyles(count, b_styles.clone()));
                     if a_count > b_count {
                         a.head = Some(AddStyles(a_count - b_count, a_styles));
-                        a.next();
-                        addres.place(&AddStyles(b_count, combined_styles));
+                        b.next();
                     } else if a_count < b_count {
-                        b.head = Some(DelStyles(b_count - a_count, b_styles));
                         a.next();
-                        addres.place(&AddStyles(b_count, combined_styles));
+                        b.head = Some(DelStyles(b_count - a_count, b_styles));
                     } else {
                         a.next();
                         b.next();
-                        addres.place(&AddStyles(a_count, combined_styles));
                     }
-                    delres.place(&b.next().unwrap());
                 }
                 AddSkip(a_count) => {
                     addres.place(&AddSkip(cmp::min(a_count, b_count)));
@@ -563,11 +630,8 @@ fn compose_add_del_inner<S: Schema>(
                         b.next();
                     }
                 }
-                AddWithGroup(..) => {
-                    panic!("DelStyles by AddWithGroup is ILLEGAL");
-                }
-                AddGroup(..) => {
-                    panic!("DelStyles by AddGroup is ILLEGAL");
+                AddWithGroup(..) | AddGroup(..) | AddAttrs(..) => {
+                    return mismatch(OpErrorKind::ExpectedText);
                 }
             },
             DelSkip(bcount) => match a.get_head() {
@@ -614,7 +678,7 @@ fn compose_add_del_inner<S: Schema>(
                         b.next();
                     }
                 }
-                AddWithGroup(..) => {
+                AddWithGroup(..) | AddAttrs(..) => {
                     addres.place(&a.next().unwrap());
                     delres.place(&DelSkip(1));
                     if bcount == 1 {
@@ -636,11 +700,8 @@ fn compose_add_del_inner<S: Schema>(
                 }
             },
             DelWithGroup(span) => match a.get_head() {
-                AddText(..) => {
-                    panic!("DelWithGroup by AddText is ILLEGAL");
-                }
-                AddStyles(..) => {
-                    panic!("DelWithGroup by AddStyles is ILLEGAL");
+                AddText(..) | AddStyles(..) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
                 }
                 AddSkip(acount) => {
                     delres.place(&b.next().unwrap());
@@ -655,26 +716,31 @@ fn compose_add_del_inner<S: Schema>(
                     a.next();
                     b.next();
 
-                    let Op(del, ins) = compose_add_del(&insspan, &span);
+                    let Op(del, ins) = try_compose_add_del(&insspan, &span)?;
                     delres.place(&DelWithGroup(del));
                     addres.place(&AddWithGroup(ins));
                 }
+                AddAttrs(attrs, insspan) => {
+                    a.next();
+                    b.next();
+
+                    let Op(del, ins) = try_compose_add_del(&insspan, &span)?;
+                    delres.place(&DelWithGroup(del));
+                    addres.place(&AddAttrs(attrs, ins));
+                }
                 AddGroup(attr, insspan) => {
                     a.next();
                     b.next();
 
-                    let Op(del, ins) = compose_add_del(&insspan, &span);
+                    let Op(del, ins) = try_compose_add_del(&insspan, &span)?;
                     addres.place(&AddGroup(attr, ins));
                     delres.place_all(&del);
                 }
             },
             DelGroup(span) => {
                 match a.get_head() {
-                    AddText(..) => {
-                        panic!("DelGroup by AddText is ILLEGAL");
-                    }
-                    AddStyles(..) => {
-                        panic!("DelGroup by AddStyles is ILLEGAL");
+                    AddText(..) | AddStyles(..) => {
+                        return mismatch(OpErrorKind::ExpectedGroup);
                     }
                     AddSkip(acount) => {
                         delres.place(&b.next().unwrap());
@@ -687,11 +753,11 @@ fn compose_add_del_inner<S: Schema>(
                             a.next();
                         }
                     }
-                    AddWithGroup(insspan) => {
+                    AddWithGroup(insspan) | AddAttrs(_, insspan) => {
                         a.next();
                         b.next();
 
-                        let Op(del, ins) = compose_add_del(&insspan, &span);
+                        let Op(del, ins) = try_compose_add_del(&insspan, &span)?;
                         delres.place(&DelGroup(del));
                         addres.place_all(&ins[..]);
 
@@ -723,126 +789,131 @@ fn compose_add_del_inner<S: Schema>(
                         a.next();
                         b.next();
 
-                        let Op(del, ins) = compose_add_del(&insspan, &span);
+                        let Op(del, ins) = try_compose_add_del(&insspan, &span)?;
                         delres.place_all(&del[..]);
                         addres.place_all(&ins[..]);
                     }
                 }
-            } // DelObject => {
-              //     match a.get_head() {
-              //         AddSkip(acount) => {
-              //             if acount > 1 {
-              //                 a.head = Some(AddSkip(acount - 1));
-              //                 delres.place(&b.next().unwrap());
-              //             } else {
-              //                 a.next();
-              //                 delres.place(&b.next().unwrap());
-              //             }
-              //         }
-              //         _ => {
-              //             panic!("Bad");
-              //         }
-              //     }
-              // }
-              // DelMany(bcount) => {
-              //     match a.get_head() {
-              //         AddText(avalue) => {
-              //             let alen = avalue.chars().count();
-              //             if bcount < alen {
-              //                 a.head = Some(AddText(avalue.chars().skip(bcount).collect()));
-              //                 b.next();
-              //             } else if bcount > alen {
-              //                 a.next();
-              //                 b.head = Some(DelMany(bcount - alen));
-              //             } else {
-              //                 a.next();
-              //                 b.next();
-              //             }
-              //         }
-              //         AddSkip(acount) => {
-              //             if bcount < acount {
-              //                 a.head = Some(AddSkip(acount - bcount));
-              //                 delres.place(&b.next().unwrap());
-              //             } else if bcount > acount {
-              //                 a.next();
-              //                 delres.place(&DelMany(acount));
-              //                 b.head = Some(DelMany(bcount - acount));
-              //             } else {
-              //                 a.next();
-              //                 b.next();
-              //             }
-              //         }
-              //         AddGroup(attr, ins_span) => {
-              //             if bcount > 1 {
-              //                 a.next();
-              //                 delres.place(&DelMany(ins_span.skip_len()));
-              //                 b.head = Some(DelMany(bcount - 1));
-              //             } else {
-              //                 a.next();
-              //                 b.next();
-              //             }
-              //         }
-              //         AddWithGroup(insspan) => {
-              //             if bcount > 1 {
-              //                 a.next();
-              //                 b.head = Some(DelMany(bcount - 1));
-              //             } else {
-              //                 a.next();
-              //                 b.next();
-              //             }
-              //             delres.place(&DelMany(1));
-              //         }
-              //     }
-              // }
-              // DelGroupAll => {
-              //     match a.get_head() {
-              //         AddText(avalue) => {
-              //             panic!("DelGroupAll by AddText is ILLEGAL");
-              //         }
-              //         AddSkip(acount) => {
-              //             delres.place(&b.next().unwrap());
-              //             if acount > 1 {
-              //                 a.head = Some(AddSkip(acount - 1));
-              //             } else {
-              //                 a.next();
-              //             }
-              //         }
-              //         AddWithGroup(insspan) => {
-              //             a.next();
-              //             delres.place(&b.next().unwrap());
-              //         }
-              //         AddGroup(attr, insspan) => {
-              //             a.next();
-              //             b.next();
-              //         }
-              //     }
-              // }
+            }
+            DelMany(bcount) => match a.get_head() {
+                AddText(a_styles, avalue) => {
+                    if bcount < avalue.char_len() {
+                        let (_a_left, a_right) = avalue.split_at(bcount);
+                        a.head = Some(AddText(a_styles, a_right));
+                        b.next();
+                    } else if bcount > avalue.char_len() {
+                        a.next();
+                        b.head = Some(DelMany(bcount - avalue.char_len()));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddStyles(acount, a_styles) => {
+                    delres.place(&DelMany(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddStyles(acount - bcount, a_styles));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelMany(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddSkip(acount) => {
+                    delres.place(&DelMany(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddSkip(acount - bcount));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelMany(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddGroup(_, ins_span) => {
+                    // Delete everything the new group wrapped.
+                    if ins_span.skip_pre_len() > 0 {
+                        delres.place(&DelMany(ins_span.skip_pre_len()));
+                    }
+                    a.next();
+                    if bcount > 1 {
+                        b.head = Some(DelMany(bcount - 1));
+                    } else {
+                        b.next();
+                    }
+                }
+                AddWithGroup(..) | AddAttrs(..) => {
+                    delres.place(&DelMany(1));
+                    a.next();
+                    if bcount > 1 {
+                        b.head = Some(DelMany(bcount - 1));
+                    } else {
+                        b.next();
+                    }
+                }
+            },
+            DelGroupAll | DelObject => match a.get_head() {
+                AddText(..) | AddStyles(..) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
+                }
+                AddSkip(acount) => {
+                    delres.place(&b.next().unwrap());
+                    if acount > 1 {
+                        a.head = Some(AddSkip(acount - 1));
+                    } else {
+                        a.next();
+                    }
+                }
+                AddWithGroup(..) | AddAttrs(..) => {
+                    a.next();
+                    delres.place(&b.next().unwrap());
+                }
+                AddGroup(_, ins_span) => {
+                    // Delete everything the new group wrapped.
+                    if ins_span.skip_pre_len() > 0 {
+                        delres.place(&DelMany(ins_span.skip_pre_len()));
+                    }
+                    a.next();
+                    b.next();
+                }
+            },
         }
     }
+    Ok(())
 }
 
 pub fn compose<S: Schema>(a: &Op<S>, b: &Op<S>) -> Op<S> {
+    try_compose(a, b).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Composes `a` and `b`, or returns how they didn't fit together.
+pub(crate) fn try_compose<S: Schema>(a: &Op<S>, b: &Op<S>) -> Result<Op<S>, OpError> {
     let &Op(ref adel, ref ains) = a;
     let &Op(ref bdel, ref bins) = b;
 
     log_compose!("`````````````` >(compose)<");
     log_compose!("``````````````a_ins {:?}", ains);
     log_compose!("``````````````b_del {:?}", bdel);
-    let Op(mdel, mins) = compose_add_del(ains, bdel);
+    let Op(mdel, mins) = try_compose_add_del(ains, bdel)?;
     log_compose!("``````````````  a=> {:?}", mdel);
     log_compose!("``````````````  b=> {:?}", mins);
 
     log_compose!("``````````````a_del {:?}", adel);
     log_compose!("``````````````  a=>  {:?}", mdel);
-    let a_ = compose_del_del(adel, &mdel);
+    let a_ = try_compose_del_del(adel, &mdel)?;
     log_compose!("``````````````  del' {:?}", a_);
 
     log_compose!("``````````````  b=> {:?}", mins);
     log_compose!("`````````````b_ins {:?}", bins);
-    let b_ = compose_add_add(&mins, bins);
+    let b_ = try_compose_add_add(&mins, bins)?;
     log_compose!("`````````````` ins' {:?}", b_);
     log_compose!();
     log_compose!();
 
-    Op(a_, b_)
+    Ok(Op(a_, b_))
 }
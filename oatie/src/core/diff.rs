@@ -0,0 +1,171 @@
+//! Computes an operation that turns one document into another.
+//!
+//! The diff is structural: groups that line up between the two documents
+//! (same track type, in the same position relative to their unchanged
+//! neighbors) are descended into and their contents diffed character by
+//! character. Anything else is deleted and re-added whole. This keeps the
+//! resulting operation small for the common case of an external edit that
+//! touches a few blocks, so it transforms cleanly against concurrent edits.
+
+use super::doc::*;
+use crate::normalize::normalize;
+
+/// A single unit of a document span: one character or one group.
+enum Unit<'a, S: Schema> {
+    Char(&'a S::CharsProperties, char),
+    Group(&'a S::GroupProperties, &'a DocSpan<S>),
+}
+
+fn units<S: Schema>(span: &DocSpan<S>) -> Vec<Unit<S>> {
+    let mut res = vec![];
+    for elem in span {
+        match *elem {
+            DocText(ref styles, ref text) => {
+                res.extend(text.as_str().chars().map(|c| Unit::Char(styles, c)));
+            }
+            DocGroup(ref attrs, ref inner) => {
+                res.push(Unit::Group(attrs, inner));
+            }
+        }
+    }
+    res
+}
+
+fn unit_eq<S: Schema>(a: &Unit<S>, b: &Unit<S>) -> bool {
+    match (a, b) {
+        (Unit::Char(a_styles, a_char), Unit::Char(b_styles, b_char)) => {
+            a_char == b_char && a_styles == b_styles
+        }
+        (Unit::Group(a_attrs, a_span), Unit::Group(b_attrs, b_span)) => {
+            S::attrs_eq(a_attrs, b_attrs) && a_span == b_span
+        }
+        _ => false,
+    }
+}
+
+/// Whether two groups can be diffed in place rather than replaced.
+fn unit_pairs<S: Schema>(a: &Unit<S>, b: &Unit<S>) -> bool {
+    match (a, b) {
+        (Unit::Group(a_attrs, _), Unit::Group(b_attrs, _)) => {
+            S::track_type_from_attrs(a_attrs) == S::track_type_from_attrs(b_attrs)
+        }
+        _ => false,
+    }
+}
+
+/// Converts units into an addition that recreates them.
+fn units_to_add<S: Schema>(list: &[Unit<S>], add: &mut AddSpan<S>) {
+    for unit in list {
+        match *unit {
+            Unit::Char(styles, c) => {
+                add.place(&AddText(
+                    styles.clone(),
+                    DocString::from_string(c.to_string()),
+                ));
+            }
+            Unit::Group(attrs, inner) => {
+                let mut inner_add = vec![];
+                units_to_add(&units(inner), &mut inner_add);
+                add.place(&AddGroup(attrs.clone(), inner_add));
+            }
+        }
+    }
+}
+
+/// Diffs the contents of two paired groups.
+fn diff_pair<S: Schema>(a: &Unit<S>, b: &Unit<S>, del: &mut DelSpan<S>, add: &mut AddSpan<S>) {
+    if let (Unit::Group(a_attrs, a_span), Unit::Group(b_attrs, b_span)) = (a, b) {
+        let (inner_del, inner_add) = diff_span(a_span, b_span);
+
+        if inner_del.is_continuous_skip() {
+            del.place(&DelSkip(1));
+        } else {
+            del.place(&DelWithGroup(inner_del));
+        }
+
+        if !S::attrs_eq(a_attrs, b_attrs) {
+            add.place(&AddAttrs((*b_attrs).clone(), inner_add));
+        } else if inner_add.is_continuous_skip() {
+            add.place(&AddSkip(1));
+        } else {
+            add.place(&AddWithGroup(inner_add));
+        }
+    } else {
+        unreachable!();
+    }
+}
+
+fn diff_span<S: Schema>(a: &DocSpan<S>, b: &DocSpan<S>) -> (DelSpan<S>, AddSpan<S>) {
+    let a = units(a);
+    let b = units(b);
+
+    // Strip the units both spans share verbatim from the front and back.
+    let mut prefix = 0;
+    while prefix < a.len() && prefix < b.len() && unit_eq(&a[prefix], &b[prefix]) {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < a.len() - prefix
+        && suffix < b.len() - prefix
+        && unit_eq(&a[a.len() - suffix - 1], &b[b.len() - suffix - 1])
+    {
+        suffix += 1;
+    }
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    // Of what remains, pair up groups at either end that can be diffed in
+    // place instead of being replaced.
+    let mut head = 0;
+    while head < a_mid.len() && head < b_mid.len() && unit_pairs(&a_mid[head], &b_mid[head]) {
+        head += 1;
+    }
+    let mut tail = 0;
+    while tail < a_mid.len() - head
+        && tail < b_mid.len() - head
+        && unit_pairs(
+            &a_mid[a_mid.len() - tail - 1],
+            &b_mid[b_mid.len() - tail - 1],
+        )
+    {
+        tail += 1;
+    }
+
+    let mut del: DelSpan<S> = vec![];
+    let mut add: AddSpan<S> = vec![];
+
+    if prefix > 0 {
+        del.place(&DelSkip(prefix));
+        add.place(&AddSkip(prefix));
+    }
+    for i in 0..head {
+        diff_pair(&a_mid[i], &b_mid[i], &mut del, &mut add);
+    }
+    for unit in &a_mid[head..a_mid.len() - tail] {
+        match *unit {
+            Unit::Char(..) => del.place(&DelText(1)),
+            Unit::Group(..) => del.place(&DelGroupAll),
+        }
+    }
+    units_to_add(&b_mid[head..b_mid.len() - tail], &mut add);
+    for i in 0..tail {
+        diff_pair(
+            &a_mid[a_mid.len() - tail + i],
+            &b_mid[b_mid.len() - tail + i],
+            &mut del,
+            &mut add,
+        );
+    }
+    if suffix > 0 {
+        del.place(&DelSkip(suffix));
+        add.place(&AddSkip(suffix));
+    }
+
+    (del, add)
+}
+
+/// Returns an operation that, applied to `a`, produces `b`.
+pub fn diff<S: Schema>(a: &Doc<S>, b: &Doc<S>) -> Op<S> {
+    let (del, add) = diff_span(&a.0, &b.0);
+    normalize(Op(del, add))
+}
@@ -5,7 +5,9 @@ use serde::{
     Serialize,
 };
 use crate::apply::*;
-use crate::compose::*;
+use crate::check::*;
+use crate::compose::compose;
+use crate::invert::invert;
 use crate::transform::transform;
 
 // Re-exports
@@ -17,6 +19,29 @@ pub use crate::ot::OT;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Op<S: Schema>(pub DelSpan<S>, pub AddSpan<S>);
 
+impl<S: Schema> Op<S> {
+    /// Returns the operation which reverts `op` after it's applied to `doc`.
+    pub fn invert(doc: &Doc<S>, op: &Self) -> Self {
+        invert(&doc.0, op)
+    }
+
+    /// Applies `op` to `doc`, or returns an error if it doesn't fit.
+    pub fn try_apply(doc: &Doc<S>, op: &Self) -> Result<Doc<S>, OpError> {
+        Ok(Doc(try_apply(&doc.0, op)?))
+    }
+
+    /// Composes `a` and `b`, or returns an error if `b` doesn't fit `a`.
+    pub fn try_compose(a: &Self, b: &Self) -> Result<Self, OpError> {
+        try_compose(a, b)
+    }
+
+    /// Transforms `a` and `b`, or returns an error if they don't share a
+    /// document.
+    pub fn try_transform(a: &Self, b: &Self) -> Result<(Self, Self), OpError> {
+        try_transform(a, b)
+    }
+}
+
 impl<S: Schema> OT for Op<S> {
     type Doc = Doc<S>;
 
@@ -76,10 +101,9 @@ pub enum DelElement<S: Schema> {
     DelText(usize),
     DelGroup(DelSpan<S>),
     DelStyles(usize, S::CharsProperties),
-    // TODO Implement these
-    // DelGroupAll,
-    // DelMany(usize),
-    // DelObject,
+    DelGroupAll,
+    DelMany(usize),
+    DelObject,
 }
 
 pub use self::DelElement::*;
@@ -91,6 +115,7 @@ pub enum AddElement<S: Schema> {
     AddText(S::CharsProperties, DocString),
     AddGroup(S::GroupProperties, AddSpan<S>),
     AddStyles(usize, S::CharsProperties),
+    AddAttrs(S::GroupProperties, AddSpan<S>),
 }
 
 pub use self::AddElement::*;
@@ -0,0 +1,315 @@
+//! Methods to compute the inverse of an operation.
+//!
+//! An operation on its own doesn't record what it deletes, so inverting it
+//! requires the document it was applied to. Given `doc` and `op`, `invert`
+//! returns an operation such that applying it to `apply(doc, op)` yields
+//! `doc` again.
+
+use super::apply::apply_delete;
+use super::compose::compose;
+use super::doc::*;
+use crate::normalize::normalize;
+
+fn elem_len<S: Schema>(elem: &DocElement<S>) -> usize {
+    match elem {
+        DocText(_, ref text) => text.char_len(),
+        DocGroup(..) => 1,
+    }
+}
+
+/// Removes up to `count` characters from the front of `span`, returning them
+/// as a single element. Groups are always removed whole.
+fn take_head<S: Schema>(span: &mut DocSpan<S>, count: usize) -> DocElement<S> {
+    if span.is_empty() {
+        panic!("exhausted document in invert");
+    }
+
+    match span.remove(0) {
+        DocText(styles, text) => {
+            if text.char_len() > count {
+                let (left, right) = text.split_at(count);
+                span.insert(0, DocText(styles.clone(), right));
+                DocText(styles, left)
+            } else {
+                DocText(styles, text)
+            }
+        }
+        group => group,
+    }
+}
+
+/// Converts a document span into an addition that recreates it.
+fn doc_to_add<S: Schema>(span: &DocSpan<S>) -> AddSpan<S> {
+    let mut res: AddSpan<S> = vec![];
+    for elem in span {
+        match *elem {
+            DocText(ref styles, ref text) => {
+                res.place(&AddText(styles.clone(), text.clone()));
+            }
+            DocGroup(ref attrs, ref inner) => {
+                res.place(&AddGroup(attrs.clone(), doc_to_add(inner)));
+            }
+        }
+    }
+    res
+}
+
+/// Returns the styles in `set` which are also present in `existing`.
+fn styles_intersect<S: Schema>(
+    set: &S::CharsProperties,
+    existing: &S::CharsProperties,
+) -> S::CharsProperties {
+    let mut outside = set.clone();
+    outside.remove(existing);
+    let mut result = set.clone();
+    result.remove(&outside);
+    result
+}
+
+/// Returns the styles in `set` which are not present in `existing`.
+fn styles_difference<S: Schema>(
+    set: &S::CharsProperties,
+    existing: &S::CharsProperties,
+) -> S::CharsProperties {
+    let mut result = set.clone();
+    result.remove(existing);
+    result
+}
+
+/// Inverts a deletion against the document span it was applied to. The
+/// result is an addition which restores everything the deletion removed.
+/// Groups are walked to their end so that restored groups wrap all of their
+/// original children.
+fn invert_del_inner<S: Schema>(span: &mut DocSpan<S>, del: &DelSpan<S>) -> AddSpan<S> {
+    let mut res: AddSpan<S> = vec![];
+
+    for elem in del {
+        match *elem {
+            DelSkip(count) => {
+                let mut count = count;
+                while count > 0 {
+                    let head = take_head(span, count);
+                    count -= elem_len(&head);
+                    res.place(&AddSkip(elem_len(&head)));
+                }
+            }
+            DelText(count) => {
+                let mut count = count;
+                while count > 0 {
+                    match take_head(span, count) {
+                        DocText(styles, text) => {
+                            count -= text.char_len();
+                            res.place(&AddText(styles, text));
+                        }
+                        DocGroup(..) => {
+                            panic!("Invalid DelText");
+                        }
+                    }
+                }
+            }
+            DelStyles(count, ref del_styles) => {
+                let mut count = count;
+                while count > 0 {
+                    match take_head(span, count) {
+                        DocText(styles, text) => {
+                            count -= text.char_len();
+                            let removed = styles_intersect::<S>(del_styles, &styles);
+                            if removed.is_empty() {
+                                res.place(&AddSkip(text.char_len()));
+                            } else {
+                                res.place(&AddStyles(text.char_len(), removed));
+                            }
+                        }
+                        DocGroup(..) => {
+                            panic!("Invalid DelStyles");
+                        }
+                    }
+                }
+            }
+            DelMany(count) => {
+                let mut count = count;
+                while count > 0 {
+                    let head = take_head(span, count);
+                    count -= elem_len(&head);
+                    res.place_all(&doc_to_add(&vec![head]));
+                }
+            }
+            DelGroupAll | DelObject => match take_head(span, 1) {
+                DocGroup(attrs, inner_span) => {
+                    res.place(&AddGroup(attrs, doc_to_add(&inner_span)));
+                }
+                DocText(..) => {
+                    panic!("Invalid DelGroupAll");
+                }
+            },
+            DelWithGroup(ref inner_del) => match take_head(span, 1) {
+                DocGroup(_, mut inner_span) => {
+                    let inner = invert_del_inner(&mut inner_span, inner_del);
+                    if inner.is_continuous_skip() {
+                        res.place(&AddSkip(1));
+                    } else {
+                        res.place(&AddWithGroup(inner));
+                    }
+                }
+                DocText(..) => {
+                    panic!("Invalid DelWithGroup");
+                }
+            },
+            DelGroup(ref inner_del) => match take_head(span, 1) {
+                DocGroup(attrs, mut inner_span) => {
+                    let inner = invert_del_inner(&mut inner_span, inner_del);
+                    res.place(&AddGroup(attrs, inner));
+                }
+                DocText(..) => {
+                    panic!("Invalid DelGroup");
+                }
+            },
+        }
+    }
+
+    // Anything left over was untouched by the deletion.
+    while !span.is_empty() {
+        let head = span.remove(0);
+        res.place(&AddSkip(elem_len(&head)));
+    }
+
+    res
+}
+
+/// Inverts an addition against the document span it was applied to. The
+/// result is a deletion which removes everything the addition inserted.
+/// Groups created by the addition wrap content from the same span, so the
+/// span is shared with any nested AddGroup.
+fn invert_add_inner<S: Schema>(span: &mut DocSpan<S>, add: &AddSpan<S>) -> DelSpan<S> {
+    let mut res: DelSpan<S> = vec![];
+
+    for elem in add {
+        match *elem {
+            AddSkip(count) => {
+                let mut count = count;
+                while count > 0 {
+                    let head = take_head(span, count);
+                    count -= elem_len(&head);
+                    res.place(&DelSkip(elem_len(&head)));
+                }
+            }
+            AddText(_, ref text) => {
+                res.place(&DelText(text.char_len()));
+            }
+            AddStyles(count, ref add_styles) => {
+                let mut count = count;
+                while count > 0 {
+                    match take_head(span, count) {
+                        DocText(styles, text) => {
+                            count -= text.char_len();
+                            let added = styles_difference::<S>(add_styles, &styles);
+                            if added.is_empty() {
+                                res.place(&DelSkip(text.char_len()));
+                            } else {
+                                res.place(&DelStyles(text.char_len(), added));
+                            }
+                        }
+                        DocGroup(..) => {
+                            panic!("Invalid AddStyles");
+                        }
+                    }
+                }
+            }
+            AddWithGroup(ref inner_add) | AddAttrs(_, ref inner_add) => match take_head(span, 1) {
+                DocGroup(_, mut inner_span) => {
+                    let inner = invert_add_inner(&mut inner_span, inner_add);
+                    if inner.is_continuous_skip() {
+                        res.place(&DelSkip(1));
+                    } else {
+                        res.place(&DelWithGroup(inner));
+                    }
+                }
+                DocText(..) => {
+                    panic!("Invalid AddWithGroup");
+                }
+            },
+            AddGroup(_, ref inner_add) => {
+                let inner = invert_add_inner(span, inner_add);
+                res.place(&DelGroup(inner));
+            }
+        }
+    }
+
+    res
+}
+
+/// Restores the attributes of every group whose attributes were changed by
+/// an addition. The result is an addition against the document after `add`
+/// was applied, and is otherwise entirely skips.
+fn invert_attrs_inner<S: Schema>(span: &mut DocSpan<S>, add: &AddSpan<S>) -> AddSpan<S> {
+    let mut res: AddSpan<S> = vec![];
+
+    for elem in add {
+        match *elem {
+            AddSkip(count) | AddStyles(count, _) => {
+                let mut remaining = count;
+                while remaining > 0 {
+                    let head = take_head(span, remaining);
+                    remaining -= elem_len(&head);
+                }
+                res.place(&AddSkip(count));
+            }
+            AddText(_, ref text) => {
+                res.place(&AddSkip(text.char_len()));
+            }
+            AddWithGroup(ref inner_add) => match take_head(span, 1) {
+                DocGroup(_, mut inner_span) => {
+                    let inner = invert_attrs_inner(&mut inner_span, inner_add);
+                    if inner.is_continuous_skip() {
+                        res.place(&AddSkip(1));
+                    } else {
+                        res.place(&AddWithGroup(inner));
+                    }
+                }
+                DocText(..) => {
+                    panic!("Invalid AddWithGroup");
+                }
+            },
+            AddAttrs(_, ref inner_add) => match take_head(span, 1) {
+                DocGroup(attrs, mut inner_span) => {
+                    let inner = invert_attrs_inner(&mut inner_span, inner_add);
+                    res.place(&AddAttrs(attrs, inner));
+                }
+                DocText(..) => {
+                    panic!("Invalid AddAttrs");
+                }
+            },
+            AddGroup(_, ref inner_add) => {
+                let inner = invert_attrs_inner(span, inner_add);
+                if inner.is_continuous_skip() {
+                    res.place(&AddSkip(1));
+                } else {
+                    res.place(&AddWithGroup(inner));
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// Computes the inverse of `op` as applied to `doc`.
+pub fn invert<S: Schema>(doc: &DocSpan<S>, op: &Op<S>) -> Op<S> {
+    let &Op(ref del, ref add) = op;
+
+    // The inverted addition undoes the deletion, and is computed against the
+    // original document. The inverted deletion undoes the addition, and is
+    // computed against the document as it was in between the two.
+    let postdel = apply_delete(doc, del);
+    let inverse_add = invert_del_inner(&mut doc.clone(), del);
+    let inverse_del = invert_add_inner(&mut postdel.clone(), add);
+
+    // Attribute changes are undone before anything else, while the groups
+    // they changed are still in place.
+    let restore = invert_attrs_inner(&mut postdel.clone(), add);
+    if restore.is_continuous_skip() {
+        normalize(Op(inverse_del, inverse_add))
+    } else {
+        normalize(compose(&Op(vec![], restore), &Op(inverse_del, inverse_add)))
+    }
+}
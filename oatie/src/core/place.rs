@@ -124,14 +124,17 @@ impl<S: Schema> DelPlaceable<S> for DelSpan<S> {
                     self.push(DelSkip(count));
                 }
             }
-            DelGroup(..) | DelWithGroup(..) => {
+            DelMany(count) => {
+                assert!(count > 0);
+                if let Some(&mut DelMany(ref mut value)) = self.last_mut() {
+                    *value += count;
+                } else {
+                    self.push(DelMany(count));
+                }
+            }
+            DelGroup(..) | DelWithGroup(..) | DelGroupAll | DelObject => {
                 self.push(elem.clone());
-            } // DelGroupAll | DelObject => {
-              //     unimplemented!();
-              // }
-              // DelMany(count) => {
-              //     unimplemented!();
-              // }
+            }
         }
     }
 
@@ -141,8 +144,8 @@ impl<S: Schema> DelPlaceable<S> for DelSpan<S> {
             ret += match *item {
                 DelSkip(len) | DelText(len) | DelStyles(len, _) => len,
                 DelGroup(..) | DelWithGroup(..) => 1,
-                // DelMany(len) => len,
-                // DelObject | DelGroupAll  => 1,
+                DelMany(len) => len,
+                DelObject | DelGroupAll => 1,
             };
         }
         ret
@@ -156,7 +159,7 @@ impl<S: Schema> DelPlaceable<S> for DelSpan<S> {
                 DelText(..) => 0,
                 DelWithGroup(..) => 1,
                 DelGroup(ref span) => span.skip_post_len(),
-                // DelObject | DelMany(..) | DelGroupAll => 0,
+                DelObject | DelMany(..) | DelGroupAll => 0,
             };
         }
         ret
@@ -236,7 +239,7 @@ impl<S: Schema> AddPlaceable<S> for AddSpan<S> {
                     self.push(AddSkip(count));
                 }
             }
-            AddGroup(..) | AddWithGroup(..) => {
+            AddGroup(..) | AddWithGroup(..) | AddAttrs(..) => {
                 self.push(elem.clone());
             }
         }
@@ -249,7 +252,7 @@ impl<S: Schema> AddPlaceable<S> for AddSpan<S> {
                 AddSkip(len) | AddStyles(len, _) => len,
                 AddText(ref _chars, _) => 0,
                 AddGroup(_, ref span) => span.skip_pre_len(),
-                AddWithGroup(..) => 1,
+                AddWithGroup(..) | AddAttrs(..) => 1,
             };
         }
         ret
@@ -261,7 +264,7 @@ impl<S: Schema> AddPlaceable<S> for AddSpan<S> {
             ret += match *item {
                 AddSkip(len) | AddStyles(len, _) => len,
                 AddText(_, ref chars) => chars.char_len(),
-                AddGroup(..) | AddWithGroup(..) => 1,
+                AddGroup(..) | AddWithGroup(..) | AddAttrs(..) => 1,
             };
         }
         ret
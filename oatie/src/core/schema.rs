@@ -43,7 +43,9 @@ pub trait Schema: Clone + Debug + PartialEq {
     /// Get the track type from this Attrs.
     fn track_type_from_attrs(attrs: &Self::GroupProperties) -> Option<Self::Track>;
 
-    /// Combine two Attrs into a new definition.
+    /// Combine two Attrs into a new definition. This decides the outcome when
+    /// two operations concurrently change the attributes of the same group;
+    /// returning `None` lets the transform prefer the first operation.
     fn merge_attrs(
         a: &Self::GroupProperties,
         b: &Self::GroupProperties,
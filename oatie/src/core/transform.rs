@@ -1,5 +1,10 @@
 //! Performs operational transform on a Doc.
 
+use super::check::{
+    mismatch,
+    OpError,
+    OpErrorKind,
+};
 use super::compose;
 use super::doc::*;
 pub use super::schema::*;
@@ -18,6 +23,11 @@ struct TrackState<S: Schema> {
     _phantom: PhantomData<S>,
 }
 
+/// The error for track states the transform can't continue from.
+fn unsupported() -> OpError {
+    OpError::new(OpErrorKind::Unsupported)
+}
+
 struct Transform<S: Schema> {
     tracks: Vec<TrackState<S>>,
     a_del: DelWriter<S>,
@@ -106,7 +116,7 @@ impl<S: Schema> Transform<S> {
         self.b_add.begin();
     }
 
-    fn enter_b(&mut self, a: Option<S::GroupProperties>, b: &S::GroupProperties) {
+    fn enter_b(&mut self, a: Option<S::GroupProperties>, b: &S::GroupProperties) -> Result<(), OpError> {
         log_transform!("ENTER B");
 
         let last = self
@@ -132,11 +142,12 @@ impl<S: Schema> Transform<S> {
                         .as_ref()
                         .or(last.tag_real.as_ref())
                         .or(last.tag_b.as_ref())
-                        .unwrap(),
+                        .ok_or_else(unsupported)?,
                 )
             {
                 log_transform!("-----> UGH {:?}", last);
-                panic!("Should not have consecutive similar tracks.");
+                // Consecutive similar tracks can't be nested.
+                return mismatch(OpErrorKind::Unsupported);
             }
         }
 
@@ -158,36 +169,42 @@ impl<S: Schema> Transform<S> {
         self.a_add.begin();
         self.b_del.begin();
         self.b_add.begin();
+        Ok(())
     }
 
     // Close the topmost track.
     fn abort(
         &mut self,
-    ) -> (
-        Option<S::GroupProperties>,
-        Option<S::GroupProperties>,
-        Option<S::GroupProperties>,
-    ) {
-        let track = self.tracks.pop().unwrap();
+    ) -> Result<
+        (
+            Option<S::GroupProperties>,
+            Option<S::GroupProperties>,
+            Option<S::GroupProperties>,
+        ),
+        OpError,
+    > {
+        let track = self.tracks.pop().ok_or_else(unsupported)?;
 
         if let Some(ref real) = track.tag_real {
             self.a_add.close(real.clone());
             self.b_add.close(real.clone());
         }
 
-        (track.tag_a, track.tag_real, track.tag_b)
+        Ok((track.tag_a, track.tag_real, track.tag_b))
     }
 
-    fn unenter_a(&mut self, ty: S::Track) {
+    fn unenter_a(&mut self, ty: S::Track) -> Result<(), OpError> {
         self.a_del.begin();
-        let track = self.next_track_a_by_type(ty).unwrap();
+        let track = self.next_track_a_by_type(ty).ok_or_else(unsupported)?;
         track.tag_a = track.tag_real.clone();
+        Ok(())
     }
 
-    fn unenter_b(&mut self, ty: S::Track) {
+    fn unenter_b(&mut self, ty: S::Track) -> Result<(), OpError> {
         self.b_del.begin();
-        let track = self.next_track_b_by_type(ty).unwrap();
+        let track = self.next_track_b_by_type(ty).ok_or_else(unsupported)?;
         track.tag_b = track.tag_real.clone();
+        Ok(())
     }
 
     fn skip_a(&mut self, n: usize) {
@@ -218,6 +235,14 @@ impl<S: Schema> Transform<S> {
         self.b_add.place(&AddWithGroup(span.clone()));
     }
 
+    fn with_attrs_a(&mut self, attrs: &S::GroupProperties, span: &AddSpan<S>) {
+        self.a_add.place(&AddAttrs(attrs.clone(), span.clone()));
+    }
+
+    fn with_attrs_b(&mut self, attrs: &S::GroupProperties, span: &AddSpan<S>) {
+        self.b_add.place(&AddAttrs(attrs.clone(), span.clone()));
+    }
+
     fn group_a(&mut self, attrs: &S::GroupProperties, span: &AddSpan<S>) {
         self.a_add.place(&AddGroup(attrs.clone(), span.clone()));
     }
@@ -243,15 +268,15 @@ impl<S: Schema> Transform<S> {
         }
     }
 
-    fn close(&mut self) {
-        let (track, index) = self.top_track_a().unwrap();
+    fn close(&mut self) -> Result<(), OpError> {
+        let (track, index) = self.top_track_a().ok_or_else(unsupported)?;
 
         if track.is_original_a && track.tag_real == track.tag_a {
             self.a_del.exit();
             self.a_add.exit();
         } else {
             self.a_del.close();
-            self.a_add.close(track.tag_real.clone().unwrap());
+            self.a_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
         }
 
         if track.is_original_b && track.tag_real == track.tag_b {
@@ -259,10 +284,11 @@ impl<S: Schema> Transform<S> {
             self.b_add.exit();
         } else {
             self.b_del.close();
-            self.b_add.close(track.tag_real.clone().unwrap());
+            self.b_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
         }
 
         self.tracks.remove(index);
+        Ok(())
     }
 
     fn top_track_a(&mut self) -> Option<(TrackState<S>, usize)> {
@@ -314,8 +340,8 @@ impl<S: Schema> Transform<S> {
         }
     }
 
-    fn close_a(&mut self) {
-        let (track, index) = self.top_track_a().unwrap();
+    fn close_a(&mut self) -> Result<(), OpError> {
+        let (track, index) = self.top_track_a().ok_or_else(unsupported)?;
 
         // Determine whether to split tags for this track type.
         // TODO do the same for track opening?
@@ -332,12 +358,12 @@ impl<S: Schema> Transform<S> {
         } else {
             self.a_del.close();
             if track_split || track.tag_b.is_none() {
-                self.a_add.close(track.tag_real.clone().unwrap());
+                self.a_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
             }
         }
 
         if track_split || track.tag_b.is_none() {
-            self.b_add.close(track.tag_real.clone().unwrap());
+            self.b_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
         }
 
         if track.tag_b.is_none() {
@@ -352,10 +378,11 @@ impl<S: Schema> Transform<S> {
                 self.tracks[index].tag_real = None;
             }
         }
+        Ok(())
     }
 
-    fn close_b(&mut self) {
-        let (track, index) = self.top_track_b().unwrap();
+    fn close_b(&mut self) -> Result<(), OpError> {
+        let (track, index) = self.top_track_b().ok_or_else(unsupported)?;
 
         // Determine whether to split tags for this track type.
         // TODO do the same for track opening?
@@ -372,12 +399,12 @@ impl<S: Schema> Transform<S> {
         } else {
             self.b_del.close();
             if track_split || track.tag_a.is_none() {
-                self.b_add.close(track.tag_real.clone().unwrap());
+                self.b_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
             }
         }
 
         if track_split || track.tag_a.is_none() {
-            self.a_add.close(track.tag_real.clone().unwrap());
+            self.a_add.close(track.tag_real.clone().ok_or_else(unsupported)?);
         }
 
         if track.tag_a.is_none() {
@@ -394,12 +421,13 @@ impl<S: Schema> Transform<S> {
                 self.tracks[index].tag_real = None;
             }
         }
+        Ok(())
     }
 
     // Interrupt all tracks up the ancestry until we get to
     // a particular type, OR a type than could be an ancestor
     // of the given type
-    fn interrupt(&mut self, itype: S::Track, inclusive: bool) {
+    fn interrupt(&mut self, itype: S::Track, inclusive: bool) -> Result<(), OpError> {
         let mut regen = vec![];
         while let Some(track) = self.current() {
             let (istag, hasparent) = if let Some(ref real) = track.tag_real {
@@ -415,7 +443,7 @@ impl<S: Schema> Transform<S> {
 
             if track.tag_real.is_some() && ((!istag && hasparent) || (istag && inclusive)) {
                 log_transform!("aborting by {:?} {:?} {:?}", itype, inclusive, istag);
-                let aborted = self.abort();
+                let aborted = self.abort()?;
                 regen.push(aborted);
                 if istag && inclusive {
                     break;
@@ -435,6 +463,7 @@ impl<S: Schema> Transform<S> {
                 _phantom: PhantomData,
             })
         }
+        Ok(())
     }
 
     // TODO combine this with regenerate_until ?
@@ -515,7 +544,7 @@ impl<S: Schema> Transform<S> {
         }
     }
 
-    fn result(mut self) -> (Op<S>, Op<S>) {
+    fn result(mut self) -> Result<(Op<S>, Op<S>), OpError> {
         let mut a_del = self.a_del;
         let mut a_add = self.a_add;
         let mut b_del = self.b_del;
@@ -524,24 +553,24 @@ impl<S: Schema> Transform<S> {
         for track in self.tracks.iter_mut().rev() {
             log_transform!("TRACK RESULT: {:?}", track);
             if !track.is_original_a && track.tag_real.is_some() {
-                a_add.close(track.tag_a.clone().unwrap());
+                a_add.close(track.tag_a.clone().ok_or_else(unsupported)?);
             }
             if track.is_original_a {
                 a_del.exit();
                 a_add.exit();
             }
             if !track.is_original_b && track.tag_real.is_some() {
-                b_add.close(track.tag_b.clone().unwrap());
+                b_add.close(track.tag_b.clone().ok_or_else(unsupported)?);
             }
             if track.is_original_b {
                 b_del.exit();
                 b_add.exit();
             }
         }
-        (
+        Ok((
             Op(a_del.result(), a_add.result()),
             Op(b_del.result(), b_add.result()),
-        )
+        ))
     }
 
     // fn current_type(&self) -> Option<S::Track> {
@@ -560,6 +589,13 @@ impl<S: Schema> Transform<S> {
 }
 
 pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) -> (Op<S>, Op<S>) {
+    try_transform_insertions(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn try_transform_insertions<S: Schema>(
+    avec: &AddSpan<S>,
+    bvec: &AddSpan<S>,
+) -> Result<(Op<S>, Op<S>), OpError> {
     let mut a = AddStepper::new(avec);
     let mut b = AddStepper::new(bvec);
 
@@ -595,6 +631,11 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     t.with_group_a(span);
                     b.next();
                 }
+                Some(AddAttrs(ref attrs, ref span)) => {
+                    t.skip_b(1);
+                    t.with_attrs_a(attrs, span);
+                    b.next();
+                }
                 Some(AddText(b_styles, b_chars)) => {
                     t.skip_b(b_chars.char_len());
                     t.chars_a(b_chars, b_styles);
@@ -611,7 +652,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     b.next();
                 }
                 None => {
-                    t.close_b();
+                    t.close_b()?;
                     b.exit();
                 }
             }
@@ -633,6 +674,11 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     t.with_group_b(span);
                     a.next();
                 }
+                Some(AddAttrs(ref attrs, ref span)) => {
+                    t.skip_a(1);
+                    t.with_attrs_b(attrs, span);
+                    a.next();
+                }
                 Some(AddText(a_styles, a_chars)) => {
                     t.skip_a(a_chars.char_len());
                     t.chars_b(a_chars, a_styles);
@@ -649,7 +695,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     a.next();
                 }
                 None => {
-                    t.close_a();
+                    t.close_a()?;
                     a.exit();
                 }
             }
@@ -667,7 +713,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                 // Closing
                 (None, None) => {
                     let (a_tag, b_tag) = {
-                        let t = t.tracks.last().unwrap();
+                        let t = t.tracks.last().ok_or_else(unsupported)?;
                         (t.tag_a.clone(), t.tag_b.clone())
                     };
 
@@ -676,10 +722,10 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                         && S::track_type_from_attrs(a_tag.as_ref().unwrap())
                             == S::track_type_from_attrs(b_tag.as_ref().unwrap())
                     {
-                        // t.interrupt(a_tag || b_tag);
+                        // t.interrupt(a_tag || b_tag)?;
                         a.exit();
                         b.exit();
-                        t.close();
+                        t.close()?;
                     } else if a_tag.is_some()
                         && (b_tag.is_none()
                             || S::track_type_from_attrs(a_tag.as_ref().unwrap())
@@ -690,13 +736,13 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                     *x == S::track_type_from_attrs(b_tag.as_ref().unwrap()).unwrap()
                                 }))
                     {
-                        // t.interrupt(a_tag);
+                        // t.interrupt(a_tag)?;
                         a.exit();
-                        t.close_a();
+                        t.close_a()?;
                     } else if b_tag.is_some() {
-                        // t.interrupt(b_tag);
+                        // t.interrupt(b_tag)?;
                         b.exit();
-                        t.close_b();
+                        t.close_b()?;
                     }
                 }
 
@@ -723,8 +769,8 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                         if b_type.is_object() {
                             b.enter();
                             b.exit();
-                            t.enter_b(None, b_attrs);
-                            t.close_b();
+                            t.enter_b(None, b_attrs)?;
+                            t.close_b()?;
 
                             true
                         } else {
@@ -739,18 +785,16 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                 .iter()
                                 .rev()
                                 .find(|t| t.tag_a.is_some())
-                                .unwrap()
-                                .tag_a
-                                .as_ref()
-                                .unwrap(),
+                                .and_then(|t| t.tag_a.as_ref())
+                                .ok_or_else(unsupported)?,
                         )
                         .unwrap();
                         log_transform!("what is up with a {:?}", t.a_add);
-                        t.interrupt(a_typ, false);
+                        t.interrupt(a_typ, false)?;
                         // log_transform!("... {:?} {:?}", t.a_del, t.a_add);
                         // log_transform!("... {:?} {:?}", t.b_del, t.b_add);
                         log_transform!("~~~> tracks {:?}", t.tracks);
-                        t.close_a();
+                        t.close_a()?;
                         // log_transform!("...");
                         a.exit();
                         log_transform!("<~~~ tracks {:?}", t.tracks);
@@ -780,9 +824,9 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                             b.enter();
                             b.exit();
                             t.enter_a(&a_attrs, None);
-                            t.close_a();
-                            t.enter_b(None, &b_attrs);
-                            t.close_b();
+                            t.close_a()?;
+                            t.enter_b(None, &b_attrs)?;
+                            t.close_b()?;
                         } else {
                             a.enter();
                             b.enter();
@@ -803,7 +847,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                             // if a_type.map_or(false, |x| x.do_open_split()) {
                             if true {
                                 log_transform!("INTERRUPTING A");
-                                t.interrupt(a_type.clone(), false);
+                                t.interrupt(a_type.clone(), false)?;
                                 log_transform!("BUT THE TRACKS -----<> {:?}", t.tracks);
                                 if let Some(j) = t.next_track_a_by_type(a_type) {
                                     j.tag_a = Some(a_attrs.clone());
@@ -812,11 +856,11 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                 }
                                 t.a_del.begin();
                             } else {
-                                // TODO t.interrupt(a_type, true);
-                                t.unenter_a(a_type);
+                                // TODO t.interrupt(a_type, true)?;
+                                t.unenter_a(a_type)?;
                             }
                         } else {
-                            t.interrupt(a_type.clone(), false); // caret-46
+                            t.interrupt(a_type.clone(), false)?; // caret-46
                             t.enter_a(&a_attrs, None);
                         }
                     } else
@@ -832,7 +876,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                             // if b_type.map_or(false, |x| x.do_open_split()) {
                             if true {
                                 log_transform!("INTERRUPTING B");
-                                t.interrupt(b_type.clone(), false);
+                                t.interrupt(b_type.clone(), false)?;
                                 if let Some(j) = t.next_track_b_by_type(b_type.clone()) {
                                     j.tag_b = Some(b_attrs.clone());
                                     j.is_original_b = false;
@@ -840,12 +884,12 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                 }
                                 t.b_del.begin();
                             } else {
-                                // TODO t.interrupt(b_type, true);
-                                t.unenter_b(b_type);
+                                // TODO t.interrupt(b_type, true)?;
+                                t.unenter_b(b_type)?;
                             }
                         } else {
-                            t.interrupt(b_type.clone(), false); // caret-43
-                            t.enter_b(None, &b_attrs);
+                            t.interrupt(b_type.clone(), false)?; // caret-43
+                            t.enter_b(None, &b_attrs)?;
                         }
                     }
 
@@ -875,7 +919,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                 a.enter();
                                 a.exit();
                                 t.enter_a(a_attrs, None);
-                                t.close_a();
+                                t.close_a()?;
 
                                 true
                             } else {
@@ -890,14 +934,12 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                                     .iter()
                                     .rev()
                                     .find(|t| t.tag_b.is_some())
-                                    .unwrap()
-                                    .tag_b
-                                    .as_ref()
-                                    .unwrap(),
+                                    .and_then(|t| t.tag_b.as_ref())
+                                    .ok_or_else(unsupported)?,
                             )
                             .unwrap();
-                            t.interrupt(b_typ, false);
-                            t.close_b();
+                            t.interrupt(b_typ, false)?;
+                            t.close_b()?;
                             b.exit();
                         }
                     }
@@ -912,7 +954,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                         a.enter();
                         a.exit();
                         t.enter_a(&a_attrs, None);
-                        t.close_a();
+                        t.close_a()?;
                     } else {
                         a.enter();
 
@@ -924,17 +966,17 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                             if a_type.do_open_split() {
                                 // if true {
                                 log_transform!("INTERRUPTING A");
-                                t.interrupt(a_type, true);
+                                t.interrupt(a_type, true)?;
                                 if let Some(j) = t.next_track_a_by_type(a_type) {
                                     j.tag_a = Some(a_attrs.clone());
                                     j.is_original_a = true;
                                 }
                                 t.a_del.begin();
                             } else {
-                                t.unenter_a(a_type);
+                                t.unenter_a(a_type)?;
                             }
                         } else {
-                            t.interrupt(a_type, true);
+                            t.interrupt(a_type, true)?;
                             t.enter_a(&a_attrs, None);
                         }
                     }
@@ -946,8 +988,8 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     if b_type.is_object() {
                         b.enter();
                         b.exit();
-                        t.enter_b(None, &b_attrs);
-                        t.close_b();
+                        t.enter_b(None, &b_attrs)?;
+                        t.close_b()?;
                     } else {
                         // log_transform!("groupgruop {:?} {:?}", a_type, b_type);
                         b.enter();
@@ -956,19 +998,19 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                         if t.next_track_b_by_type(b_type).is_some() {
                             if b_type.do_open_split() {
                                 log_transform!("INTERRUPTING B");
-                                t.interrupt(b_type, true);
+                                t.interrupt(b_type, true)?;
                                 if let Some(j) = t.next_track_b_by_type(b_type) {
                                     j.tag_b = Some(b_attrs.clone());
                                     j.is_original_b = true;
                                 }
                                 t.b_del.begin();
                             } else {
-                                // TODO? t.interrupt(b_type, true);
-                                t.unenter_b(b_type);
+                                // TODO? t.interrupt(b_type, true)?;
+                                t.unenter_b(b_type)?;
                             }
                         } else {
-                            t.interrupt(b_type, false); // caret-32
-                            t.enter_b(None, &b_attrs);
+                            t.interrupt(b_type, false)?; // caret-32
+                            t.enter_b(None, &b_attrs)?;
                         }
                     }
                 }
@@ -1020,7 +1062,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                         b.next();
                     }
                     t.style_a(cmp::min(a_count, b_count), b_styles.clone());
-                    t.skip_a(cmp::min(a_count, b_count));
+                    t.skip_b(cmp::min(a_count, b_count));
                 }
                 (Some(AddStyles(a_count, a_styles)), Some(AddSkip(b_count))) => {
                     t.regenerate();
@@ -1055,11 +1097,9 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                 }
 
                 // With Groups
-                (Some(AddWithGroup(..)), Some(AddStyles(..))) => {
-                    panic!("invalid transform AddWithGroup by AddStyles");
-                }
-                (Some(AddStyles(..)), Some(AddWithGroup(..))) => {
-                    panic!("invalid transform AddStyles by AddWithGroup");
+                (Some(AddWithGroup(..)), Some(AddStyles(..)))
+                | (Some(AddStyles(..)), Some(AddWithGroup(..))) => {
+                    return mismatch(OpErrorKind::ExpectedText);
                 }
                 (Some(AddWithGroup(a_inner)), Some(AddSkip(b_count))) => {
                     t.regenerate(); // caret-31
@@ -1079,7 +1119,7 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                 (Some(AddWithGroup(a_inner)), Some(AddWithGroup(b_inner))) => {
                     t.regenerate(); // caret-31
 
-                    let (a_op, b_op) = transform_insertions::<S>(&a_inner, &b_inner);
+                    let (a_op, b_op) = try_transform_insertions::<S>(&a_inner, &b_inner)?;
 
                     t.a_del.place(&DelWithGroup(a_op.0));
                     t.a_add.place(&AddWithGroup(a_op.1));
@@ -1104,7 +1144,8 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
                     }
                     b.next();
                 }
-                (Some(AddWithGroup(_a_inner)), Some(AddText(b_styles, b_chars))) => {
+                (Some(AddWithGroup(..)), Some(AddText(b_styles, b_chars)))
+                | (Some(AddAttrs(..)), Some(AddText(b_styles, b_chars))) => {
                     t.regenerate(); // caret-35
 
                     t.b_del.place(&DelSkip(b_chars.char_len()));
@@ -1114,6 +1155,94 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
 
                     b.next();
                 }
+
+                // Attribute changes
+                (Some(AddAttrs(..)), Some(AddStyles(..)))
+                | (Some(AddStyles(..)), Some(AddAttrs(..))) => {
+                    return mismatch(OpErrorKind::ExpectedText);
+                }
+                (Some(AddAttrs(a_attrs, a_inner)), Some(AddSkip(b_count))) => {
+                    t.regenerate();
+
+                    t.a_del.place(&DelSkip(1));
+                    t.a_add.place(&AddSkip(1));
+                    t.b_del.place(&DelSkip(1));
+                    t.b_add.place(&AddAttrs(a_attrs, a_inner));
+
+                    a.next();
+                    if b_count > 1 {
+                        b.head = Some(AddSkip(b_count - 1));
+                    } else {
+                        b.next();
+                    }
+                }
+                (Some(AddSkip(a_count)), Some(AddAttrs(b_attrs, b_inner))) => {
+                    t.regenerate();
+
+                    t.a_del.place(&DelSkip(1));
+                    t.a_add.place(&AddAttrs(b_attrs, b_inner));
+                    t.b_del.place(&DelSkip(1));
+                    t.b_add.place(&AddSkip(1));
+
+                    if a_count > 1 {
+                        a.head = Some(AddSkip(a_count - 1));
+                    } else {
+                        a.next();
+                    }
+                    b.next();
+                }
+                (Some(AddAttrs(a_attrs, a_inner)), Some(AddWithGroup(b_inner))) => {
+                    t.regenerate();
+
+                    let (a_op, b_op) = try_transform_insertions::<S>(&a_inner, &b_inner)?;
+
+                    t.a_del.place(&DelWithGroup(a_op.0));
+                    t.a_add.place(&AddWithGroup(a_op.1));
+                    t.b_del.place(&DelWithGroup(b_op.0));
+                    t.b_add.place(&AddAttrs(a_attrs, b_op.1));
+
+                    a.next();
+                    b.next();
+                }
+                (Some(AddWithGroup(a_inner)), Some(AddAttrs(b_attrs, b_inner))) => {
+                    t.regenerate();
+
+                    let (a_op, b_op) = try_transform_insertions::<S>(&a_inner, &b_inner)?;
+
+                    t.a_del.place(&DelWithGroup(a_op.0));
+                    t.a_add.place(&AddAttrs(b_attrs, a_op.1));
+                    t.b_del.place(&DelWithGroup(b_op.0));
+                    t.b_add.place(&AddWithGroup(b_op.1));
+
+                    a.next();
+                    b.next();
+                }
+                (Some(AddAttrs(a_attrs, a_inner)), Some(AddAttrs(b_attrs, b_inner))) => {
+                    t.regenerate();
+
+                    let (a_op, b_op) = try_transform_insertions::<S>(&a_inner, &b_inner)?;
+
+                    // Both sides retyped the same group. Let the schema decide
+                    // the outcome, or otherwise prefer A.
+                    let real =
+                        S::merge_attrs(&a_attrs, &b_attrs).unwrap_or_else(|| a_attrs.clone());
+
+                    t.a_del.place(&DelWithGroup(a_op.0));
+                    if S::attrs_eq(&real, &a_attrs) {
+                        t.a_add.place(&AddWithGroup(a_op.1));
+                    } else {
+                        t.a_add.place(&AddAttrs(real.clone(), a_op.1));
+                    }
+                    t.b_del.place(&DelWithGroup(b_op.0));
+                    if S::attrs_eq(&real, &b_attrs) {
+                        t.b_add.place(&AddWithGroup(b_op.1));
+                    } else {
+                        t.b_add.place(&AddAttrs(real, b_op.1));
+                    }
+
+                    a.next();
+                    b.next();
+                }
             }
         }
     }
@@ -1123,10 +1252,10 @@ pub fn transform_insertions<S: Schema>(avec: &AddSpan<S>, bvec: &AddSpan<S>) ->
     log_transform!("TRACK B DEL {:?}", t.b_del);
     log_transform!("TRACK B ADD {:?}", t.b_add);
 
-    let (op_a, op_b) = t.result();
+    let (op_a, op_b) = t.result()?;
     log_transform!("RESULT A: {:?}", op_a.clone());
     log_transform!("RESULT B: {:?}", op_b.clone());
-    (op_a, op_b)
+    Ok((op_a, op_b))
 }
 
 // Create del span that occurs after the input deletion has occurred.
@@ -1149,6 +1278,9 @@ fn undel<S: Schema>(input_del: &DelSpan<S>) -> DelSpan<S> {
             &DelGroup(ref del_span) => {
                 del.place_all(&undel(&del_span));
             }
+            &DelMany(..) | &DelGroupAll | &DelObject => {
+                // skip
+            }
         }
     }
     del
@@ -1159,7 +1291,7 @@ pub fn transform_del_del_inner<S: Schema>(
     b_del: &mut DelWriter<S>,
     a: &mut DelStepper<S>,
     b: &mut DelStepper<S>,
-) {
+) -> Result<(), OpError> {
     while !a.is_done() && !b.is_done() {
         log_transform!("{}", Green.bold().paint("transform_deletions:"));
         log_transform!("{}", BrightGreen.paint(format!(" @ a_del: {:?}", a_del)));
@@ -1177,7 +1309,7 @@ pub fn transform_del_del_inner<S: Schema>(
         match (a.head.clone(), b.head.clone()) {
             // Groups
             (Some(DelGroup(a_inner)), Some(DelGroup(b_inner))) => {
-                let (a_del_inner, b_del_inner) = transform_deletions(&a_inner, &b_inner);
+                let (a_del_inner, b_del_inner) = try_transform_deletions(&a_inner, &b_inner)?;
 
                 a_del.place_all(&a_del_inner);
                 b_del.place_all(&b_del_inner);
@@ -1197,9 +1329,7 @@ pub fn transform_del_del_inner<S: Schema>(
                     &mut b_inner_del,
                     &mut a_inner_step,
                     &mut b_inner_step,
-                );
-
-                assert!(b_inner_step.is_done());
+                )?;
 
                 // Del the del
                 let mut del_span = vec![];
@@ -1211,6 +1341,15 @@ pub fn transform_del_del_inner<S: Schema>(
                 a_inner_del.place_all(&undel(&del_span));
                 b_inner_del.place_all(&del_span);
 
+                // The rest of b's changes apply to the unwrapped contents.
+                let mut rest_span = vec![];
+                while !b_inner_step.is_done() {
+                    rest_span.push(b_inner_step.head.clone().unwrap());
+                    b_inner_step.next();
+                }
+                a_inner_del.place_all(&rest_span);
+                b_inner_del.place_all(&undel(&rest_span));
+
                 a_del.place_all(&a_inner_del.result());
                 b_del.place(&DelGroup(b_inner_del.result()));
 
@@ -1357,34 +1496,34 @@ pub fn transform_del_del_inner<S: Schema>(
                 if a_count > b_count {
                     a.head = Some(DelStyles(a_count - b_count, a_styles.clone()));
                     b.next();
-                    a_del.place(&DelText(b_count));
                 } else if a_count < b_count {
                     a.next();
                     b.head = Some(DelText(b_count - a_count));
                 } else {
                     a.next();
                     b.next();
-                    a_del.place(&DelText(a_count));
                 }
+
+                a_del.place(&DelText(cmp::min(a_count, b_count)));
             }
             (Some(DelText(a_count)), Some(DelStyles(b_count, b_styles))) => {
                 if a_count > b_count {
                     a.head = Some(DelText(a_count - b_count));
                     b.next();
-                    b_del.place(&DelText(b_count));
                 } else if a_count < b_count {
                     a.next();
                     b.head = Some(DelStyles(b_count - a_count, b_styles.clone()));
                 } else {
                     a.next();
                     b.next();
-                    b_del.place(&DelText(a_count));
                 }
+
+                b_del.place(&DelText(cmp::min(a_count, b_count)));
             }
 
             // With Groups
             (Some(DelWithGroup(a_inner)), Some(DelWithGroup(b_inner))) => {
-                let (a_del_inner, b_del_inner) = transform_deletions(&a_inner, &b_inner);
+                let (a_del_inner, b_del_inner) = try_transform_deletions(&a_inner, &b_inner)?;
 
                 a_del.place(&DelWithGroup(a_del_inner));
                 b_del.place(&DelWithGroup(b_del_inner));
@@ -1426,9 +1565,7 @@ pub fn transform_del_del_inner<S: Schema>(
                     &mut b_inner_del,
                     &mut a_inner_step,
                     &mut b_inner_step,
-                );
-
-                assert!(a_inner_step.is_done());
+                )?;
 
                 // Del the del
                 let mut del_span = vec![];
@@ -1439,6 +1576,15 @@ pub fn transform_del_del_inner<S: Schema>(
                 a_inner_del.place_all(&del_span);
                 b_inner_del.place_all(&undel(&del_span));
 
+                // The rest of a's changes apply to the unwrapped contents.
+                let mut rest_span = vec![];
+                while !a_inner_step.is_done() {
+                    rest_span.push(a_inner_step.head.clone().unwrap());
+                    a_inner_step.next();
+                }
+                a_inner_del.place_all(&undel(&rest_span));
+                b_inner_del.place_all(&rest_span);
+
                 a_del.place(&DelGroup(a_inner_del.result()));
                 b_del.place_all(&b_inner_del.result());
 
@@ -1446,61 +1592,216 @@ pub fn transform_del_del_inner<S: Schema>(
                 b.next();
             }
 
-            // TODO
-            // (Some(DelGroupAll), Some(DelWithGroup(_))) => {
-            //     b_del.group_all();
-
-            //     a.next();
-            //     b.next();
-            // }
-            // (Some(DelWithGroup(_)), Some(DelGroupAll)) => {
-            //     a_del.group_all();
-
-            //     a.next();
-            //     b.next();
-            // }
-            // (Some(DelSkip(a_count)), Some(DelGroupAll)) => {
-            //     a_del.group_all();
-            //     if a_count > 1 {
-            //         a.head = Some(DelSkip(a_count - 1));
-            //     } else {
-            //         a.next();
-            //     }
-
-            //     b.next();
-            // }
-            // (Some(DelGroupAll), Some(DelSkip(b_count))) => {
-            //     b_del.group_all();
-
-            //     a.next();
-            //     if b_count > 1 {
-            //         b.head = Some(DelSkip(b_count - 1));
-            //     } else {
-            //         b.next();
-            //     }
-            // }
-            // (Some(DelGroupAll), Some(DelGroup(b_inner))) => {
-            //     if b_inner.skip_post_len() > 0 {
-            //         b_del.many(b_inner.skip_post_len());
-            //     }
-
-            //     a.next();
-            //     b.next();
-            // }
-            // (Some(DelGroup(a_inner)), Some(DelGroupAll)) => {
-            //     if a_inner.skip_post_len() > 0 {
-            //         a_del.many(a_inner.skip_post_len());
-            //     }
-
-            //     a.next();
-            //     b.next();
-            // }
-            // (Some(DelGroupAll), Some(DelGroupAll)) => {
-            //     a.next();
-            //     b.next();
-            // }
-
-            // TODO why are these unreachable?
+            // Whole deletions
+            (Some(DelMany(a_count)), Some(DelMany(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelMany(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelMany(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+            }
+            (Some(DelMany(a_count)), Some(DelGroupAll))
+            | (Some(DelMany(a_count)), Some(DelObject)) => {
+                if a_count > 1 {
+                    a.head = Some(DelMany(a_count - 1));
+                } else {
+                    a.next();
+                }
+                b.next();
+            }
+            (Some(DelGroupAll), Some(DelMany(b_count)))
+            | (Some(DelObject), Some(DelMany(b_count))) => {
+                a.next();
+                if b_count > 1 {
+                    b.head = Some(DelMany(b_count - 1));
+                } else {
+                    b.next();
+                }
+            }
+            (Some(DelGroupAll), Some(DelGroupAll))
+            | (Some(DelGroupAll), Some(DelObject))
+            | (Some(DelObject), Some(DelGroupAll))
+            | (Some(DelObject), Some(DelObject)) => {
+                a.next();
+                b.next();
+            }
+            (Some(DelSkip(a_count)), Some(DelMany(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelSkip(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelMany(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+
+                a_del.many(cmp::min(a_count, b_count));
+            }
+            (Some(DelMany(a_count)), Some(DelSkip(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelMany(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelSkip(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+
+                b_del.many(cmp::min(a_count, b_count));
+            }
+            (Some(DelText(a_count)), Some(DelMany(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelText(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelMany(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+            }
+            (Some(DelMany(a_count)), Some(DelText(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelMany(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelText(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+            }
+            (Some(DelStyles(a_count, a_styles)), Some(DelMany(b_count))) => {
+                if a_count > b_count {
+                    a.head = Some(DelStyles(a_count - b_count, a_styles));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelMany(b_count - a_count));
+                } else {
+                    a.next();
+                    b.next();
+                }
+
+                a_del.many(cmp::min(a_count, b_count));
+            }
+            (Some(DelMany(a_count)), Some(DelStyles(b_count, b_styles))) => {
+                if a_count > b_count {
+                    a.head = Some(DelMany(a_count - b_count));
+                    b.next();
+                } else if a_count < b_count {
+                    a.next();
+                    b.head = Some(DelStyles(b_count - a_count, b_styles));
+                } else {
+                    a.next();
+                    b.next();
+                }
+
+                b_del.many(cmp::min(a_count, b_count));
+            }
+            (Some(DelSkip(a_count)), Some(DelGroupAll))
+            | (Some(DelSkip(a_count)), Some(DelObject)) => {
+                a_del.place(&b.head.clone().unwrap());
+                if a_count > 1 {
+                    a.head = Some(DelSkip(a_count - 1));
+                } else {
+                    a.next();
+                }
+                b.next();
+            }
+            (Some(DelGroupAll), Some(DelSkip(b_count)))
+            | (Some(DelObject), Some(DelSkip(b_count))) => {
+                b_del.place(&a.head.clone().unwrap());
+                a.next();
+                if b_count > 1 {
+                    b.head = Some(DelSkip(b_count - 1));
+                } else {
+                    b.next();
+                }
+            }
+            (Some(DelWithGroup(_)), Some(DelMany(b_count))) => {
+                a_del.many(1);
+                a.next();
+                if b_count > 1 {
+                    b.head = Some(DelMany(b_count - 1));
+                } else {
+                    b.next();
+                }
+            }
+            (Some(DelMany(a_count)), Some(DelWithGroup(_))) => {
+                b_del.many(1);
+                if a_count > 1 {
+                    a.head = Some(DelMany(a_count - 1));
+                } else {
+                    a.next();
+                }
+                b.next();
+            }
+            (Some(DelWithGroup(_)), Some(DelGroupAll))
+            | (Some(DelWithGroup(_)), Some(DelObject)) => {
+                a_del.place(&b.head.clone().unwrap());
+                a.next();
+                b.next();
+            }
+            (Some(DelGroupAll), Some(DelWithGroup(_)))
+            | (Some(DelObject), Some(DelWithGroup(_))) => {
+                b_del.place(&a.head.clone().unwrap());
+                a.next();
+                b.next();
+            }
+            (Some(DelGroup(a_inner)), Some(DelMany(b_count))) => {
+                // Delete the contents which A's deletion left unwrapped.
+                if a_inner.skip_post_len() > 0 {
+                    a_del.many(a_inner.skip_post_len());
+                }
+                a.next();
+                if b_count > 1 {
+                    b.head = Some(DelMany(b_count - 1));
+                } else {
+                    b.next();
+                }
+            }
+            (Some(DelMany(a_count)), Some(DelGroup(b_inner))) => {
+                // Delete the contents which B's deletion left unwrapped.
+                if b_inner.skip_post_len() > 0 {
+                    b_del.many(b_inner.skip_post_len());
+                }
+                if a_count > 1 {
+                    a.head = Some(DelMany(a_count - 1));
+                } else {
+                    a.next();
+                }
+                b.next();
+            }
+            (Some(DelGroup(a_inner)), Some(DelGroupAll))
+            | (Some(DelGroup(a_inner)), Some(DelObject)) => {
+                if a_inner.skip_post_len() > 0 {
+                    a_del.many(a_inner.skip_post_len());
+                }
+                a.next();
+                b.next();
+            }
+            (Some(DelGroupAll), Some(DelGroup(b_inner)))
+            | (Some(DelObject), Some(DelGroup(b_inner))) => {
+                if b_inner.skip_post_len() > 0 {
+                    b_del.many(b_inner.skip_post_len());
+                }
+                a.next();
+                b.next();
+            }
+
+            // These pair text with groups, which the checks rule out.
             (None, _)
             | (_, None)
             | (Some(DelWithGroup(_)), Some(DelText(_)))
@@ -1510,27 +1811,43 @@ pub fn transform_del_del_inner<S: Schema>(
             | (Some(DelGroup(_)), Some(DelText(_)))
             | (Some(DelGroup(_)), Some(DelStyles(_, _)))
             | (Some(DelStyles(_, _)), Some(DelGroup(_)))
-            | (Some(DelText(_)), Some(DelGroup(_))) => {
+            | (Some(DelText(_)), Some(DelGroup(_)))
+            | (Some(DelText(_)), Some(DelGroupAll))
+            | (Some(DelText(_)), Some(DelObject))
+            | (Some(DelStyles(_, _)), Some(DelGroupAll))
+            | (Some(DelStyles(_, _)), Some(DelObject))
+            | (Some(DelGroupAll), Some(DelText(_)))
+            | (Some(DelObject), Some(DelText(_)))
+            | (Some(DelGroupAll), Some(DelStyles(_, _)))
+            | (Some(DelObject), Some(DelStyles(_, _))) => {
                 log_transform!("Not reachable: {:?}", unimplemented);
-                unreachable!();
+                return mismatch(OpErrorKind::Unsupported);
             }
         }
     }
 
     log_transform!("{}", BrightYellow.paint(format!("done")),);
+    Ok(())
 }
 
 pub fn transform_deletions<S: Schema>(
     avec: &DelSpan<S>,
     bvec: &DelSpan<S>,
 ) -> (DelSpan<S>, DelSpan<S>) {
+    try_transform_deletions(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn try_transform_deletions<S: Schema>(
+    avec: &DelSpan<S>,
+    bvec: &DelSpan<S>,
+) -> Result<(DelSpan<S>, DelSpan<S>), OpError> {
     let mut a_del = DelWriter::new();
     let mut b_del = DelWriter::new();
 
     let mut a = DelStepper::new(avec);
     let mut b = DelStepper::new(bvec);
 
-    transform_del_del_inner(&mut a_del, &mut b_del, &mut a, &mut b);
+    transform_del_del_inner(&mut a_del, &mut b_del, &mut a, &mut b)?;
 
     while !b.is_done() {
         log_transform!(
@@ -1572,7 +1889,7 @@ pub fn transform_deletions<S: Schema>(
     log_transform!("{}", BrightYellow.paint(format!("Result A: {:?}", a_res)));
     log_transform!("{}", BrightYellow.paint(format!("Result B: {:?}", b_res)));
 
-    (a_res, b_res)
+    Ok((a_res, b_res))
 }
 
 pub fn transform_add_del_inner<S: Schema>(
@@ -1580,7 +1897,7 @@ pub fn transform_add_del_inner<S: Schema>(
     addres: &mut AddSpan<S>,
     a: &mut AddStepper<S>,
     b: &mut DelStepper<S>,
-) {
+) -> Result<(), OpError> {
     while !b.is_done() && !a.is_done() {
         match b.get_head() {
             DelText(bcount) => match a.get_head() {
@@ -1602,11 +1919,24 @@ pub fn transform_add_del_inner<S: Schema>(
                         delres.place(&b.next().unwrap());
                     }
                 }
+                AddStyles(acount, a_styles) => {
+                    delres.place(&DelText(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddStyles(acount - bcount, a_styles));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelText(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
                 AddGroup(attrs, a_span) => {
                     let mut a_inner = AddStepper::new(&a_span);
                     let mut addres_inner: AddSpan<S> = vec![];
                     let mut delres_inner: DelSpan<S> = vec![];
-                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b);
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
                     if !a_inner.is_done() {
                         addres_inner.place(&a_inner.head.unwrap());
                         addres_inner.place_all(&a_inner.rest);
@@ -1617,7 +1947,7 @@ pub fn transform_add_del_inner<S: Schema>(
                 }
                 _unknown => {
                     log_transform!("Compare: {:?} {:?}", DelText(bcount), _unknown);
-                    panic!("Unimplemented or Unexpected");
+                    return mismatch(OpErrorKind::ExpectedText);
                 }
             },
             DelSkip(bcount) => match a.get_head() {
@@ -1654,7 +1984,7 @@ pub fn transform_add_del_inner<S: Schema>(
                         b.next();
                     }
                 }
-                AddWithGroup(..) => {
+                AddWithGroup(..) | AddAttrs(..) => {
                     addres.place(&a.next().unwrap());
                     delres.place(&DelSkip(1));
                     if bcount == 1 {
@@ -1667,7 +1997,7 @@ pub fn transform_add_del_inner<S: Schema>(
                     let mut a_inner = AddStepper::new(&a_span);
                     let mut addres_inner: AddSpan<S> = vec![];
                     let mut delres_inner: DelSpan<S> = vec![];
-                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b);
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
                     if !a_inner.is_done() {
                         addres_inner.place(&a_inner.head.unwrap());
                         addres_inner.place_all(&a_inner.rest);
@@ -1688,8 +2018,12 @@ pub fn transform_add_del_inner<S: Schema>(
                     let mut combined_styles = a_styles.clone();
                     combined_styles.remove(&b_styles);
 
-                    addres.place(&AddStyles(cmp::min(a_count, b_count), combined_styles));
-                    delres.place(&DelStyles(b_count, b_styles.clone())); // Not combined
+                    if combined_styles.is_empty() {
+                        addres.place(&AddSkip(cmp::min(a_count, b_count)));
+                    } else {
+                        addres.place(&AddStyles(cmp::min(a_count, b_count), combined_styles));
+                    }
+                    delres.place(&DelStyles(cmp::min(a_count, b_count), b_styles.clone())); // Not combined
                     if a_count > b_count {
                         a.head = Some(AddStyles(a_count - b_count, a_styles));
                         b.next();
@@ -1719,7 +2053,7 @@ pub fn transform_add_del_inner<S: Schema>(
                     let mut a_inner = AddStepper::new(&a_span);
                     let mut addres_inner: AddSpan<S> = vec![];
                     let mut delres_inner: DelSpan<S> = vec![];
-                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b);
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
                     if !a_inner.is_done() {
                         addres_inner.place(&a_inner.head.unwrap());
                         addres_inner.place_all(&a_inner.rest);
@@ -1728,11 +2062,11 @@ pub fn transform_add_del_inner<S: Schema>(
                     delres.place(&DelWithGroup(delres_inner));
                     a.next();
                 }
-                AddWithGroup(..) => panic!("Invalid DelStyles x AddWithGroup"),
+                AddWithGroup(..) | AddAttrs(..) => return mismatch(OpErrorKind::ExpectedText),
             },
             DelWithGroup(span) => match a.get_head() {
                 AddStyles(..) => {
-                    panic!("invalid transform DelWithGroup with AddStyles");
+                    return mismatch(OpErrorKind::ExpectedGroup);
                 }
                 AddText(_, avalue) => {
                     delres.place(&DelSkip(avalue.char_len()));
@@ -1751,15 +2085,23 @@ pub fn transform_add_del_inner<S: Schema>(
                     a.next();
                     b.next();
 
-                    let Op(del, ins) = transform_add_del(&insspan, &span);
+                    let Op(del, ins) = try_transform_add_del(&insspan, &span)?;
                     delres.place(&DelWithGroup(del));
                     addres.place(&AddWithGroup(ins));
                 }
+                AddAttrs(attrs, insspan) => {
+                    a.next();
+                    b.next();
+
+                    let Op(del, ins) = try_transform_add_del(&insspan, &span)?;
+                    delres.place(&DelWithGroup(del));
+                    addres.place(&AddAttrs(attrs, ins));
+                }
                 AddGroup(attrs, a_span) => {
                     let mut a_inner = AddStepper::new(&a_span);
                     let mut addres_inner: AddSpan<S> = vec![];
                     let mut delres_inner: DelSpan<S> = vec![];
-                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b);
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
                     if !a_inner.is_done() {
                         addres_inner.place(&a_inner.head.unwrap());
                         addres_inner.place_all(&a_inner.rest);
@@ -1772,7 +2114,7 @@ pub fn transform_add_del_inner<S: Schema>(
             DelGroup(span) => {
                 match a.get_head() {
                     AddStyles(..) => {
-                        panic!("invalid transform DelGroup with AddStyles");
+                        return mismatch(OpErrorKind::ExpectedGroup);
                     }
                     AddText(_, avalue) => {
                         delres.place(&DelSkip(avalue.char_len()));
@@ -1789,7 +2131,9 @@ pub fn transform_add_del_inner<S: Schema>(
                             a.next();
                         }
                     }
-                    AddWithGroup(ins_span) => {
+                    // The group is unwrapped by the deletion, so any change to
+                    // its attributes is lost along with it.
+                    AddWithGroup(ins_span) | AddAttrs(_, ins_span) => {
                         // "Delall" transform hack to avoid fully deleted elements that
                         // leave their content unwrapped. Because one side deletes the
                         // group, we can't recreate it (because we have no knowledge of
@@ -1809,7 +2153,8 @@ pub fn transform_add_del_inner<S: Schema>(
                                         &AddSkip(value) => {
                                             del.place(&DelSkip(value));
                                         }
-                                        &AddWithGroup(ref ins_span) => {
+                                        &AddWithGroup(ref ins_span)
+                                        | &AddAttrs(_, ref ins_span) => {
                                             del.place(&DelWithGroup(unadd(ins_span)));
                                         }
                                         &AddGroup(ref _attrs, ref ins_span) => {
@@ -1821,7 +2166,7 @@ pub fn transform_add_del_inner<S: Schema>(
                             }
 
                             // Undo any additions, then apply the complete deletion.
-                            let del_span = compose::compose_del_del(&unadd(&ins_span), &span);
+                            let del_span = compose::try_compose_del_del(&unadd(&ins_span), &span)?;
                             delres.place(&DelGroup(del_span));
                         } else {
                             let mut a_inner = AddStepper::new(&ins_span);
@@ -1833,7 +2178,7 @@ pub fn transform_add_del_inner<S: Schema>(
                                 &mut addres_inner,
                                 &mut a_inner,
                                 &mut b_inner,
-                            );
+                            )?;
 
                             // TODO should this be part of the top-level resolution for transform_add_del
                             // Finish consuming the Del or Add component
@@ -1884,7 +2229,7 @@ pub fn transform_add_del_inner<S: Schema>(
                             &mut addres_inner,
                             &mut a_inner,
                             b,
-                        );
+                        )?;
                         if !a_inner.is_done() {
                             addres_inner.place(&a_inner.head.unwrap());
                             addres_inner.place_all(&a_inner.rest);
@@ -1904,113 +2249,117 @@ pub fn transform_add_del_inner<S: Schema>(
                         a.next();
                     }
                 }
-            } // DelObject => {
-              //     unimplemented!();
-              // }
-              // DelMany(bcount) => {
-              //     match a.get_head() {
-              //         AddText(avalue) => {
-              //             addres.place(&AddText(avalue.clone()));
-              //             delres.place(&DelSkip(avalue.len()));
-              //             a.next();
-              //         }
-              //         AddSkip(acount) => {
-              //             if bcount < acount {
-              //                 a.head = Some(AddSkip(acount - bcount));
-              //                 delres.place(&b.next().unwrap());
-              //             } else if bcount > acount {
-              //                 a.next();
-              //                 delres.place(&DelMany(acount));
-              //                 b.head = Some(DelMany(bcount - acount));
-              //             } else {
-              //                 a.next();
-              //                 delres.place(&b.next().unwrap());
-              //             }
-              //         }
-              //         AddGroup(attrs, a_span) => {
-              //             let mut a_inner = AddStepper::new(&a_span);
-              //             let mut addres_inner: AddSpan = vec![];
-              //             let mut delres_inner: DelSpan = vec![];
-              //             transform_add_del_inner(
-              //                 &mut delres_inner,
-              //                 &mut addres_inner,
-              //                 &mut a_inner,
-              //                 b,
-              //             );
-              //             if !a_inner.is_done() {
-              //                 addres_inner.place(&a_inner.head.unwrap());
-              //                 addres_inner.place_all(&a_inner.rest);
-              //             }
-              //             addres.place(&AddGroup(attrs, addres_inner));
-              //             delres.place(&DelWithGroup(delres_inner));
-              //             a.next();
-              //         }
-              //         AddWithGroup(ins_span) => {
-              //             if bcount > 1 {
-              //                 delres.place(&DelMany(1));
-              //                 b.head = Some(DelMany(bcount - 1));
-              //                 a.next();
-              //             } else {
-              //                 delres.place(&b.next().unwrap());
-              //                 a.next();
-              //             }
-              //         }
-              //     }
-              // }
-              // DelGroupAll => {
-              //     match a.get_head() {
-              //         AddText(avalue) => {
-              //             delres.place(&DelSkip(avalue.chars().count()));
-              //             addres.place(&a.next().unwrap());
-              //         }
-              //         AddSkip(acount) => {
-              //             delres.place(&b.next().unwrap());
-              //             if acount > 1 {
-              //                 a.head = Some(AddSkip(acount - 1));
-              //             } else {
-              //                 a.next();
-              //             }
-              //         }
-              //         AddWithGroup(insspan) => {
-              //             a.next();
-              //             delres.place(&b.next().unwrap());
-              //         }
-              //         AddGroup(attrs, ins_span) => {
-              //             let mut a_inner = AddStepper::new(&ins_span);
-              //             let mut delres_inner: DelSpan = vec![];
-              //             let mut addres_inner: AddSpan = vec![];
-              //             transform_add_del_inner(
-              //                 &mut delres_inner,
-              //                 &mut addres_inner,
-              //                 &mut a_inner,
-              //                 b,
-              //             );
-              //             if !a_inner.is_done() {
-              //                 addres_inner.place(&a_inner.head.unwrap());
-              //                 addres_inner.place_all(&a_inner.rest);
-              //             }
-              //             addres.place(&AddGroup(attrs, addres_inner));
-              //             delres.place(&DelWithGroup(delres_inner));
-
-              //             log_transform!("NOW   ->{:?}\n   ->{:?}", addres, delres);
-              //             a.next();
-              //         }
-              //     }
-              // }
+            }
+            DelMany(bcount) => match a.get_head() {
+                AddText(a_styles, avalue) => {
+                    delres.place(&DelSkip(avalue.char_len()));
+                    addres.place(&AddText(a_styles, avalue));
+                    a.next();
+                }
+                AddSkip(acount) => {
+                    delres.place(&DelMany(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddSkip(acount - bcount));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelMany(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddStyles(acount, a_styles) => {
+                    delres.place(&DelMany(cmp::min(acount, bcount)));
+                    if acount > bcount {
+                        a.head = Some(AddStyles(acount - bcount, a_styles));
+                        b.next();
+                    } else if acount < bcount {
+                        a.next();
+                        b.head = Some(DelMany(bcount - acount));
+                    } else {
+                        a.next();
+                        b.next();
+                    }
+                }
+                AddGroup(attrs, a_span) => {
+                    let mut a_inner = AddStepper::new(&a_span);
+                    let mut addres_inner: AddSpan<S> = vec![];
+                    let mut delres_inner: DelSpan<S> = vec![];
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
+                    if !a_inner.is_done() {
+                        addres_inner.place(&a_inner.head.unwrap());
+                        addres_inner.place_all(&a_inner.rest);
+                    }
+                    addres.place(&AddGroup(attrs, addres_inner));
+                    delres.place(&DelWithGroup(delres_inner));
+                    a.next();
+                }
+                AddWithGroup(..) | AddAttrs(..) => {
+                    // The group is deleted along with anything inserted into it.
+                    delres.place(&DelMany(1));
+                    a.next();
+                    if bcount > 1 {
+                        b.head = Some(DelMany(bcount - 1));
+                    } else {
+                        b.next();
+                    }
+                }
+            },
+            DelGroupAll | DelObject => match a.get_head() {
+                AddStyles(..) => {
+                    return mismatch(OpErrorKind::ExpectedGroup);
+                }
+                AddText(_, avalue) => {
+                    delres.place(&DelSkip(avalue.char_len()));
+                    addres.place(&a.next().unwrap());
+                }
+                AddSkip(acount) => {
+                    delres.place(&b.next().unwrap());
+                    if acount > 1 {
+                        a.head = Some(AddSkip(acount - 1));
+                    } else {
+                        a.next();
+                    }
+                }
+                AddWithGroup(..) | AddAttrs(..) => {
+                    // The group is deleted along with anything inserted into it.
+                    a.next();
+                    delres.place(&b.next().unwrap());
+                }
+                AddGroup(attrs, ins_span) => {
+                    let mut a_inner = AddStepper::new(&ins_span);
+                    let mut delres_inner: DelSpan<S> = vec![];
+                    let mut addres_inner: AddSpan<S> = vec![];
+                    transform_add_del_inner(&mut delres_inner, &mut addres_inner, &mut a_inner, b)?;
+                    if !a_inner.is_done() {
+                        addres_inner.place(&a_inner.head.unwrap());
+                        addres_inner.place_all(&a_inner.rest);
+                    }
+                    addres.place(&AddGroup(attrs, addres_inner));
+                    delres.place(&DelWithGroup(delres_inner));
+                    a.next();
+                }
+            },
         }
     }
+    Ok(())
 }
 
 /// Transforms a insertion preceding a deletion into a deletion preceding an insertion.
 /// After this, sequential deletions and insertions can be composed together in one operation.
 pub fn transform_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Op<S> {
+    try_transform_add_del(avec, bvec).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn try_transform_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Result<Op<S>, OpError> {
     let mut delres: DelSpan<S> = Vec::with_capacity(avec.len() + bvec.len());
     let mut addres: AddSpan<S> = Vec::with_capacity(avec.len() + bvec.len());
 
     let mut a = AddStepper::new(avec);
     let mut b = DelStepper::new(bvec);
 
-    transform_add_del_inner(&mut delres, &mut addres, &mut a, &mut b);
+    transform_add_del_inner(&mut delres, &mut addres, &mut a, &mut b)?;
 
     if !b.is_done() {
         delres.place_all(&b.into_span());
@@ -2022,18 +2371,23 @@ pub fn transform_add_del<S: Schema>(avec: &AddSpan<S>, bvec: &DelSpan<S>) -> Op<
         addres.place_all(&rest);
     }
 
-    Op(delres, addres)
+    Ok(Op(delres, addres))
 }
 
 /// Transform two operations according to a schema.
 pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
+    try_transform(a, b).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Transforms `a` and `b`, or returns how they didn't fit together.
+pub(crate) fn try_transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> Result<(Op<S>, Op<S>), OpError> {
     // Transform deletions A and B against each other to get delA` and delB`.
     log_transform!(" # transform[1] transform_deletions");
     log_transform!(" a_del   {:?}", a.0);
     log_transform!(" b_del   {:?}", b.0);
     log_transform!();
 
-    let (a_del_0, b_del_0) = transform_deletions(&a.0, &b.0);
+    let (a_del_0, b_del_0) = try_transform_deletions(&a.0, &b.0)?;
     log_transform!(" == a_del_0 {:?}", a_del_0);
     log_transform!(" == b_del_0 {:?}", b_del_0);
     log_transform!();
@@ -2045,7 +2399,7 @@ pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
     log_transform!(" a_ins   {:?}", a.1);
     log_transform!(" a_del_0 {:?}", a_del_0);
     log_transform!(" ~ transform_add_del()");
-    let Op(a_del_1, a_ins_1) = transform_add_del(&a.1, &a_del_0);
+    let Op(a_del_1, a_ins_1) = try_transform_add_del(&a.1, &a_del_0)?;
     log_transform!(" == a_del_1 {:?}", a_del_1);
     log_transform!(" == a_ins_1 {:?}", a_ins_1);
     log_transform!();
@@ -2054,7 +2408,7 @@ pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
     log_transform!(" b_ins   {:?}", b.1);
     log_transform!(" b_del_0 {:?}", b_del_0);
     log_transform!(" ~ transform_add_del()");
-    let Op(b_del_1, b_ins_1) = transform_add_del(&b.1, &b_del_0);
+    let Op(b_del_1, b_ins_1) = try_transform_add_del(&b.1, &b_del_0)?;
     log_transform!(" == b_del_1 {:?}", b_del_1);
     log_transform!(" == b_ins_1 {:?}", b_ins_1);
     log_transform!();
@@ -2066,7 +2420,7 @@ pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
     log_transform!(" # transform[4] transform_insertions");
     log_transform!(" a_ins_1 {:?}", a_ins_1);
     log_transform!(" b_ins_1 {:?}", b_ins_1);
-    let (Op(a_del_2, a_ins_2), Op(b_del_2, b_ins_2)) = transform_insertions::<S>(&a_ins_1, &b_ins_1);
+    let (Op(a_del_2, a_ins_2), Op(b_del_2, b_ins_2)) = try_transform_insertions::<S>(&a_ins_1, &b_ins_1)?;
     log_transform!(" == a_del_2 {:?}", a_del_2);
     log_transform!(" == a_ins_2 {:?}", a_ins_2);
     log_transform!(" == b_del_2 {:?}", b_del_2);
@@ -2077,13 +2431,13 @@ pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
     log_transform!(" # transform[5] compose_del_del");
     log_transform!(" a_del_1 {:?}", a_del_1);
     log_transform!(" a_del_2 {:?}", a_del_2);
-    let a_del_3 = compose::compose_del_del(&a_del_1, &a_del_2);
+    let a_del_3 = compose::try_compose_del_del(&a_del_1, &a_del_2)?;
     log_transform!(" == a_del_3 {:?}", a_del_3);
     log_transform!();
     log_transform!(" # transform[6] compose_del_del");
     log_transform!(" b_del_1 {:?}", b_del_1);
     log_transform!(" b_del_2 {:?}", b_del_2);
-    let b_del_3 = compose::compose_del_del(&b_del_1, &b_del_2);
+    let b_del_3 = compose::try_compose_del_del(&b_del_1, &b_del_2)?;
     log_transform!(" == b_del_3 {:?}", b_del_3);
     log_transform!();
 
@@ -2101,5 +2455,193 @@ pub fn transform<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>) {
     log_transform!(" =b_ins_2  {:?}", b_ins_2);
     log_transform!();
 
-    (Op(a_del_3, a_ins_2), Op(b_del_3, b_ins_2))
+    Ok((Op(a_del_3, a_ins_2), Op(b_del_3, b_ins_2)))
+}
+
+/// Decides which side of an insertion a cursor ends up on when the insertion
+/// is made exactly at the cursor's position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gravity {
+    /// The cursor stays before the inserted content.
+    Left,
+    /// The cursor moves past the inserted content.
+    Right,
+}
+
+/// A cursor at a single level of the document: either it descends into the
+/// group at its position, or it points at the element at its position.
+#[derive(Clone, Debug)]
+enum CurTarget {
+    Enter(CurSpan),
+    Point(CurElement),
+}
+
+fn cursor_split(cur: &CurSpan) -> (usize, CurTarget) {
+    let mut index = 0;
+    for elem in cur {
+        match *elem {
+            CurSkip(count) => index += count,
+            CurWithGroup(ref span) => return (index, CurTarget::Enter(span.clone())),
+            CurGroup | CurChar => return (index, CurTarget::Point(elem.clone())),
+        }
+    }
+    panic!("Cursor does not point at an element");
+}
+
+fn cursor_join(index: usize, target: CurTarget) -> CurSpan {
+    let mut res: CurSpan = vec![];
+    if index > 0 {
+        res.place(&CurSkip(index));
+    }
+    match target {
+        CurTarget::Enter(span) => res.place(&CurWithGroup(span)),
+        CurTarget::Point(elem) => res.place(&elem),
+    }
+    res
+}
+
+/// The element the cursor ultimately points at.
+fn cursor_point(target: &CurTarget) -> CurElement {
+    match *target {
+        CurTarget::Enter(ref span) => cursor_point(&cursor_split(span).1),
+        CurTarget::Point(ref elem) => elem.clone(),
+    }
+}
+
+fn transform_cursor_del<S: Schema>(cur: &CurSpan, del: &DelSpan<S>) -> CurSpan {
+    let (index, target) = cursor_split(cur);
+
+    // Position of the cursor's level before and after the deletion.
+    let mut pos = 0;
+    let mut out = 0;
+    for elem in del {
+        match *elem {
+            DelSkip(count) | DelStyles(count, _) => {
+                if index < pos + count {
+                    return cursor_join(out + (index - pos), target);
+                }
+                pos += count;
+                out += count;
+            }
+            DelText(count) | DelMany(count) => {
+                if index < pos + count {
+                    // The element under the cursor was deleted, so the
+                    // cursor now points at whatever follows the deletion.
+                    return cursor_join(out, CurTarget::Point(cursor_point(&target)));
+                }
+                pos += count;
+            }
+            DelGroupAll | DelObject => {
+                if index == pos {
+                    return cursor_join(out, CurTarget::Point(cursor_point(&target)));
+                }
+                pos += 1;
+            }
+            DelWithGroup(ref span) => {
+                if index == pos {
+                    return match target {
+                        CurTarget::Enter(ref inner) => {
+                            cursor_join(out, CurTarget::Enter(transform_cursor_del(inner, span)))
+                        }
+                        CurTarget::Point(..) => cursor_join(out, target),
+                    };
+                }
+                pos += 1;
+                out += 1;
+            }
+            DelGroup(ref span) => {
+                if index == pos {
+                    return match target {
+                        CurTarget::Enter(ref inner) => {
+                            // The group is unwrapped, so the cursor moves up
+                            // to this level alongside the group's contents.
+                            let (inner_index, inner_target) =
+                                cursor_split(&transform_cursor_del(inner, span));
+                            cursor_join(out + inner_index, inner_target)
+                        }
+                        CurTarget::Point(..) => cursor_join(out, target),
+                    };
+                }
+                pos += 1;
+                out += span.skip_post_len();
+            }
+        }
+    }
+
+    cursor_join(out + (index - pos), target)
+}
+
+fn transform_cursor_add<S: Schema>(cur: &CurSpan, add: &AddSpan<S>, gravity: Gravity) -> CurSpan {
+    let (index, target) = cursor_split(cur);
+
+    // Gravity only applies to a cursor pointing at this level. A cursor inside
+    // of a group always stays with that group.
+    let stay = gravity == Gravity::Left
+        && match target {
+            CurTarget::Point(..) => true,
+            CurTarget::Enter(..) => false,
+        };
+
+    // Position of the cursor's level before and after the addition.
+    let mut pos = 0;
+    let mut out = 0;
+    for elem in add {
+        match *elem {
+            AddSkip(count) | AddStyles(count, _) => {
+                if index < pos + count {
+                    return cursor_join(out + (index - pos), target);
+                }
+                pos += count;
+                out += count;
+            }
+            AddText(_, ref text) => {
+                if index == pos && stay {
+                    return cursor_join(out, CurTarget::Point(CurChar));
+                }
+                out += text.char_len();
+            }
+            AddGroup(_, ref span) => {
+                let len = span.skip_pre_len();
+                if index < pos + len {
+                    // The element under the cursor was wrapped by the new
+                    // group, so the cursor moves inside of it.
+                    let inner = cursor_join(index - pos, target);
+                    return cursor_join(
+                        out,
+                        CurTarget::Enter(transform_cursor_add(&inner, span, gravity)),
+                    );
+                }
+                if len == 0 && index == pos && stay {
+                    return cursor_join(out, CurTarget::Point(CurGroup));
+                }
+                pos += len;
+                out += 1;
+            }
+            AddWithGroup(ref span) | AddAttrs(_, ref span) => {
+                if index == pos {
+                    return match target {
+                        CurTarget::Enter(ref inner) => cursor_join(
+                            out,
+                            CurTarget::Enter(transform_cursor_add(inner, span, gravity)),
+                        ),
+                        CurTarget::Point(..) => cursor_join(out, target),
+                    };
+                }
+                pos += 1;
+                out += 1;
+            }
+        }
+    }
+
+    cursor_join(out + (index - pos), target)
+}
+
+/// Transforms a cursor through an operation, returning a cursor that points
+/// at the same place in the document after the operation is applied. If the
+/// element under the cursor is deleted, the cursor moves to whatever follows
+/// it. `gravity` decides where the cursor ends up relative to content that
+/// is inserted exactly at its position.
+pub fn transform_cursor<S: Schema>(cur: &CurSpan, op: &Op<S>, gravity: Gravity) -> CurSpan {
+    let &Op(ref del, ref add) = op;
+    transform_cursor_add(&transform_cursor_del(cur, del), add, gravity)
 }
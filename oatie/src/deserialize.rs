@@ -99,6 +99,9 @@ pub mod v1 {
         DelChars(usize),
         DelGroup(DelSpan),
         DelStyles(usize, Vec<Style>),
+        DelGroupAll,
+        DelMany(usize),
+        DelObject,
     }
 
     pub type AddSpan = Vec<AddElement>;
@@ -110,6 +113,7 @@ pub mod v1 {
         AddChars(DocString),
         AddGroup(HashMap<String, String>, AddSpan),
         AddStyles(usize, Vec<Style>),
+        AddAttrs(HashMap<String, String>, AddSpan),
     }
 
     fn update_attrs(input: HashMap<String, String>) -> Result<crate::rtf::Attrs, Error> {
@@ -125,10 +129,14 @@ pub mod v1 {
                 "h4" => crate::rtf::Attrs::Header(4),
                 "h5" => crate::rtf::Attrs::Header(5),
                 "h6" => crate::rtf::Attrs::Header(6),
-                "pre" => crate::rtf::Attrs::Code,
+                "pre" => crate::rtf::Attrs::Code(None),
                 "html" => crate::rtf::Attrs::Html,
                 "hr" => crate::rtf::Attrs::Rule,
                 "bullet" => crate::rtf::Attrs::ListItem,
+                "ordered" => crate::rtf::Attrs::OrderedListItem(
+                    input.get("start").and_then(|x| x.parse().ok()).unwrap_or(1),
+                ),
+                "blockquote" => crate::rtf::Attrs::BlockQuote,
                 "caret" => crate::rtf::Attrs::Caret {
                     client_id: input
                         .get("client")
@@ -161,6 +169,17 @@ pub mod v1 {
         Ok(crate::rtf::StyleSet::from(set))
     }
 
+    /// Like `update_styles`, but keeps the URL of a link.
+    fn update_styles_map(
+        styles: HashMap<Style, Option<String>>,
+    ) -> Result<crate::rtf::StyleSet, Error> {
+        let mut set = update_styles(styles.keys().cloned().collect())?;
+        if let Some(Some(url)) = styles.get(&Style::Link) {
+            set.insert(crate::rtf::RtfStyle::Link(url.to_owned()));
+        }
+        Ok(set)
+    }
+
     fn update_docspan(input: DocSpan) -> Result<crate::doc::DocSpan<crate::rtf::RtfSchema>, Error> {
         let mut output = vec![];
         for item in input {
@@ -169,7 +188,7 @@ pub mod v1 {
                     crate::doc::DocGroup(update_attrs(attrs)?, update_docspan(span)?)
                 }
                 DocElement::DocChars(string) => crate::doc::DocText(
-                    update_styles(string.1.into_iter().map(|(k, _v)| k).collect())?,
+                    update_styles_map(string.1)?,
                     crate::doc::DocString::from_string(string.0),
                 ),
             });
@@ -184,7 +203,7 @@ pub mod v1 {
                 AddElement::AddSkip(skip) => crate::doc::AddSkip(skip),
                 AddElement::AddWithGroup(span) => crate::doc::AddWithGroup(update_addspan(span)?),
                 AddElement::AddChars(string) => crate::doc::AddText(
-                    update_styles(string.1.into_iter().map(|(k, _v)| k).collect())?,
+                    update_styles_map(string.1)?,
                     crate::doc::DocString::from_string(string.0),
                 ),
                 AddElement::AddGroup(attrs, span) => {
@@ -193,6 +212,9 @@ pub mod v1 {
                 AddElement::AddStyles(skip, styles) => {
                     crate::doc::AddStyles(skip, update_styles(styles)?)
                 }
+                AddElement::AddAttrs(attrs, span) => {
+                    crate::doc::AddAttrs(update_attrs(attrs)?, update_addspan(span)?)
+                }
             });
         }
         Ok(output)
@@ -209,6 +231,9 @@ pub mod v1 {
                 DelElement::DelStyles(skip, styles) => {
                     crate::doc::DelStyles(skip, update_styles(styles)?)
                 }
+                DelElement::DelGroupAll => crate::doc::DelGroupAll,
+                DelElement::DelMany(count) => crate::doc::DelMany(count),
+                DelElement::DelObject => crate::doc::DelObject,
             });
         }
         Ok(output)
@@ -322,6 +347,9 @@ pub mod v2 {
         DelChars(usize),
         DelGroup(DelSpan),
         DelStyles(usize, Vec<Style>),
+        DelGroupAll,
+        DelMany(usize),
+        DelObject,
     }
 
     pub type AddSpan = Vec<AddElement>;
@@ -333,6 +361,7 @@ pub mod v2 {
         AddChars(DocString, #[serde(default)] Vec<Style>),
         AddGroup(HashMap<String, String>, AddSpan),
         AddStyles(usize, Vec<Style>),
+        AddAttrs(HashMap<String, String>, AddSpan),
     }
 
     fn update_attrs(input: HashMap<String, String>) -> Result<crate::rtf::Attrs, Error> {
@@ -348,10 +377,14 @@ pub mod v2 {
                 "h4" => crate::rtf::Attrs::Header(4),
                 "h5" => crate::rtf::Attrs::Header(5),
                 "h6" => crate::rtf::Attrs::Header(6),
-                "pre" => crate::rtf::Attrs::Code,
+                "pre" => crate::rtf::Attrs::Code(None),
                 "html" => crate::rtf::Attrs::Html,
                 "hr" => crate::rtf::Attrs::Rule,
                 "bullet" => crate::rtf::Attrs::ListItem,
+                "ordered" => crate::rtf::Attrs::OrderedListItem(
+                    input.get("start").and_then(|x| x.parse().ok()).unwrap_or(1),
+                ),
+                "blockquote" => crate::rtf::Attrs::BlockQuote,
                 "caret" => crate::rtf::Attrs::Caret {
                     client_id: input
                         .get("client")
@@ -416,6 +449,9 @@ pub mod v2 {
                 AddElement::AddStyles(skip, styles) => {
                     crate::doc::AddStyles(skip, update_styles(styles)?)
                 }
+                AddElement::AddAttrs(attrs, span) => {
+                    crate::doc::AddAttrs(update_attrs(attrs)?, update_addspan(span)?)
+                }
             });
         }
         Ok(output)
@@ -432,6 +468,9 @@ pub mod v2 {
                 DelElement::DelStyles(skip, styles) => {
                     crate::doc::DelStyles(skip, update_styles(styles)?)
                 }
+                DelElement::DelGroupAll => crate::doc::DelGroupAll,
+                DelElement::DelMany(count) => crate::doc::DelMany(count),
+                DelElement::DelObject => crate::doc::DelObject,
             });
         }
         Ok(output)
@@ -488,10 +527,45 @@ pub fn doc_ron(input: &str) -> Result<crate::doc::Doc<crate::rtf::RtfSchema>, Er
     }
 }
 
+/// Rewrites V3 JSON written before code blocks had a language, where a code
+/// block's attributes were the bare string "Code".
+fn update_code_attrs(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, mut value)| {
+                    if key == "DocGroup" {
+                        if let Some(attrs) = value.get_mut(0) {
+                            if *attrs == Value::String("Code".to_string()) {
+                                let mut code = serde_json::Map::new();
+                                code.insert("Code".to_string(), Value::Null);
+                                *attrs = Value::Object(code);
+                            }
+                        }
+                    }
+                    (key, update_code_attrs(value))
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(update_code_attrs).collect()),
+        value => value,
+    }
+}
+
 pub fn doc_json(input: &str) -> Result<crate::doc::Doc<crate::rtf::RtfSchema>, Error> {
     match serde_json::from_str::<crate::doc::Doc<crate::rtf::RtfSchema>>(&input) {
         Ok(value) => Ok(value),
         Err(err) => {
+            // Try V3 encoding from before code blocks had a language.
+            if let Ok(value) = serde_json::from_str(&input)
+                .map(update_code_attrs)
+                .and_then(serde_json::from_value::<crate::doc::DocSpan<crate::rtf::RtfSchema>>)
+            {
+                return Ok(Doc(value));
+            }
+
             // Try V2 encoding.
             if let Ok(value) = v2::docspan_json(&input) {
                 return Ok(Doc(value));
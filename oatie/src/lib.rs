@@ -44,6 +44,7 @@ mod core;
 mod parse;
 pub mod deserialize;
 pub mod normalize;
+pub mod random;
 pub mod rtf;
 pub mod stepper;
 pub mod transform_test;
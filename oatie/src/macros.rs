@@ -20,6 +20,9 @@ macro_rules! doc {
             $crate::doc::DocGroup($b, doc![ @span $( $v )* ])
         }
     };
+    ( @span ) => {
+        vec![]
+    };
     ( @span $( $i:ident ( $( $v:tt )+ ) ),+ $(,)* ) => {
         vec![
             $( doc!(@kind $i $( $v )* , ) ),*
@@ -59,6 +62,11 @@ macro_rules! add_span {
             AddGroup($b, add_span![ $( $v )* ])
         }
     };
+    ( @kind AddAttrs $b:expr , [ $( $v:tt )* ] $(,)* ) => {
+        {
+            AddAttrs($b, add_span![ $( $v )* ])
+        }
+    };
     ( ) => {
         vec![]
     };
@@ -87,6 +95,12 @@ macro_rules! del_span {
     ( @kind DelGroupAll $(,)* ) => {
         DelGroupAll
     };
+    ( @kind DelMany $b:expr $(,)* ) => {
+        DelMany($b)
+    };
+    ( @kind DelObject $(,)* ) => {
+        DelObject
+    };
     ( ) => {
         vec![]
     };
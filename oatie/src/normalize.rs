@@ -16,6 +16,12 @@ fn normalize_add_element<S: Schema>(elem: AddElement<S>) -> AddElement<S> {
                 AddWithGroup(span)
             }
         }
+        AddAttrs(attrs, span) => {
+            // Changing attributes is meaningful even if the inner span is
+            // nothing but skips, so there is no shortcut.
+            let span = normalize_add_span(span, true);
+            AddAttrs(attrs, span)
+        }
         _ => elem,
     }
 }
@@ -53,10 +59,28 @@ fn normalize_del_element<S: Schema>(elem: DelElement<S>) -> DelElement<S> {
     }
 }
 
+/// The number of elements a deletion removes whole, if that's all it does.
+fn whole_count<S: Schema>(elem: &DelElement<S>) -> Option<usize> {
+    match *elem {
+        DelMany(count) => Some(count),
+        DelGroupAll => Some(1),
+        _ => None,
+    }
+}
+
 fn normalize_del_span<S: Schema>(del: DelSpan<S>, trim_last: bool) -> DelSpan<S> {
     let mut ret: DelSpan<S> = vec![];
     for elem in del.into_iter() {
-        ret.place(&normalize_del_element(elem));
+        let elem = normalize_del_element(elem);
+
+        // A run of elements deleted whole is written as a single DelMany.
+        if let (Some(count), Some(last)) = (whole_count(&elem), ret.last_mut()) {
+            if let Some(last_count) = whole_count(last) {
+                *last = DelMany(last_count + count);
+                continue;
+            }
+        }
+        ret.place(&elem);
     }
     if trim_last {
         if let Some(&DelSkip(..)) = ret.last() {
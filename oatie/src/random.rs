@@ -1,97 +1,728 @@
-use super::*;
-use super::compose::*;
+//! Random documents and operations for property testing.
+//!
+//! `random_doc` and `random_op` generate valid `RtfSchema` documents and
+//! operations against them. A `Case` bundles a document with the operations a
+//! `Property` is checked against; when a case fails, `shrink` reduces it to a
+//! minimal failing case, and `test_spec_ron` writes it out in a form the
+//! runner in `transform_test` can load.
+
+use super::apply::apply_delete;
 use super::doc::*;
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
+use super::normalize::normalize;
+use super::rtf::*;
+use super::transform_test::{
+    apply_test_ron,
+    compose_test_ron,
+    transform_test_ron,
+};
+use super::validate::validate_doc;
+use failure::Error;
+use rand::Rng;
+use std::panic;
+
+const CLIENT_IDS: &[&str] = &["left", "right"];
+
+fn random_styles<R: Rng>(rng: &mut R) -> StyleSet {
+    let mut styles = StyleSet::new();
+    if rng.gen_weighted_bool(3) {
+        styles.insert(RtfStyle::Bold);
+    }
+    if rng.gen_weighted_bool(3) {
+        styles.insert(RtfStyle::Italic);
+    }
+    if rng.gen_weighted_bool(5) {
+        styles.insert(RtfStyle::Code);
+    }
+    if rng.gen_weighted_bool(5) {
+        styles.insert(RtfStyle::Strikethrough);
+    }
+    if rng.gen_weighted_bool(8) {
+        styles.insert(RtfStyle::Link(format!(
+            "https://{}.com/",
+            random_text(rng).as_str()
+        )));
+    }
+    styles
+}
 
-/// Given a document span, create a random Add operation that can be applied
-/// to the span.
-pub fn random_add_span(input: &DocSpan) -> AddSpan {
-    let mut rng = thread_rng();
+fn random_nonempty_styles<R: Rng>(rng: &mut R) -> StyleSet {
+    let mut styles = random_styles(rng);
+    if styles.is_empty() {
+        styles.insert(RtfStyle::Bold);
+    }
+    styles
+}
 
-    let mut res: AddSpan = vec![];
-    for elem in input {
+fn random_text<R: Rng>(rng: &mut R) -> DocString {
+    let len = rng.gen_range(1, 5);
+    let text = (0..len)
+        .map(|_| *rng.choose(b"abcdefgh").unwrap() as char)
+        .collect::<String>();
+    DocString::from_string(text)
+}
+
+fn random_block_attrs<R: Rng>(rng: &mut R) -> Attrs {
+    match rng.gen_range(0, 6) {
+        0 => Attrs::Header(rng.gen_range(1, 7)),
+        1 => Attrs::Code(if rng.gen() {
+            Some("rust".to_string())
+        } else {
+            None
+        }),
+        _ => Attrs::Para,
+    }
+}
+
+fn random_list_attrs<R: Rng>(rng: &mut R) -> Attrs {
+    match rng.gen_range(0, 4) {
+        0 => Attrs::OrderedListItem(rng.gen_range(1, 3)),
+        1 => Attrs::TaskListItem { checked: rng.gen() },
+        _ => Attrs::ListItem,
+    }
+}
+
+fn random_caret<R: Rng>(rng: &mut R) -> Attrs {
+    Attrs::Caret {
+        client_id: rng.choose(CLIENT_IDS).unwrap().to_string(),
+        focus: rng.gen(),
+    }
+}
+
+fn random_image<R: Rng>(rng: &mut R) -> Attrs {
+    Attrs::Image {
+        src: format!("{}.png", random_text(rng).as_str()),
+        alt: random_text(rng).to_string(),
+    }
+}
+
+fn random_inlines<R: Rng>(rng: &mut R) -> DocSpan<RtfSchema> {
+    let mut span: DocSpan<RtfSchema> = vec![];
+    for _ in 0..rng.gen_range(0, 4) {
+        if rng.gen_weighted_bool(4) {
+            span.place(&DocGroup(random_caret(rng), vec![]));
+        } else if rng.gen_weighted_bool(8) {
+            span.place(&DocGroup(random_image(rng), vec![]));
+        } else {
+            span.place(&DocText(random_styles(rng), random_text(rng)));
+        }
+    }
+    span
+}
+
+/// Generates an HTML block, which holds its source as unstyled text.
+fn random_html<R: Rng>(rng: &mut R) -> DocElement<RtfSchema> {
+    let html = format!("<div>{}</div>", random_text(rng).as_str());
+    DocGroup(
+        Attrs::Html,
+        vec![DocText(StyleSet::new(), DocString::from_string(html))],
+    )
+}
+
+/// Generates a table with rows of the same number of cells, each holding a
+/// paragraph.
+fn random_table<R: Rng>(rng: &mut R) -> DocElement<RtfSchema> {
+    let columns = rng.gen_range(1, 4);
+    let rows = (0..rng.gen_range(1, 4))
+        .map(|_| {
+            let cells = (0..columns)
+                .map(|_| {
+                    let para = DocGroup(Attrs::Para, random_inlines(rng));
+                    DocGroup(Attrs::TableCell, vec![para])
+                })
+                .collect();
+            DocGroup(Attrs::TableRow, cells)
+        })
+        .collect();
+    DocGroup(Attrs::Table, rows)
+}
+
+/// Generates the contents of the root, a list item, or a block quote.
+fn random_blocks<R: Rng>(rng: &mut R, depth: usize) -> DocSpan<RtfSchema> {
+    let mut span = vec![];
+    for _ in 0..rng.gen_range(1, 4) {
+        span.push(match rng.gen_range(0, 10) {
+            0 => DocGroup(Attrs::Rule, vec![]),
+            1 | 2 if depth < 2 => DocGroup(random_list_attrs(rng), random_blocks(rng, depth + 1)),
+            3 if depth < 2 => DocGroup(Attrs::BlockQuote, random_blocks(rng, depth + 1)),
+            4 if depth < 2 => random_table(rng),
+            5 => random_html(rng),
+            _ => DocGroup(random_block_attrs(rng), random_inlines(rng)),
+        });
+    }
+    span
+}
+
+/// Generates a random valid document.
+pub fn random_doc<R: Rng>(rng: &mut R) -> Doc<RtfSchema> {
+    Doc(random_blocks(rng, 0))
+}
+
+fn track(attrs: &Attrs) -> RtfTrack {
+    RtfSchema::track_type_from_attrs(attrs).unwrap()
+}
+
+fn random_del_inlines<R: Rng>(rng: &mut R, span: &DocSpan<RtfSchema>) -> DelSpan<RtfSchema> {
+    let mut del: DelSpan<RtfSchema> = vec![];
+    for elem in span {
         match *elem {
-            DocText(ref value) => {
-                let mut n = 0;
-                let max = value.chars().count();
-
-                // Iterate up to `max` characters.
-                while n < max {
-                    // Skip a random number of characters.
-                    let slice = rng.gen_range(1, max - n + 1);
-                    res.place(&AddSkip(slice));
-                    n += slice;
-
-                    // Decide whether to add new characters or a new (empty) group.
-                    if n < max || rng.gen_weighted_bool(2) {
-                        if rng.gen_weighted_bool(2) {
-                            let len = rng.gen_range(1, 5);
-                            res.place(&AddText(rng.gen_ascii_chars().take(len).collect()));
-                        } else {
-                            res.place(&AddGroup(HashMap::new(), vec![]));
-                        }
+            DocText(_, ref text) => {
+                for _ in 0..text.char_len() {
+                    match rng.gen_range(0, 6) {
+                        0 => del.place(&DelText(1)),
+                        1 => del.place(&DelStyles(1, random_nonempty_styles(rng))),
+                        _ => del.place(&DelSkip(1)),
                     }
                 }
             }
-            DocGroup(_, ref span) => {
-                if rng.gen_weighted_bool(2) {
-                    res.place(&AddWithGroup(random_add_span(span)));
+            DocGroup(..) => {
+                if rng.gen_weighted_bool(3) {
+                    del.place(&DelObject);
                 } else {
-                    res.place(&AddSkip(1));
+                    del.place(&DelSkip(1));
                 }
             }
         }
     }
-    // for _ in 0..rng.gen_range(1, 2) {
-    // 	match rng.gen_range(0, 3) {
-    // 		0 => { add_place_any(&mut res, &AddSkip(1)); },
-    // 		1 => { add_place_any(&mut res, &AddGroup(HashMap::new(), vec![])); },
-    // 		2 => { add_place_any(&mut res, &AddText(rng.gen_ascii_chars().take(3).collect())); },
-    // 		_ => {},
-    // 	}
-    // }
+    del
+}
+
+fn random_del_blocks<R: Rng>(rng: &mut R, span: &DocSpan<RtfSchema>) -> DelSpan<RtfSchema> {
+    let mut del: DelSpan<RtfSchema> = vec![];
+    for elem in span {
+        match *elem {
+            DocGroup(ref attrs, ref inner) => match (track(attrs), rng.gen_range(0, 6)) {
+                (_, 0) => del.place(&DelGroupAll),
+                (RtfTrack::Blocks, 1) | (RtfTrack::Blocks, 2) => {
+                    del.place(&DelWithGroup(random_del_inlines(rng, inner)));
+                }
+                (RtfTrack::ListItems, 1) => {
+                    del.place(&DelGroup(random_del_blocks(rng, inner)));
+                }
+                (RtfTrack::ListItems, 2) => {
+                    del.place(&DelWithGroup(random_del_blocks(rng, inner)));
+                }
+                _ => del.place(&DelSkip(1)),
+            },
+            DocText(..) => unreachable!("text outside of a block"),
+        }
+    }
+    del
+}
+
+fn random_add_inline<R: Rng>(rng: &mut R, add: &mut AddSpan<RtfSchema>) {
+    match rng.gen_range(0, 8) {
+        0 => add.place(&AddGroup(random_caret(rng), vec![])),
+        1 | 2 => add.place(&AddText(random_styles(rng), random_text(rng))),
+        _ => {}
+    }
+}
+
+fn random_add_inlines<R: Rng>(rng: &mut R, span: &DocSpan<RtfSchema>) -> AddSpan<RtfSchema> {
+    let mut add = vec![];
+    for elem in span {
+        match *elem {
+            DocText(_, ref text) => {
+                for _ in 0..text.char_len() {
+                    random_add_inline(rng, &mut add);
+                    if rng.gen_weighted_bool(6) {
+                        add.place(&AddStyles(1, random_nonempty_styles(rng)));
+                    } else {
+                        add.place(&AddSkip(1));
+                    }
+                }
+            }
+            DocGroup(..) => {
+                random_add_inline(rng, &mut add);
+                add.place(&AddSkip(1));
+            }
+        }
+    }
+    random_add_inline(rng, &mut add);
+    add
+}
+
+fn random_add_block<R: Rng>(rng: &mut R, add: &mut AddSpan<RtfSchema>) {
+    match rng.gen_range(0, 10) {
+        0 => add.place(&AddGroup(Attrs::Rule, vec![])),
+        1 => {
+            let text = AddText(random_styles(rng), random_text(rng));
+            add.place(&AddGroup(random_block_attrs(rng), vec![text]));
+        }
+        _ => {}
+    }
+}
+
+fn random_add_blocks<R: Rng>(rng: &mut R, span: &DocSpan<RtfSchema>) -> AddSpan<RtfSchema> {
+    let mut add = vec![];
+    for elem in span {
+        random_add_block(rng, &mut add);
+        match *elem {
+            DocGroup(ref attrs, ref inner) => match (track(attrs), rng.gen_range(0, 6)) {
+                (RtfTrack::Blocks, 0) => {
+                    let inner_add = random_add_inlines(rng, inner);
+                    add.place(&AddAttrs(random_block_attrs(rng), inner_add));
+                }
+                (RtfTrack::Blocks, 1) | (RtfTrack::Blocks, 2) => {
+                    add.place(&AddWithGroup(random_add_inlines(rng, inner)));
+                }
+                (RtfTrack::ListItems, 0) => {
+                    let inner_add = random_add_blocks(rng, inner);
+                    add.place(&AddAttrs(random_list_attrs(rng), inner_add));
+                }
+                (RtfTrack::ListItems, 1) | (RtfTrack::ListItems, 2) => {
+                    add.place(&AddWithGroup(random_add_blocks(rng, inner)));
+                }
+                (_, 3) => {
+                    // Wrap this block in a new list item.
+                    add.place(&AddGroup(random_list_attrs(rng), vec![AddSkip(1)]));
+                }
+                _ => add.place(&AddSkip(1)),
+            },
+            DocText(..) => unreachable!("text outside of a block"),
+        }
+    }
+    random_add_block(rng, &mut add);
+    add
+}
+
+/// Generates a random operation which can be applied to `doc`, and leaves it
+/// valid.
+pub fn random_op<R: Rng>(rng: &mut R, doc: &Doc<RtfSchema>) -> Op<RtfSchema> {
+    let del = random_del_blocks(rng, &doc.0);
+    let deleted = apply_delete(&doc.0, &del);
+    let add = random_add_blocks(rng, &deleted);
+    normalize(Op(del, add))
+}
+
+/// A property the operations in a `Case` should satisfy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Property {
+    /// Applying an operation leaves a valid document.
+    Apply,
+    /// Two concurrent operations converge once transformed (TP1), and leave a
+    /// valid document.
+    Transform,
+    /// Composing three consecutive operations is associative.
+    Compose,
+}
+
+/// A document and the operations a property is checked against.
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub doc: Doc<RtfSchema>,
+    pub ops: Vec<Op<RtfSchema>>,
+}
+
+/// Generates a case for a property.
+pub fn random_case<R: Rng>(rng: &mut R, property: Property) -> Case {
+    let doc = random_doc(rng);
+    let ops = match property {
+        Property::Apply => vec![random_op(rng, &doc)],
+        Property::Transform => vec![random_op(rng, &doc), random_op(rng, &doc)],
+        Property::Compose => {
+            let a = random_op(rng, &doc);
+            let doc_a = Op::apply(&doc, &a);
+            let b = random_op(rng, &doc_a);
+            let doc_b = Op::apply(&doc_a, &b);
+            let c = random_op(rng, &doc_b);
+            vec![a, b, c]
+        }
+    };
+    Case { doc, ops }
+}
+
+fn check_apply(doc: &Doc<RtfSchema>, op: &Op<RtfSchema>) -> Result<Doc<RtfSchema>, Error> {
+    let result = Op::try_apply(doc, op)?;
+    validate_doc(&result)?;
+    Ok(result)
+}
+
+/// Whether the operations in `case` fit its document: concurrently for
+/// transforms, one after another otherwise.
+fn is_well_formed(property: Property, case: &Case) -> bool {
+    let res = panic::catch_unwind(|| -> Result<(), Error> {
+        validate_doc(&case.doc)?;
+        let mut doc = case.doc.clone();
+        for op in &case.ops {
+            let result = check_apply(&doc, op)?;
+            if property != Property::Transform {
+                doc = result;
+            }
+        }
+        Ok(())
+    });
+    match res {
+        Ok(Ok(())) => true,
+        _ => false,
+    }
+}
+
+fn check_inner(property: Property, case: &Case) -> Result<(), Error> {
+    let doc = &case.doc;
+    match (property, case.ops.as_slice()) {
+        (Property::Apply, [a]) => {
+            check_apply(doc, a)?;
+        }
+        (Property::Transform, [a, b]) => {
+            let (a_, b_) = Op::try_transform(a, b)?;
+            let doc_a = check_apply(&check_apply(doc, a)?, &a_)?;
+            let doc_b = check_apply(&check_apply(doc, b)?, &b_)?;
+            ensure!(doc_a == doc_b, "Transformed operations did not converge");
+        }
+        (Property::Compose, [a, b, c]) => {
+            let sequential = check_apply(&check_apply(&check_apply(doc, a)?, b)?, c)?;
+            let left = Op::try_compose(&Op::try_compose(a, b)?, c)?;
+            let right = Op::try_compose(a, &Op::try_compose(b, c)?)?;
+            ensure!(
+                check_apply(doc, &left)? == sequential,
+                "(a : b) : c does not match a : b : c"
+            );
+            ensure!(
+                check_apply(doc, &right)? == sequential,
+                "a : (b : c) does not match a : b : c"
+            );
+        }
+        _ => bail!("Wrong number of operations for {:?}", property),
+    }
+    Ok(())
+}
+
+/// Checks that `case` satisfies `property`. Panics are reported as failures.
+pub fn check(property: Property, case: &Case) -> Result<(), Error> {
+    match panic::catch_unwind(|| check_inner(property, case)) {
+        Ok(res) => res,
+        Err(_) => bail!("Panicked while checking {:?}", property),
+    }
+}
+
+/// Returns each way of replacing one element of `span` with something simpler.
+fn simplify_del(span: &DelSpan<RtfSchema>) -> Vec<DelSpan<RtfSchema>> {
+    let mut res = vec![];
+    for (i, elem) in span.iter().enumerate() {
+        let replacements: Vec<DelSpan<RtfSchema>> = match *elem {
+            DelSkip(..) => vec![],
+            DelText(count) | DelStyles(count, _) | DelMany(count) => vec![vec![DelSkip(count)]],
+            DelGroupAll | DelObject => vec![vec![DelSkip(1)]],
+            DelWithGroup(ref inner) => {
+                let mut list = vec![vec![DelSkip(1)]];
+                list.extend(
+                    simplify_del(inner)
+                        .into_iter()
+                        .map(|x| vec![DelWithGroup(x)]),
+                );
+                list
+            }
+            DelGroup(ref inner) => {
+                let mut list = vec![vec![DelWithGroup(inner.clone())]];
+                list.extend(simplify_del(inner).into_iter().map(|x| vec![DelGroup(x)]));
+                list
+            }
+        };
+        for replacement in replacements {
+            let mut del: DelSpan<RtfSchema> = vec![];
+            del.place_all(&span[..i]);
+            del.place_all(&replacement);
+            del.place_all(&span[i + 1..]);
+            res.push(del);
+        }
+    }
     res
 }
 
-pub fn random_del_span(input: &DocSpan) -> DelSpan {
-    let mut rng = thread_rng();
+/// Returns each way of replacing one element of `span` with something simpler.
+fn simplify_add(span: &AddSpan<RtfSchema>) -> Vec<AddSpan<RtfSchema>> {
+    let mut res = vec![];
+    for (i, elem) in span.iter().enumerate() {
+        let replacements: Vec<AddSpan<RtfSchema>> = match *elem {
+            AddSkip(..) => vec![],
+            AddText(..) => vec![vec![]],
+            AddStyles(count, _) => vec![vec![AddSkip(count)]],
+            AddWithGroup(ref inner) => {
+                let mut list = vec![vec![AddSkip(1)]];
+                list.extend(
+                    simplify_add(inner)
+                        .into_iter()
+                        .map(|x| vec![AddWithGroup(x)]),
+                );
+                list
+            }
+            AddAttrs(ref attrs, ref inner) => {
+                let mut list = vec![vec![AddWithGroup(inner.clone())]];
+                list.extend(
+                    simplify_add(inner)
+                        .into_iter()
+                        .map(|x| vec![AddAttrs(attrs.clone(), x)]),
+                );
+                list
+            }
+            AddGroup(ref attrs, ref inner) => {
+                let mut list = vec![inner.clone()];
+                list.extend(
+                    simplify_add(inner)
+                        .into_iter()
+                        .map(|x| vec![AddGroup(attrs.clone(), x)]),
+                );
+                list
+            }
+        };
+        for replacement in replacements {
+            let mut add: AddSpan<RtfSchema> = vec![];
+            add.place_all(&span[..i]);
+            add.place_all(&replacement);
+            add.place_all(&span[i + 1..]);
+            res.push(add);
+        }
+    }
+    res
+}
 
+fn simplify_op(op: &Op<RtfSchema>) -> Vec<Op<RtfSchema>> {
+    let &Op(ref del, ref add) = op;
     let mut res = vec![];
-    for elem in input {
+    if !del.is_empty() || !add.is_empty() {
+        res.push(Op::empty());
+    }
+    res.extend(
+        simplify_del(del)
+            .into_iter()
+            .map(|del| normalize(Op(del, add.clone()))),
+    );
+    res.extend(
+        simplify_add(add)
+            .into_iter()
+            .map(|add| normalize(Op(del.clone(), add))),
+    );
+    res
+}
+
+/// Removes the unit at `path` from a deletion which skips over it, returning
+/// the span and the unit's path in the document left by the deletion.
+fn del_remove(
+    span: &DelSpan<RtfSchema>,
+    path: &[usize],
+) -> Option<(DelSpan<RtfSchema>, Vec<usize>)> {
+    let mut res: DelSpan<RtfSchema> = vec![];
+    let mut offset = 0;
+    let mut removed = 0;
+    let mut inner_path = None;
+    for elem in span {
+        let len = match *elem {
+            DelSkip(count) | DelText(count) | DelStyles(count, _) | DelMany(count) => count,
+            _ => 1,
+        };
+        if inner_path.is_some() || offset + len <= path[0] {
+            // Count what's deleted ahead of the unit, so its path can be
+            // mapped into the document left by the deletion.
+            if inner_path.is_none() {
+                match *elem {
+                    DelText(..) | DelMany(..) | DelGroupAll | DelObject => removed += len,
+                    DelGroup(..) => return None,
+                    _ => {}
+                }
+            }
+            res.place(elem);
+            offset += len;
+            continue;
+        }
+
         match *elem {
-            DocText(ref value) => {
-                let mut n = 0;
-                let max = value.chars().count();
-                while n < max {
-                    if max - n == 1 {
-                        res.push(DelSkip(1));
-                        n += 1;
-                    } else {
-                        let slice = rng.gen_range(2, max - n + 1);
-                        if slice == 2 {
-                            res.push(DelSkip(1));
-                            res.push(DelText(1));
-                            n += 2;
-                        } else {
-                            let keep = rng.gen_range(1, slice - 1);
-                            res.push(DelSkip(keep));
-                            res.push(DelText(slice - keep));
-                            n += slice;
-                        }
+            DelSkip(count) if path.len() == 1 => {
+                if path[0] > offset {
+                    res.place(&DelSkip(path[0] - offset));
+                }
+                if offset + count - path[0] > 1 {
+                    res.place(&DelSkip(offset + count - path[0] - 1));
+                }
+                inner_path = Some(vec![]);
+            }
+            DelSkip(..) => {
+                res.place(elem);
+                inner_path = Some(path[1..].to_vec());
+            }
+            DelWithGroup(ref inner) if path.len() > 1 => {
+                let (inner, rest) = del_remove(inner, &path[1..])?;
+                res.place(&DelWithGroup(inner));
+                inner_path = Some(rest);
+            }
+            _ => return None,
+        }
+        offset += len;
+    }
+
+    let mut result_path = vec![path[0] - removed];
+    result_path.extend(inner_path.unwrap_or_else(|| path[1..].to_vec()));
+    Some((res, result_path))
+}
+
+/// Removes the unit at `path` from an addition which skips over it.
+fn add_remove(span: &AddSpan<RtfSchema>, path: &[usize]) -> Option<AddSpan<RtfSchema>> {
+    let mut res: AddSpan<RtfSchema> = vec![];
+    let mut offset = 0;
+    let mut done = false;
+    for elem in span {
+        let len = match *elem {
+            AddSkip(count) | AddStyles(count, _) => count,
+            AddText(..) => 0,
+            AddWithGroup(..) | AddAttrs(..) => 1,
+            AddGroup(_, ref inner) => inner.skip_pre_len(),
+        };
+        if done || offset + len <= path[0] {
+            res.place(elem);
+            offset += len;
+            continue;
+        }
+
+        match *elem {
+            AddSkip(count) if path.len() == 1 => {
+                if path[0] > offset {
+                    res.place(&AddSkip(path[0] - offset));
+                }
+                if offset + count - path[0] > 1 {
+                    res.place(&AddSkip(offset + count - path[0] - 1));
+                }
+            }
+            AddSkip(..) => res.place(elem),
+            AddWithGroup(ref inner) if path.len() > 1 => {
+                res.place(&AddWithGroup(add_remove(inner, &path[1..])?));
+            }
+            AddAttrs(ref attrs, ref inner) if path.len() > 1 => {
+                res.place(&AddAttrs(attrs.clone(), add_remove(inner, &path[1..])?));
+            }
+            _ => return None,
+        }
+        done = true;
+        offset += len;
+    }
+    Some(res)
+}
+
+/// Removes the unit at `path` from an operation which doesn't touch it.
+fn op_remove(op: &Op<RtfSchema>, path: &[usize]) -> Option<Op<RtfSchema>> {
+    let &Op(ref del, ref add) = op;
+    let (del, add_path) = del_remove(del, path)?;
+    let add = add_remove(add, &add_path)?;
+    Some(normalize(Op(del, add)))
+}
+
+/// Removes the unit at `path` from a document.
+fn doc_remove(span: &DocSpan<RtfSchema>, path: &[usize]) -> DocSpan<RtfSchema> {
+    let mut res: DocSpan<RtfSchema> = vec![];
+    let mut offset = 0;
+    for elem in span {
+        match *elem {
+            DocText(ref styles, ref text) => {
+                let len = text.char_len();
+                if path.len() == 1 && offset <= path[0] && path[0] < offset + len {
+                    let text = text
+                        .as_str()
+                        .chars()
+                        .enumerate()
+                        .filter(|&(i, _)| offset + i != path[0])
+                        .map(|(_, c)| c)
+                        .collect::<String>();
+                    if !text.is_empty() {
+                        res.place(&DocText(styles.clone(), DocString::from_string(text)));
                     }
+                } else {
+                    res.place(elem);
                 }
+                offset += len;
             }
-            DocGroup(_, ref span) => {
-                match rng.gen_range(0, 2) {
-                    0 => res.place(&DelWithGroup(random_del_span(span))),
-                    1 => res.place(&DelSkip(1)),
-                    _ => {
-                        unreachable!();
+            DocGroup(ref attrs, ref inner) => {
+                if offset == path[0] {
+                    if path.len() > 1 {
+                        res.place(&DocGroup(attrs.clone(), doc_remove(inner, &path[1..])));
                     }
+                } else {
+                    res.place(elem);
+                }
+                offset += 1;
+            }
+        }
+    }
+    res
+}
+
+/// Lists the path of every unit in a document, outermost first.
+fn doc_paths(span: &DocSpan<RtfSchema>, prefix: &[usize], res: &mut Vec<Vec<usize>>) {
+    let mut offset = 0;
+    for elem in span {
+        match *elem {
+            DocText(_, ref text) => {
+                for i in 0..text.char_len() {
+                    let mut path = prefix.to_vec();
+                    path.push(offset + i);
+                    res.push(path);
                 }
+                offset += text.char_len();
+            }
+            DocGroup(_, ref inner) => {
+                let mut path = prefix.to_vec();
+                path.push(offset);
+                res.push(path.clone());
+                doc_paths(inner, &path, res);
+                offset += 1;
             }
         }
     }
+}
+
+/// Returns every case one step simpler than `case`.
+fn shrink_candidates(case: &Case) -> Vec<Case> {
+    let mut res = vec![];
+
+    // Remove parts of the document no operation touches.
+    let mut paths = vec![];
+    doc_paths(&case.doc.0, &[], &mut paths);
+    for path in paths {
+        let ops = case
+            .ops
+            .iter()
+            .map(|op| op_remove(op, &path))
+            .collect::<Option<Vec<_>>>();
+        if let Some(ops) = ops {
+            res.push(Case {
+                doc: Doc(doc_remove(&case.doc.0, &path)),
+                ops,
+            });
+        }
+    }
+
+    // Simplify each operation.
+    for (i, op) in case.ops.iter().enumerate() {
+        for simpler in simplify_op(op) {
+            let mut ops = case.ops.clone();
+            ops[i] = simpler;
+            res.push(Case {
+                doc: case.doc.clone(),
+                ops,
+            });
+        }
+    }
+
     res
 }
+
+/// Reduces a case that fails `property` to a minimal one that still fails.
+pub fn shrink(property: Property, mut case: Case) -> Case {
+    loop {
+        let next = shrink_candidates(&case).into_iter().find(|candidate| {
+            is_well_formed(property, candidate) && check(property, candidate).is_err()
+        });
+        match next {
+            Some(next) => case = next,
+            None => return case,
+        }
+    }
+}
+
+/// Serializes a case as a test spec for `transform_test::run_transform_test`.
+pub fn test_spec_ron(property: Property, case: &Case) -> Result<String, Error> {
+    let doc = &case.doc.0;
+    match (property, case.ops.as_slice()) {
+        (Property::Apply, [a]) => apply_test_ron(doc, a),
+        (Property::Transform, [a, b]) => transform_test_ron(doc, a, b),
+        (Property::Compose, [a, b, c]) => compose_test_ron(doc, a, b, c),
+        _ => bail!("Wrong number of operations for {:?}", property),
+    }
+}
@@ -15,18 +15,47 @@ use std::fmt;
 pub enum Attrs {
     Header(u8),
     Para,
-    Code,
+    /// A code block, with the language (like "rust") its code is
+    /// highlighted as.
+    Code(Option<String>),
     Html,
     ListItem,
+    /// A numbered list item. Holds the number its list starts counting from,
+    /// which is taken from the first item in a run of numbered items.
+    OrderedListItem(u64),
+    /// A bulleted list item with a checkbox. Checking it only changes its
+    /// attributes, so it doesn't conflict with edits to the item's text.
+    TaskListItem {
+        checked: bool,
+    },
+    BlockQuote,
     Rule,
-    Caret { client_id: String, focus: bool },
+    /// A table, made of rows of cells. The first row is its header.
+    Table,
+    TableRow,
+    /// A table cell, which holds blocks like a list item does.
+    TableCell,
+    Caret {
+        client_id: String,
+        focus: bool,
+    },
+    /// An inline image, with the text to show in its place if it can't be
+    /// displayed.
+    Image {
+        src: String,
+        alt: String,
+    },
 }
 
-#[repr(u8)]
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Clone)]
 pub enum RtfStyle {
     Bold,
     Italic,
+    /// Inline code, shown in a monospace font.
+    Code,
+    Strikethrough,
+    /// A hyperlink to the contained URL.
+    Link(String),
 }
 
 // impl Hash for RtfStyle {
@@ -37,8 +66,12 @@ pub enum RtfStyle {
 
 impl fmt::Display for RtfStyle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Use the Debug implementation for Display.
-        fmt::Debug::fmt(self, f)
+        match self {
+            // Display only the kind of style, not the URL.
+            RtfStyle::Link(_) => write!(f, "Link"),
+            // Use the Debug implementation for Display.
+            _ => fmt::Debug::fmt(self, f),
+        }
     }
 }
 
@@ -65,6 +98,33 @@ impl StyleSet {
     pub fn contains(&self, style: &RtfStyle) -> bool {
         self.0.contains(style)
     }
+
+    /// Returns the link styles in this set.
+    pub fn links(&self) -> StyleSet {
+        StyleSet(
+            self.0
+                .iter()
+                .filter(|style| match style {
+                    RtfStyle::Link(_) => true,
+                    _ => false,
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Returns the URL this text links to. Two clients linking the same text
+    /// concurrently leaves it with both links, so the first URL in sort order
+    /// is picked to show the same link everywhere.
+    pub fn link(&self) -> Option<&str> {
+        self.0
+            .iter()
+            .filter_map(|style| match style {
+                RtfStyle::Link(url) => Some(url.as_str()),
+                _ => None,
+            })
+            .min()
+    }
 }
 
 impl Default for StyleSet {
@@ -119,12 +179,15 @@ impl StyleTrait for StyleSet {
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum RtfTrack {
-    ListItems,     // bullet
+    ListItems,     // bullet, ordered
     BlockQuotes,   // blockquote
+    Tables,        // table
+    TableRows,     // tr
+    TableCells,    // td
     Blocks,        // h1, h2, h3, h4, h5, h6, p, pre
     BlockObjects,  // hr
     Inlines,       // span
-    InlineObjects, // caret
+    InlineObjects, // caret, img
 }
 
 impl Track for RtfTrack {
@@ -155,7 +218,7 @@ impl Track for RtfTrack {
     fn allowed_in_root(&self) -> bool {
         use self::RtfTrack::*;
         match *self {
-            Blocks | ListItems | BlockObjects => true,
+            Blocks | ListItems | BlockQuotes | BlockObjects | Tables => true,
             _ => false,
         }
     }
@@ -175,7 +238,10 @@ impl Track for RtfTrack {
         match *self {
             ListItems => vec![ListItems, BlockQuotes],
             BlockQuotes => vec![ListItems, BlockQuotes],
-            Blocks => vec![ListItems, BlockQuotes],
+            Tables => vec![ListItems, BlockQuotes],
+            TableRows => vec![Tables],
+            TableCells => vec![TableRows],
+            Blocks => vec![ListItems, BlockQuotes, TableCells],
             BlockObjects => vec![ListItems, BlockQuotes],
             Inlines | InlineObjects => vec![Blocks],
         }
@@ -188,9 +254,21 @@ impl Track for RtfTrack {
         match *self {
             ListItems => vec![ListItems, BlockQuotes],
             BlockQuotes => vec![ListItems, BlockQuotes],
-            Blocks => vec![ListItems, BlockObjects],
+            Tables => vec![ListItems, BlockQuotes],
+            TableRows => vec![ListItems, BlockQuotes, Tables],
+            TableCells => vec![ListItems, BlockQuotes, Tables, TableRows],
+            Blocks => vec![ListItems, BlockQuotes, BlockObjects, Tables, TableRows, TableCells],
             BlockObjects => vec![ListItems, BlockQuotes],
-            Inlines | InlineObjects => vec![ListItems, BlockQuotes, Blocks],
+            Inlines | InlineObjects => {
+                vec![
+                    ListItems,
+                    BlockQuotes,
+                    Tables,
+                    TableRows,
+                    TableCells,
+                    Blocks,
+                ]
+            }
         }
     }
 }
@@ -210,15 +288,25 @@ impl Schema for RtfSchema {
     }
 
     fn merge_attrs(_a: &Attrs, _b: &Attrs) -> Option<Attrs> {
+        // The transform only converges for concurrently wrapped groups when
+        // the first operation's attributes win, so never merge.
         None
     }
 
     fn track_type_from_attrs(attrs: &Attrs) -> Option<Self::Track> {
         match attrs {
-            Attrs::ListItem => Some(RtfTrack::ListItems),
-            Attrs::Para | Attrs::Header(..) | Attrs::Code | Attrs::Html => Some(RtfTrack::Blocks),
+            Attrs::ListItem | Attrs::OrderedListItem(..) | Attrs::TaskListItem { .. } => {
+                Some(RtfTrack::ListItems)
+            }
+            Attrs::BlockQuote => Some(RtfTrack::BlockQuotes),
+            Attrs::Table => Some(RtfTrack::Tables),
+            Attrs::TableRow => Some(RtfTrack::TableRows),
+            Attrs::TableCell => Some(RtfTrack::TableCells),
+            Attrs::Para | Attrs::Header(..) | Attrs::Code(..) | Attrs::Html => {
+                Some(RtfTrack::Blocks)
+            }
             // "span" => Some(RtfTrack::Inlines),
-            Attrs::Caret { .. } => Some(RtfTrack::InlineObjects),
+            Attrs::Caret { .. } | Attrs::Image { .. } => Some(RtfTrack::InlineObjects),
             Attrs::Rule => Some(RtfTrack::BlockObjects),
             // _ => None,
         }
@@ -130,7 +130,9 @@ impl<S: Schema> AddStepper<S> {
         let head = self.head.clone();
         self.stack.push(self.rest.clone());
         match head {
-            Some(AddGroup(_, ref span)) | Some(AddWithGroup(ref span)) => {
+            Some(AddGroup(_, ref span))
+            | Some(AddWithGroup(ref span))
+            | Some(AddAttrs(_, ref span)) => {
                 self.head = None;
                 self.rest = span.to_vec();
                 self.next();
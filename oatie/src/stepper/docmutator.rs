@@ -77,6 +77,11 @@ pub trait DocMutator<S: Schema> {
         unimplemented!();
     }
 
+    /// Replaces the attributes of the group that was just exited.
+    fn ReplaceAttrs(&mut self, _attrs: S::GroupProperties) {
+        unimplemented!();
+    }
+
     fn skip(&mut self, _count: usize) {
         unimplemented!();
     }
@@ -118,6 +123,10 @@ impl<S: Schema> DocMutator<S> for NullDocMutator {
         // no-op
     }
 
+    fn ReplaceAttrs(&mut self, _attrs: S::GroupProperties) {
+        // no-op
+    }
+
     fn skip(&mut self, _count: usize) {
         // no-op
     }
@@ -132,6 +141,7 @@ pub enum Bytecode {
     DeleteElements(usize),
     InsertDocString(DocString, serde_json::Value),
     WrapPrevious(usize, serde_json::Value),
+    ReplaceAttrs(serde_json::Value),
     UnwrapSelf,
     JoinTextLeft,
 }
@@ -307,6 +317,14 @@ impl<'a, S: Schema> DocMutator<S> for RecordingDocMutator<'a, S> {
         self.writer.wrap_previous(count, attrs);
     }
 
+    fn ReplaceAttrs(&mut self, attrs: S::GroupProperties) {
+        self.bc.place(Bytecode::ReplaceAttrs(json!(attrs)));
+
+        // No-op stepper
+
+        self.writer.replace_attrs(attrs);
+    }
+
     fn skip(&mut self, count: usize) {
         let last_index = self.stepper.head_index();
 
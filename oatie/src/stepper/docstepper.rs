@@ -178,7 +178,9 @@ impl<'a, S: Schema> DocStepper<'a, S> {
             }
         }
 
-        self.current().1.get((self.head_index() - 1) as usize)
+        self.head_index()
+            .checked_sub(1)
+            .and_then(|index| self.current().1.get(index))
     }
 
     pub fn peek(&self) -> Option<DocElement<S>> {
@@ -1,7 +1,9 @@
 //! Helper methods for performing transform tests.
 
 use super::compose;
+use super::diff::diff;
 use super::doc::*;
+use super::invert::invert;
 use super::normalize::*;
 use super::parse::debug_pretty;
 use super::transform::*;
@@ -33,6 +35,16 @@ enum TestSpec<S: Schema> {
         doc: Option<DocSpan<S>>,
         op_a: Option<Op<S>>,
     },
+    ComposeTest {
+        doc: DocSpan<S>,
+        a: Op<S>,
+        b: Op<S>,
+        c: Op<S>,
+    },
+    ApplyTest {
+        doc: DocSpan<S>,
+        a: Op<S>,
+    },
 }
 
 use self::TestSpec::*;
@@ -64,24 +76,29 @@ fn op_transform_compare<S: Schema>(a: &Op<S>, b: &Op<S>) -> (Op<S>, Op<S>, Op<S>
     (a_, b_, a_res, b_res)
 }
 
+/// Splits a test file into its `name: [...]` entries.
+fn parse_named_entries(input: &str) -> HashMap<String, String> {
+    let re = Regex::new(r"(\n|^)(\w+):([\n\w\W]+?)(\n(?:\w)|(\n\]))").unwrap();
+    re.captures_iter(&input)
+        .map(|cap| {
+            let name = cap[2].to_string();
+            let end_cap = cap.get(5).map(|x| x.as_str()).unwrap_or("");
+            let body = [&cap[3], end_cap].join("");
+            (name, body)
+        })
+        .collect()
+}
+
 fn parse_transform_test(input: &str) -> Result<TestSpec<RtfSchema>, Error> {
-    Ok(if input.find("TransformTest").is_some() {
+    let is_ron = input.find("TransformTest").is_some()
+        || input.find("ComposeTest").is_some()
+        || input.find("ApplyTest").is_some();
+    Ok(if is_ron {
         // ron-defined test specs
         ron::de::from_str::<TestSpec<RtfSchema>>(input)?
     } else {
         // line by line
-        let mut test: HashMap<String, String> = HashMap::new();
-        let re = Regex::new(r"(\n|^)(\w+):([\n\w\W]+?)(\n(?:\w)|(\n\]))").unwrap();
-        let res: HashMap<String, String> = re
-            .captures_iter(&input)
-            .map(|cap| {
-                let name = cap[2].to_string();
-                let end_cap = cap.get(5).map(|x| x.as_str()).unwrap_or("");
-                let body = [&cap[3], end_cap].join("");
-                (name, body)
-            })
-            .collect();
-        test.extend(res);
+        let mut test: HashMap<String, String> = parse_named_entries(input);
 
         // Attempt old-style transform test which matches by line.
         if test.len() == 0 {
@@ -132,6 +149,85 @@ fn parse_transform_test(input: &str) -> Result<TestSpec<RtfSchema>, Error> {
     })
 }
 
+/// Serializes a transform test, in the form `run_transform_test` loads.
+pub fn transform_test_ron(
+    doc: &DocSpan<RtfSchema>,
+    a: &Op<RtfSchema>,
+    b: &Op<RtfSchema>,
+) -> Result<String, Error> {
+    let test = TransformTest {
+        a: a.clone(),
+        b: b.clone(),
+        doc: doc.clone(),
+    };
+    Ok(ron::ser::to_string_pretty(&test, Default::default())?)
+}
+
+/// Serializes a compose test, in the form `run_transform_test` loads.
+pub fn compose_test_ron(
+    doc: &DocSpan<RtfSchema>,
+    a: &Op<RtfSchema>,
+    b: &Op<RtfSchema>,
+    c: &Op<RtfSchema>,
+) -> Result<String, Error> {
+    let test = ComposeTest {
+        doc: doc.clone(),
+        a: a.clone(),
+        b: b.clone(),
+        c: c.clone(),
+    };
+    Ok(ron::ser::to_string_pretty(&test, Default::default())?)
+}
+
+/// Serializes an apply test, in the form `run_transform_test` loads.
+pub fn apply_test_ron(doc: &DocSpan<RtfSchema>, a: &Op<RtfSchema>) -> Result<String, Error> {
+    let test = ApplyTest {
+        doc: doc.clone(),
+        a: a.clone(),
+    };
+    Ok(ron::ser::to_string_pretty(&test, Default::default())?)
+}
+
+/// Checks that applying an operation to a valid document leaves it valid.
+fn run_apply_test<S: Schema>(doc: &Doc<S>, a: &Op<S>) -> Result<(), Error> {
+    println!("{}", Paint::red("(!) applying op..."));
+    validate_doc_span(&mut ValidateContext::new(), &doc.0)?;
+
+    let doc_a = Op::try_apply(doc, a)?;
+    println!(" ---> doc a : a\n{:?}", doc_a);
+    validate_doc_span(&mut ValidateContext::new(), &doc_a.0)?;
+    println!("ok");
+    println!();
+
+    println!("{}", Paint::green("(!) done."));
+
+    Ok(())
+}
+
+/// Checks that composing three consecutive operations in either grouping
+/// matches applying them one after another.
+fn run_compose_test<S: Schema>(doc: &Doc<S>, a: &Op<S>, b: &Op<S>, c: &Op<S>) -> Result<(), Error> {
+    println!("{}", Paint::red("(!) comparing composition orders..."));
+    validate_doc_span(&mut ValidateContext::new(), &doc.0)?;
+
+    let sequential = Op::apply(&Op::apply(&Op::apply(doc, a), b), c);
+    let left = Op::apply(doc, &Op::compose(&Op::compose(a, b), c));
+    let right = Op::apply(doc, &Op::compose(a, &Op::compose(b, c)));
+
+    println!(" --> a : b : c\n{:?}", sequential);
+    println!(" --> (a : b) : c\n{:?}", left);
+    println!(" --> a : (b : c)\n{:?}", right);
+
+    ensure!(left == sequential, "(a : b) : c does not match a : b : c");
+    ensure!(right == sequential, "a : (b : c) does not match a : b : c");
+    println!("ok");
+    println!();
+
+    println!("{}", Paint::green("(!) done."));
+
+    Ok(())
+}
+
 // TODO this method should take a generic Schema type
 pub fn run_transform_test(input: &str) -> Result<(), Error> {
     let mut test = parse_transform_test(input)?;
@@ -148,6 +244,12 @@ pub fn run_transform_test(input: &str) -> Result<(), Error> {
                 op_a: None,
             };
         }
+        ComposeTest { doc, a, b, c } => {
+            return run_compose_test::<RtfSchema>(&Doc(doc), &a, &b, &c);
+        }
+        ApplyTest { doc, a } => {
+            return run_apply_test::<RtfSchema>(&Doc(doc), &a);
+        }
         _ => {}
     }
 
@@ -247,3 +349,152 @@ pub fn run_transform_test(input: &str) -> Result<(), Error> {
         _ => unreachable!(),
     }
 }
+
+/// Checks that an operation applied to a document can be reverted by its
+/// inverse.
+fn op_invert_compare<S: Schema>(doc: &Doc<S>, op: &Op<S>) -> Result<Doc<S>, Error> {
+    let inverse = invert(&doc.0, op);
+
+    println!(" --> op\n{:?}", op);
+    println!(" --> inverse\n{:?}", inverse);
+
+    let doc_op = Op::apply(doc, op);
+    let doc_reverted = Op::apply(&doc_op, &inverse);
+    println!("{:?}", doc_reverted);
+
+    ensure!(
+        doc_reverted == *doc,
+        "Inverted op did not restore the original document"
+    );
+
+    Ok(doc_op)
+}
+
+// TODO this method should take a generic Schema type
+pub fn run_invert_test(input: &str) -> Result<(), Error> {
+    let test = parse_transform_test(input)?;
+
+    let (a, b, doc) = match test {
+        TransformTest { a, b, doc } => (a, b, Some(doc)),
+        TransformTestConfigurable { a, b, doc, .. } => (a, b, doc),
+        ComposeTest { .. } | ApplyTest { .. } => return Ok(()),
+    };
+
+    // Inverting an op requires the document it was applied to.
+    let doc = match doc {
+        Some(doc) => Doc(doc),
+        None => return Ok(()),
+    };
+
+    let (a_, b_) = transform::<RtfSchema>(&a, &b);
+
+    println!("{}", Paint::red("(!) inverting first ops..."));
+    let doc_a = op_invert_compare(&doc, &a)?;
+    let doc_b = op_invert_compare(&doc, &b)?;
+    println!("ok");
+    println!();
+
+    println!("{}", Paint::red("(!) inverting transformed ops..."));
+    op_invert_compare(&doc_a, &a_)?;
+    op_invert_compare(&doc_b, &b_)?;
+    println!("ok");
+    println!();
+
+    println!("{}", Paint::green("(!) done."));
+
+    Ok(())
+}
+
+fn op_diff_compare<S: Schema>(from: &Doc<S>, to: &Doc<S>) -> Result<(), Error> {
+    let op = diff(from, to);
+
+    println!(" --> diff\n{:?}", op);
+
+    let doc_op = Op::apply(from, &op);
+    ensure!(
+        doc_op == *to,
+        "Applying the diff did not reproduce the target document"
+    );
+
+    Ok(())
+}
+
+// TODO this method should take a generic Schema type
+pub fn run_diff_test(input: &str) -> Result<(), Error> {
+    let test = parse_transform_test(input)?;
+
+    let (a, b, doc) = match test {
+        TransformTest { a, b, doc } => (a, b, Some(doc)),
+        TransformTestConfigurable { a, b, doc, .. } => (a, b, doc),
+        ComposeTest { .. } | ApplyTest { .. } => return Ok(()),
+    };
+
+    let doc = match doc {
+        Some(doc) => Doc(doc),
+        None => return Ok(()),
+    };
+
+    let (a_, _) = transform::<RtfSchema>(&a, &b);
+    let doc_a = Op::apply(&doc, &a);
+    let doc_b = Op::apply(&doc, &b);
+    let doc_merged = Op::apply(&doc_a, &a_);
+    let docs = vec![doc, doc_a, doc_b, doc_merged];
+
+    println!("{}", Paint::red("(!) diffing every pair of documents..."));
+    for from in &docs {
+        for to in &docs {
+            op_diff_compare(from, to)?;
+        }
+    }
+    println!("ok");
+    println!();
+
+    println!("{}", Paint::green("(!) done."));
+
+    Ok(())
+}
+
+// TODO this method should take a generic Schema type
+pub fn run_cursor_test(input: &str) -> Result<(), Error> {
+    let test = parse_named_entries(input);
+    for key in &["cursor", "del", "add", "left", "right"] {
+        ensure!(
+            test.contains_key(*key),
+            "Missing {:?} entry in cursor test",
+            key
+        );
+    }
+
+    let cursor: CurSpan = ron::de::from_str(&test["cursor"])?;
+    let op: Op<RtfSchema> = Op(
+        crate::deserialize::v1::delspan_ron(&test["del"])?,
+        crate::deserialize::v1::addspan_ron(&test["add"])?,
+    );
+    let left: CurSpan = ron::de::from_str(&test["left"])?;
+    let right: CurSpan = ron::de::from_str(&test["right"])?;
+
+    println!(" --> cursor\n{:?}", cursor);
+    println!(" --> op\n{:?}", op);
+    println!();
+
+    for (gravity, expected) in vec![(Gravity::Left, left), (Gravity::Right, right)] {
+        println!(
+            "{}",
+            Paint::red(format!("(!) transforming with {:?} gravity...", gravity))
+        );
+        let result = transform_cursor(&cursor, &op, gravity);
+        println!(" --> result\n{:?}", result);
+        ensure!(
+            result == expected,
+            "Cursor with {:?} gravity should be {:?}",
+            gravity,
+            expected
+        );
+        println!("ok");
+        println!();
+    }
+
+    println!("{}", Paint::green("(!) done."));
+
+    Ok(())
+}
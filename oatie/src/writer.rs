@@ -46,6 +46,21 @@ impl<S: Schema> DelWriter<S> {
         self.past.place(&DelGroup(past));
     }
 
+    /// Deletes the next group and all of its contents.
+    pub fn group_all(&mut self) {
+        self.past.place(&DelGroupAll);
+    }
+
+    /// Deletes the next `count` characters or groups, including their contents.
+    pub fn many(&mut self, count: usize) {
+        self.past.place(&DelMany(count));
+    }
+
+    /// Deletes the next object.
+    pub fn object(&mut self) {
+        self.past.place(&DelObject);
+    }
+
     pub fn exit_all(&mut self) {
         while !self.stack.is_empty() {
             self.exit();
@@ -257,4 +272,11 @@ impl<S: Schema> DocWriter<S> {
         let group = self.past.split_off(start);
         self.past.push(DocGroup(attrs, group));
     }
+
+    pub(crate) fn replace_attrs(&mut self, attrs: S::GroupProperties) {
+        match self.past.last_mut() {
+            Some(DocGroup(ref mut group_attrs, _)) => *group_attrs = attrs,
+            _ => panic!("expected a group to replace attributes of"),
+        }
+    }
 }
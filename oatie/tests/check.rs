@@ -0,0 +1,283 @@
+#![allow(unused_imports)]
+
+use env_logger;
+#[macro_use]
+extern crate log;
+
+use oatie::check::*;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::*;
+
+pub fn test_start() {
+    if let Ok(_) = env_logger::init() {
+        // good
+    }
+}
+
+fn hello_doc() -> Doc<RtfSchema> {
+    Doc(vec![DocGroup(
+        Attrs::Para,
+        vec![DocText(StyleSet::new(), DocString::from_str("Hello"))],
+    )])
+}
+
+#[test]
+fn try_apply_valid() {
+    test_start();
+
+    let doc = hello_doc();
+    let op = Op(
+        vec![DelWithGroup(vec![DelText(1)])],
+        vec![AddWithGroup(vec![AddText(
+            StyleSet::new(),
+            DocString::from_str("J"),
+        )])],
+    );
+
+    assert_eq!(Op::try_apply(&doc, &op), Ok(Op::apply(&doc, &op)));
+}
+
+#[test]
+fn try_apply_invalid() {
+    test_start();
+
+    let doc = hello_doc();
+
+    assert_eq!(
+        Op::try_apply(&doc, &Op(vec![DelText(1)], vec![])),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedText,
+            path: vec![0],
+        })
+    );
+
+    assert_eq!(
+        Op::try_apply(&doc, &Op(vec![DelWithGroup(vec![DelSkip(6)])], vec![])),
+        Err(OpError {
+            kind: OpErrorKind::Exhausted,
+            path: vec![0, 5],
+        })
+    );
+
+    assert_eq!(
+        Op::try_apply(
+            &doc,
+            &Op(vec![], vec![AddWithGroup(vec![AddWithGroup(vec![])])]),
+        ),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedGroup,
+            path: vec![0, 0],
+        })
+    );
+}
+
+#[test]
+fn try_compose_invalid() {
+    test_start();
+
+    let a: Op<RtfSchema> = Op(
+        vec![],
+        vec![AddText(StyleSet::new(), DocString::from_str("Hi"))],
+    );
+    let b = Op(vec![DelSkip(1), DelGroupAll], vec![]);
+
+    assert_eq!(
+        Op::try_compose(&a, &b),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedGroup,
+            path: vec![1],
+        })
+    );
+}
+
+#[test]
+fn try_transform_invalid() {
+    test_start();
+
+    let a: Op<RtfSchema> = Op(vec![DelText(2)], vec![]);
+    let b = Op(vec![DelSkip(1), DelWithGroup(vec![])], vec![]);
+
+    assert_eq!(
+        Op::try_transform(&a, &b),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedText,
+            path: vec![1],
+        })
+    );
+}
+
+fn bold() -> StyleSet {
+    let mut styles = StyleSet::new();
+    styles.insert(RtfStyle::Bold);
+    styles
+}
+
+#[test]
+fn try_apply_wrong_kind() {
+    test_start();
+
+    let doc = hello_doc();
+
+    // AddStyles on a group.
+    assert_eq!(
+        Op::try_apply(&doc, &Op(vec![], vec![AddStyles(1, bold())])),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedText,
+            path: vec![0],
+        })
+    );
+
+    // AddAttrs on text.
+    assert_eq!(
+        Op::try_apply(
+            &doc,
+            &Op(
+                vec![],
+                vec![AddWithGroup(vec![AddAttrs(Attrs::Para, vec![])])]
+            ),
+        ),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedGroup,
+            path: vec![0, 0],
+        })
+    );
+
+    // DelObject on a group that isn't an object.
+    assert_eq!(
+        Op::try_apply(&doc, &Op(vec![DelObject], vec![])),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedObject,
+            path: vec![0],
+        })
+    );
+}
+
+#[test]
+fn try_apply_object() {
+    test_start();
+
+    let doc: Doc<RtfSchema> = Doc(vec![DocGroup(
+        Attrs::Para,
+        vec![DocGroup(
+            Attrs::Image {
+                src: "cat.png".to_string(),
+                alt: "Cat".to_string(),
+            },
+            vec![],
+        )],
+    )]);
+    let op = Op(vec![DelWithGroup(vec![DelObject])], vec![]);
+
+    assert_eq!(Op::try_apply(&doc, &op), Ok(Op::apply(&doc, &op)));
+}
+
+#[test]
+fn try_transform_add_kinds() {
+    test_start();
+
+    let expected_group = Err(OpError {
+        kind: OpErrorKind::ExpectedGroup,
+        path: vec![0],
+    });
+    let styles: Op<RtfSchema> = Op(vec![], vec![AddStyles(1, bold())]);
+
+    // AddAttrs by AddStyles.
+    let a = Op(vec![], vec![AddAttrs(Attrs::Para, vec![])]);
+    assert_eq!(Op::try_transform(&a, &styles), expected_group);
+
+    // AddWithGroup by AddStyles.
+    let a = Op(vec![], vec![AddWithGroup(vec![])]);
+    assert_eq!(Op::try_transform(&a, &styles), expected_group);
+
+    // DelWithGroup, DelGroup and DelGroupAll by AddStyles.
+    for del in vec![DelWithGroup(vec![]), DelGroup(vec![]), DelGroupAll] {
+        let a = Op(vec![del], vec![]);
+        assert_eq!(Op::try_transform(&a, &styles), expected_group);
+    }
+
+    // DelStyles by AddWithGroup and AddAttrs.
+    let a: Op<RtfSchema> = Op(vec![DelStyles(1, bold())], vec![]);
+    for add in vec![AddWithGroup(vec![]), AddAttrs(Attrs::Para, vec![])] {
+        let b = Op(vec![], vec![add]);
+        assert_eq!(
+            Op::try_transform(&a, &b),
+            Err(OpError {
+                kind: OpErrorKind::ExpectedText,
+                path: vec![0],
+            })
+        );
+    }
+}
+
+#[test]
+fn try_transform_skipped_kinds() {
+    test_start();
+
+    // The addition's expectations are mapped back past what its op deletes.
+    let a: Op<RtfSchema> = Op(vec![DelText(2)], vec![AddStyles(1, bold())]);
+    let b = Op(vec![DelSkip(2), DelWithGroup(vec![])], vec![]);
+
+    assert_eq!(
+        Op::try_transform(&a, &b),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedText,
+            path: vec![2],
+        })
+    );
+
+    // Valid concurrent edits still transform.
+    let a: Op<RtfSchema> = Op(vec![DelText(2)], vec![AddStyles(1, bold())]);
+    let b = Op(vec![DelSkip(2), DelText(1)], vec![]);
+
+    assert_eq!(Op::try_transform(&a, &b), Ok(Op::transform(&a, &b)));
+}
+
+#[test]
+fn try_apply_empty() {
+    test_start();
+
+    let doc = hello_doc();
+
+    assert_eq!(
+        Op::try_apply(&doc, &Op(vec![DelWithGroup(vec![DelSkip(0)])], vec![])),
+        Err(OpError {
+            kind: OpErrorKind::Empty,
+            path: vec![0, 0],
+        })
+    );
+
+    assert_eq!(
+        Op::try_apply(
+            &doc,
+            &Op(
+                vec![],
+                vec![AddWithGroup(vec![AddText(
+                    StyleSet::new(),
+                    DocString::from_str("")
+                )])]
+            )
+        ),
+        Err(OpError {
+            kind: OpErrorKind::Empty,
+            path: vec![0, 0],
+        })
+    );
+}
+
+#[test]
+fn try_transform_unwrapped_group() {
+    test_start();
+
+    // The group's contents aren't known until the transform reaches them.
+    let a: Op<RtfSchema> = Op(vec![DelWithGroup(vec![DelText(1)])], vec![]);
+    let b = Op(vec![DelGroup(vec![])], vec![AddWithGroup(vec![])]);
+
+    assert_eq!(
+        Op::try_transform(&a, &b),
+        Err(OpError {
+            kind: OpErrorKind::ExpectedText,
+            path: vec![],
+        })
+    );
+}
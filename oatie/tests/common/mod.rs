@@ -0,0 +1,39 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use failure::Error;
+use oatie::doc::*;
+use oatie::rtf::*;
+use oatie::validate::validate_doc;
+use std::fs;
+use std::path::Path;
+
+/// Runs `test` on every fixture file in a directory of `oatie/tests`, like
+/// "transform", failing on the first fixture it returns an error for.
+pub fn run_fixtures(dir: &str, test: fn(&str) -> Result<(), Error>) {
+    let root_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(dir);
+
+    for entry in fs::read_dir(&root_path).unwrap() {
+        let entry = entry.unwrap();
+        if entry.metadata().unwrap().is_file() {
+            let value = fs::read_to_string(entry.path()).unwrap();
+            if let Err(err) = test(&value) {
+                panic!("fixture {:?} failed: {:?}", entry.path(), err);
+            }
+        }
+    }
+}
+
+/// Transforms two concurrent operations, checking that both orders of
+/// applying them produce the same valid document, and returns it.
+pub fn converge(doc: &Doc<RtfSchema>, a: &Op<RtfSchema>, b: &Op<RtfSchema>) -> Doc<RtfSchema> {
+    let (a_, b_) = Op::transform(a, b);
+    let doc_a = Op::apply(doc, &Op::compose(a, &a_));
+    let doc_b = Op::apply(doc, &Op::compose(b, &b_));
+    assert_eq!(doc_a, doc_b);
+    validate_doc(&doc_a).unwrap();
+    doc_a
+}
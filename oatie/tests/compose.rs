@@ -32,10 +32,15 @@ fn test_compose_del_del() {
         vec![DelText(12)]
     );
 
-    // assert_eq!(
-    //     compose_del_del(&vec![DelWithGroup(vec![DelText(6)])], &vec![DelGroupAll]),
-    //     vec![DelGroupAll]
-    // );
+    assert_eq!(
+        compose_del_del::<RtfSchema>(&vec![DelWithGroup(vec![DelText(6)])], &vec![DelGroupAll]),
+        vec![DelGroupAll]
+    );
+
+    assert_eq!(
+        compose_del_del::<RtfSchema>(&vec![DelSkip(2), DelMany(2)], &vec![DelMany(3)]),
+        vec![DelMany(5)]
+    );
 
     assert_eq!(
         compose_del_del::<RtfSchema>(
@@ -244,3 +249,38 @@ fn test_compose() {
         ),
     );
 }
+
+#[test]
+fn test_normalize_del_many() {
+    test_start();
+
+    assert_eq!(
+        normalize::<RtfSchema>(Op(
+            vec![
+                DelSkip(1),
+                DelGroupAll,
+                DelMany(2),
+                DelGroupAll,
+                DelSkip(1),
+                DelGroupAll,
+            ],
+            vec![],
+        )),
+        Op(
+            vec![DelSkip(1), DelMany(4), DelSkip(1), DelGroupAll],
+            vec![]
+        ),
+    );
+
+    assert_eq!(
+        normalize::<RtfSchema>(Op(
+            vec![
+                DelWithGroup(vec![DelGroupAll, DelGroupAll]),
+                DelMany(1),
+                DelMany(1),
+            ],
+            vec![],
+        )),
+        Op(vec![DelWithGroup(vec![DelMany(2)]), DelMany(2)], vec![]),
+    );
+}
@@ -0,0 +1,9 @@
+mod common;
+
+use crate::common::run_fixtures;
+use oatie::transform_test::run_cursor_test;
+
+#[test]
+fn main() {
+    run_fixtures("cursor", run_cursor_test);
+}
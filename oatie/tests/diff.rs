@@ -0,0 +1,9 @@
+mod common;
+
+use crate::common::run_fixtures;
+use oatie::transform_test::run_diff_test;
+
+#[test]
+fn main() {
+    run_fixtures("transform", run_diff_test);
+}
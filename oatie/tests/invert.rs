@@ -0,0 +1,30 @@
+mod common;
+
+use crate::common::run_fixtures;
+use oatie::doc::*;
+use oatie::random::*;
+use oatie::transform_test::run_invert_test;
+use rand::{
+    SeedableRng,
+    XorShiftRng,
+};
+
+const ITERATIONS: u32 = 200;
+
+#[test]
+fn main() {
+    run_fixtures("transform", run_invert_test);
+}
+
+#[test]
+fn random_invert_restores_doc() {
+    for seed in 0..ITERATIONS {
+        let mut rng = XorShiftRng::from_seed([seed + 1, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]);
+        let doc = random_doc(&mut rng);
+        let op = random_op(&mut rng, &doc);
+
+        let inverse = Op::invert(&doc, &op);
+        let restored = Op::apply(&Op::apply(&doc, &op), &inverse);
+        assert_eq!(restored, doc, "op {:?} isn't undone for seed {}", op, seed);
+    }
+}
@@ -0,0 +1,58 @@
+use oatie::random::*;
+use oatie::transform_test::run_transform_test;
+use rand::{
+    SeedableRng,
+    XorShiftRng,
+};
+use std::fs;
+
+const ITERATIONS: u32 = 200;
+
+/// Checks a property against randomly generated cases. A failing case is
+/// shrunk and written to target/random-failures/ as a test spec, which can be
+/// copied into oatie/tests/transform/ to keep it as a regression test.
+fn run_property(property: Property) {
+    for seed in 0..ITERATIONS {
+        let mut rng = XorShiftRng::from_seed([seed + 1, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]);
+        let case = random_case(&mut rng, property);
+        if let Err(err) = check(property, &case) {
+            eprintln!("{:?} failed for seed {}: {:?}", property, seed, err);
+            let case = shrink(property, case);
+            let spec = test_spec_ron(property, &case).unwrap();
+
+            let dir = ::std::env::current_exe()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .join("random-failures");
+            let path = dir.join(format!("{:?}-{}", property, seed).to_lowercase());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&path, &spec).unwrap();
+
+            eprintln!("minimal case written to {:?}", path);
+
+            // The spec should reproduce the failure in the regular runner.
+            eprintln!("runner result: {:?}", run_transform_test(&spec));
+            panic!("{:?} failed for seed {}", property, seed);
+        }
+    }
+}
+
+#[test]
+fn random_apply_is_valid() {
+    run_property(Property::Apply);
+}
+
+#[test]
+fn random_transform_converges() {
+    run_property(Property::Transform);
+}
+
+#[test]
+fn random_compose_is_associative() {
+    run_property(Property::Compose);
+}
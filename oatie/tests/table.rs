@@ -0,0 +1,129 @@
+#[macro_use]
+extern crate oatie;
+
+mod common;
+
+use crate::common::converge;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+/// A table with a header row and one body row, each with two cells.
+fn table_doc() -> Doc<RtfSchema> {
+    doc![DocGroup(Attrs::Table, [
+        DocGroup(Attrs::TableRow, [
+            DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("a")])]),
+            DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("b")])]),
+        ]),
+        DocGroup(Attrs::TableRow, [
+            DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("c")])]),
+            DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("d")])]),
+        ]),
+    ])]
+}
+
+#[test]
+fn table_edit_different_cells() {
+    // Type at the start of the first cell, and the end of the second cell.
+    let a = op!(
+        [],
+        [AddWithGroup([AddWithGroup([AddWithGroup([AddWithGroup([
+            AddText("1")
+        ])])])])],
+    );
+    let b = op!(
+        [],
+        [AddWithGroup([AddWithGroup([
+            AddSkip(1),
+            AddWithGroup([AddWithGroup([AddSkip(1), AddText("2")])]),
+        ])])],
+    );
+
+    assert_eq!(
+        converge(&table_doc(), &a, &b),
+        doc![DocGroup(Attrs::Table, [
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("1a")])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("b2")])]),
+            ]),
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("c")])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("d")])]),
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn table_delete_and_edit_different_rows() {
+    // Delete the text of the first cell, and type in the last cell.
+    let a = op!(
+        [DelWithGroup([DelWithGroup([DelWithGroup([DelWithGroup([
+            DelText(1)
+        ])])])])],
+        [],
+    );
+    let b = op!(
+        [],
+        [AddWithGroup([
+            AddSkip(1),
+            AddWithGroup([
+                AddSkip(1),
+                AddWithGroup([AddWithGroup([AddText("3")])]),
+            ]),
+        ])],
+    );
+
+    assert_eq!(
+        converge(&table_doc(), &a, &b),
+        doc![DocGroup(Attrs::Table, [
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("b")])]),
+            ]),
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("c")])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("3d")])]),
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn table_add_row_and_edit_cell() {
+    // Add an empty row after the header, and type in the body row.
+    let a = op!(
+        [],
+        [AddWithGroup([
+            AddSkip(1),
+            AddGroup(Attrs::TableRow, [
+                AddGroup(Attrs::TableCell, [AddGroup(Attrs::Para, [])]),
+                AddGroup(Attrs::TableCell, [AddGroup(Attrs::Para, [])]),
+            ]),
+        ])],
+    );
+    let b = op!(
+        [],
+        [AddWithGroup([
+            AddSkip(1),
+            AddWithGroup([AddWithGroup([AddWithGroup([AddText("4")])])]),
+        ])],
+    );
+
+    assert_eq!(
+        converge(&table_doc(), &a, &b),
+        doc![DocGroup(Attrs::Table, [
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("a")])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("b")])]),
+            ]),
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [])]),
+            ]),
+            DocGroup(Attrs::TableRow, [
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("4c")])]),
+                DocGroup(Attrs::TableCell, [DocGroup(Attrs::Para, [DocText("d")])]),
+            ]),
+        ])]
+    );
+}
@@ -0,0 +1,42 @@
+#[macro_use]
+extern crate oatie;
+
+mod common;
+
+use crate::common::converge;
+use oatie::doc::*;
+use oatie::rtf::*;
+
+fn task_doc(checked: bool) -> Doc<RtfSchema> {
+    doc![DocGroup(Attrs::TaskListItem { checked }, [
+        DocGroup(Attrs::Para, [DocText("milk")]),
+    ])]
+}
+
+#[test]
+fn task_check_and_edit_text() {
+    // Check the task while typing in its text.
+    let a = op!([], [AddAttrs(Attrs::TaskListItem { checked: true }, [])]);
+    let b = op!([], [AddWithGroup([AddWithGroup([AddText("buy ")])])]);
+
+    assert_eq!(
+        converge(&task_doc(false), &a, &b),
+        doc![DocGroup(Attrs::TaskListItem { checked: true }, [
+            DocGroup(Attrs::Para, [DocText("buy milk")]),
+        ])]
+    );
+}
+
+#[test]
+fn task_uncheck_and_delete_text() {
+    // Uncheck the task while deleting its text.
+    let a = op!([], [AddAttrs(Attrs::TaskListItem { checked: false }, [])]);
+    let b = op!([DelWithGroup([DelWithGroup([DelText(4)])])], []);
+
+    assert_eq!(
+        converge(&task_doc(true), &a, &b),
+        doc![DocGroup(Attrs::TaskListItem { checked: false }, [
+            DocGroup(Attrs::Para, []),
+        ])]
+    );
+}